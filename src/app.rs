@@ -1,86 +1,238 @@
+use std::fmt;
 use std::sync::Arc;
+use std::time::Duration;
+
+use web_time::Instant;
 use winit::{
   application::ApplicationHandler,
-  event::*,
-  event_loop::ActiveEventLoop,
-  keyboard::{Key, NamedKey},
-  window::{Window, WindowId}
+  error::EventLoopError,
+  event::{ElementState, KeyEvent, MouseButton, MouseScrollDelta, WindowEvent},
+  event_loop::{ActiveEventLoop, ControlFlow, EventLoop},
+  keyboard::Key,
+  monitor::MonitorHandle,
+  window::{Window, WindowAttributes, WindowId},
 };
 
-use crate::my_lib::State;
+use crate::{RenderError, State, StateError, StateOptions};
+
+// Keyboard and mouse input since the previous frame
+#[derive(Clone, Debug, PartialEq)]
+pub enum InputEvent {
+  KeyPressed { key: Key, repeat: bool },
+  KeyReleased { key: Key },
+  // physical pixels from the top-left corner of the window
+  CursorMoved { x: f64, y: f64 },
+  MousePressed(MouseButton),
+  MouseReleased(MouseButton),
+  MouseWheel(MouseScrollDelta),
+}
+
+// what the update callback gets every frame
+#[derive(Debug)]
+pub struct Frame<'a> {
+  // time since the previous frame, zero for the first one and after the
+  // window was minimized or suspended
+  pub dt: Duration,
+  pub events: &'a [InputEvent],
+}
+
+// returned by the update callback
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Flow {
+  Continue,
+  Exit,
+}
+
+// Why run() stopped other than the callback returning Flow::Exit
+#[derive(Debug)]
+pub enum AppError {
+  EventLoop(EventLoopError),
+  State(StateError),
+  // errors State::recover() can't handle, like running out of memory
+  Render(RenderError),
+}
+
+impl fmt::Display for AppError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      AppError::EventLoop(err) => write!(f, "Event loop failed: {}", err),
+      AppError::State(err) => write!(f, "Failed to set up rendering: {}", err),
+      AppError::Render(err) => write!(f, "Rendering failed: {}", err),
+    }
+  }
+}
+
+impl std::error::Error for AppError {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      AppError::EventLoop(err) => Some(err),
+      AppError::State(err) => Some(err),
+      AppError::Render(err) => Some(err),
+    }
+  }
+}
+
+impl From<EventLoopError> for AppError {
+  fn from(err: EventLoopError) -> Self {
+    AppError::EventLoop(err)
+  }
+}
+
+// Opens a window and calls `update` before rendering every frame, until it
+// returns Flow::Exit or the window is closed:
+//
+//   sotrh::app::run(StateOptions::default(), |state, frame| {
+//     state.camera_mut().eye.x += frame.dt.as_secs_f32();
+//     Flow::Continue
+//   })
+//
+// Resizing, minimization, suspend/resume and lost surfaces are handled here.
+pub fn run(options: StateOptions, update: impl FnMut(&mut State, &Frame) -> Flow + 'static) -> Result<(), AppError> {
+  run_with_attributes(Window::default_attributes().with_title("sotrh"), options, update)
+}
+
+// run() with a custom title, size etc. for the window
+pub fn run_with_attributes(
+  attributes: WindowAttributes,
+  options: StateOptions,
+  update: impl FnMut(&mut State, &Frame) -> Flow + 'static,
+) -> Result<(), AppError> {
+  let event_loop = EventLoop::new()?;
+  // redraws are requested continuously, but nothing spins while minimized
+  event_loop.set_control_flow(ControlFlow::Wait);
+  let mut runner = Runner {
+    attributes,
+    options,
+    update,
+    window: None,
+    state: None,
+    monitor: None,
+    events: Vec::new(),
+    last_frame: None,
+    error: None,
+  };
+  event_loop.run_app(&mut runner)?;
+  match runner.error {
+    Some(err) => Err(err),
+    None => Ok(()),
+  }
+}
 
-#[derive(Default)]
-pub struct App<'window> {
+struct Runner<F> {
+  attributes: WindowAttributes,
+  options: StateOptions,
+  update: F,
   window: Option<Arc<Window>>,
-  object_state: Option<State<'window>>,
-  // a toggle flag used to control the size of the surface
-  flag: bool,
+  state: Option<State>,
+  // the monitor the window was last seen on, to notice moves between monitors
+  monitor: Option<MonitorHandle>,
+  events: Vec<InputEvent>,
+  // None when the next frame's dt should be zero
+  last_frame: Option<Instant>,
+  error: Option<AppError>,
 }
 
-#[cfg(target_arch="wasm32")]
-use wasm_bindgen::prelude::*;
+impl<F: FnMut(&mut State, &Frame) -> Flow> Runner<F> {
+  fn fail(&mut self, event_loop: &ActiveEventLoop, err: AppError) {
+    log::error!("{}", err);
+    self.error = Some(err);
+    event_loop.exit();
+  }
+
+  fn frame(&mut self, event_loop: &ActiveEventLoop) {
+    let Some(state) = self.state.as_mut() else { return };
+    if state.is_minimized() || state.is_suspended() {
+      self.last_frame = None;
+      state.frame_pacing_mut().pause();
+      return;
+    }
+
+    let now = Instant::now();
+    let dt = self.last_frame.map_or(Duration::ZERO, |last| now - last);
+    self.last_frame = Some(now);
+    let flow = (self.update)(state, &Frame { dt, events: &self.events });
+    self.events.clear();
+    if flow == Flow::Exit {
+      event_loop.exit();
+      return;
+    }
+
+    if let Err(err) = state.render_or_recover() {
+      self.fail(event_loop, AppError::Render(err));
+    }
+  }
+}
 
-impl<'window> ApplicationHandler for App<'window> {
+impl<F: FnMut(&mut State, &Frame) -> Flow> ApplicationHandler for Runner<F> {
   fn resumed(&mut self, event_loop: &ActiveEventLoop) {
-    if self.window.is_none() {
-      let win_attr = Window::default_attributes().with_title("App Initialization");
-      // use Arc.
-      let window = Arc::new(
-        event_loop.create_window(win_attr).expect("create window err."),
-      );
-      self.window = Some(window.clone());
-      let object_state = State::new(window.clone());
-      self.object_state = Some(object_state);
+    if let (Some(state), Some(window)) = (self.state.as_mut(), self.window.as_ref()) {
+      // back from suspended(), the window is kept but needs a new surface
+      if let Err(err) = state.resume(window.clone()) {
+        self.fail(event_loop, AppError::State(err));
+      }
+      return;
+    }
+
+    let window = match event_loop.create_window(self.attributes.clone()) {
+      Ok(window) => Arc::new(window),
+      Err(err) => return self.fail(event_loop, AppError::EventLoop(err.into())),
+    };
+    match State::new_with_options(window.clone(), self.options.clone()) {
+      Ok(state) => {
+        self.monitor = window.current_monitor();
+        self.window = Some(window);
+        self.state = Some(state);
+      }
+      Err(err) => self.fail(event_loop, AppError::State(err)),
+    }
+  }
+
+  fn suspended(&mut self, _event_loop: &ActiveEventLoop) {
+    if let Some(state) = self.state.as_mut() {
+      state.suspend();
     }
   }
 
-  // fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
-  //   self.window_event(event_loop, window_id, event)
-  // }
+  fn about_to_wait(&mut self, _event_loop: &ActiveEventLoop) {
+    let (Some(state), Some(window)) = (self.state.as_mut(), self.window.as_ref()) else { return };
+    // keep readbacks moving even when nothing is being redrawn
+    state.poll_outstanding();
+    if !state.is_minimized() && !state.is_suspended() {
+      window.request_redraw();
+    }
+  }
 
-  fn window_event(
-    &mut self,
-    event_loop: &ActiveEventLoop,
-    _window_id: WindowId,
-    event: WindowEvent,
-  ) {
+  fn window_event(&mut self, event_loop: &ActiveEventLoop, _window_id: WindowId, event: WindowEvent) {
+    let (Some(state), Some(window)) = (self.state.as_mut(), self.window.as_ref()) else { return };
     match event {
-      WindowEvent::CloseRequested => {
-        event_loop.exit();
-      }
-      WindowEvent::Resized(new_size) => {
-        if let (Some(object_state), Some(window)) =  (self.object_state.as_mut(), self.window.as_ref()) {
-          object_state.resize((new_size.width, new_size.height).into());
-          window.request_redraw();
+      WindowEvent::CloseRequested => event_loop.exit(),
+      WindowEvent::Resized(size) => state.resize(size),
+      WindowEvent::Moved(_) => {
+        let monitor = window.current_monitor();
+        if monitor != self.monitor {
+          self.monitor = monitor;
+          state.renegotiate_surface();
         }
       }
+      WindowEvent::ScaleFactorChanged { .. } => {
+        state.renegotiate_surface();
+      }
+      WindowEvent::RedrawRequested => self.frame(event_loop),
       WindowEvent::KeyboardInput {
-        event: KeyEvent {
-          repeat: false,
-          state: ElementState::Pressed,
-          logical_key: Key::Named(NamedKey::Enter),
-          ..
-        },
+        event: KeyEvent { logical_key, state: key_state, repeat, .. },
         ..
-      } => {
-        if let (Some(object_state), Some(window)) = (self.object_state.as_mut(), self.window.as_ref()) {
-          let size = window.inner_size();
-          let w = size.width.max(1);
-          let h = size.height.max(1);
-          if self.flag {
-            object_state.resize((w, h).into());
-          } else {
-            object_state.resize((w / 2, h / 2).into());
-          }
-          self.flag = !self.flag;
-          window.request_redraw();
-        }
-      }
-      WindowEvent::RedrawRequested => {
-        if let Some(object_state) = self.object_state.as_mut() {
-          let _ = object_state.render();
-        }
+      } => self.events.push(match key_state {
+        ElementState::Pressed => InputEvent::KeyPressed { key: logical_key, repeat },
+        ElementState::Released => InputEvent::KeyReleased { key: logical_key },
+      }),
+      WindowEvent::CursorMoved { position, .. } => {
+        self.events.push(InputEvent::CursorMoved { x: position.x, y: position.y });
       }
+      WindowEvent::MouseInput { state: button_state, button, .. } => self.events.push(match button_state {
+        ElementState::Pressed => InputEvent::MousePressed(button),
+        ElementState::Released => InputEvent::MouseReleased(button),
+      }),
+      WindowEvent::MouseWheel { delta, .. } => self.events.push(InputEvent::MouseWheel(delta)),
       _ => (),
     }
   }
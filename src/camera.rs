@@ -0,0 +1,190 @@
+use std::time::Duration;
+
+use cgmath::{Deg, EuclideanSpace, InnerSpace, Matrix4, MetricSpace, Point3, Rad, SquareMatrix, Vector3, Vector4};
+
+// cgmath's projections are built for OpenGL's -1..1 depth range, wgpu
+// expects 0..1
+const OPENGL_TO_WGPU_MATRIX: Matrix4<f32> = Matrix4::new(
+  1.0, 0.0, 0.0, 0.0,
+  0.0, 1.0, 0.0, 0.0,
+  0.0, 0.0, 0.5, 0.0,
+  0.0, 0.0, 0.5, 1.0,
+);
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Projection {
+  // vertical field of view in degrees
+  Perspective { fovy: f32, znear: f32, zfar: f32 },
+  // the view's height in world units, the same at every distance
+  Orthographic { height: f32, znear: f32, zfar: f32 },
+}
+
+impl Projection {
+  pub fn matrix(&self, aspect: f32) -> Matrix4<f32> {
+    let proj = match *self {
+      Projection::Perspective { fovy, znear, zfar } => cgmath::perspective(Deg(fovy), aspect, znear, zfar),
+      Projection::Orthographic { height, znear, zfar } => {
+        let (x, y) = (height * aspect / 2.0, height / 2.0);
+        cgmath::ortho(-x, x, -y, y, znear, zfar)
+      }
+    };
+    OPENGL_TO_WGPU_MATRIX * proj
+  }
+}
+
+// a projection change in progress, see Camera::animate_projection()
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Transition {
+  from: Projection,
+  elapsed: Duration,
+  duration: Duration,
+}
+
+// A line through the scene from a point on the screen, see Camera::screen_ray()
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Ray {
+  // on the near plane
+  pub origin: Point3<f32>,
+  // unit length
+  pub direction: Vector3<f32>,
+}
+
+// The six planes of a view projection as (normal, distance), normals
+// pointing inwards: left, right, bottom, top, near, far
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Frustum {
+  pub planes: [Vector4<f32>; 6],
+}
+
+impl Frustum {
+  // from the rows of a matrix with wgpu's 0..1 depth range
+  pub fn from_matrix(view_proj: Matrix4<f32>) -> Self {
+    let row = |i: usize| Vector4::new(view_proj.x[i], view_proj.y[i], view_proj.z[i], view_proj.w[i]);
+    let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+    let planes = [w + x, w - x, w + y, w - y, z, w - z].map(|plane| plane / plane.truncate().magnitude());
+    Self { planes }
+  }
+
+  // false only when the sphere is entirely outside one of the planes
+  pub fn intersects_sphere(&self, center: Point3<f32>, radius: f32) -> bool {
+    self.planes.iter().all(|plane| plane.truncate().dot(center.to_vec()) + plane.w >= -radius)
+  }
+}
+
+// Camera looking from `eye` at `target`. State keeps `aspect` in sync with
+// the surface size.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Camera {
+  pub eye: Point3<f32>,
+  pub target: Point3<f32>,
+  pub up: Vector3<f32>,
+  pub aspect: f32,
+  pub projection: Projection,
+  transition: Option<Transition>,
+}
+
+impl Camera {
+  // far enough back that the -0.5..0.5 demo shapes stay fully in view
+  pub fn new(aspect: f32) -> Self {
+    Self {
+      eye: Point3::new(0.0, 0.0, 2.0),
+      target: Point3::new(0.0, 0.0, 0.0),
+      up: Vector3::unit_y(),
+      aspect,
+      projection: Projection::Perspective {
+        fovy: 45.0,
+        znear: 0.1,
+        zfar: 100.0,
+      },
+      transition: None,
+    }
+  }
+
+  pub fn build_view_projection_matrix(&self) -> Matrix4<f32> {
+    let view = Matrix4::look_at_rh(self.eye, self.target, self.up);
+    self.projection_matrix() * view
+  }
+
+  // the projection, blended with the previous one while animate_projection()
+  // is still running
+  pub fn projection_matrix(&self) -> Matrix4<f32> {
+    let to = self.projection.matrix(self.aspect);
+    let Some(transition) = self.transition else { return to };
+    let t = transition.elapsed.as_secs_f32() / transition.duration.as_secs_f32();
+    // smoothstep, so the change eases in and out
+    let t = t * t * (3.0 - 2.0 * t);
+    transition.from.matrix(self.aspect) * (1.0 - t) + to * t
+  }
+
+  // Moves towards the target in perspective (factor < 1 gets closer, the
+  // target is never passed) and scales the view height in orthographic,
+  // where the distance doesn't change what's visible
+  pub fn zoom(&mut self, factor: f32) {
+    let factor = factor.max(0.0);
+    match &mut self.projection {
+      Projection::Perspective { .. } => self.eye = self.target + (self.eye - self.target) * factor,
+      Projection::Orthographic { height, .. } => *height *= factor,
+    }
+  }
+
+  // Switches to `projection` over `duration`, advanced by update(). The
+  // projection field is the new one straight away.
+  pub fn animate_projection(&mut self, projection: Projection, duration: Duration) {
+    let from = self.projection;
+    self.projection = projection;
+    self.transition = (!duration.is_zero()).then_some(Transition {
+      from,
+      elapsed: Duration::ZERO,
+      duration,
+    });
+  }
+
+  // Perspective to orthographic and back, keeping the view's height at the
+  // target, so what's at the target's distance stays the same size
+  pub fn toggle_projection(&mut self, duration: Duration) {
+    let distance = self.eye.distance(self.target);
+    let projection = match self.projection {
+      Projection::Perspective { fovy, znear, zfar } => Projection::Orthographic {
+        height: 2.0 * distance * (Rad::from(Deg(fovy)).0 / 2.0).tan(),
+        znear,
+        zfar,
+      },
+      Projection::Orthographic { height, znear, zfar } => Projection::Perspective {
+        fovy: Deg::from(Rad(2.0 * (height / 2.0 / distance).atan())).0,
+        znear,
+        zfar,
+      },
+    };
+    self.animate_projection(projection, duration);
+  }
+
+  pub fn is_animating(&self) -> bool {
+    self.transition.is_some()
+  }
+
+  // advances animate_projection()
+  pub fn update(&mut self, dt: Duration) {
+    if let Some(transition) = self.transition.as_mut() {
+      transition.elapsed += dt;
+      if transition.elapsed >= transition.duration {
+        self.transition = None;
+      }
+    }
+  }
+
+  // The ray through a point on the screen, in normalized device
+  // coordinates (-1..1, y up). In orthographic the rays are parallel.
+  pub fn screen_ray(&self, x: f32, y: f32) -> Ray {
+    let inverse = self.build_view_projection_matrix().invert().expect("view projection is invertible");
+    let unproject = |z: f32| Point3::from_homogeneous(inverse * Vector4::new(x, y, z, 1.0));
+    let (near, far) = (unproject(0.0), unproject(1.0));
+    Ray {
+      origin: near,
+      direction: (far - near).normalize(),
+    }
+  }
+
+  pub fn frustum(&self) -> Frustum {
+    Frustum::from_matrix(self.build_view_projection_matrix())
+  }
+}
@@ -0,0 +1,56 @@
+use std::fmt;
+
+// what the crate can do on the current adapter, for diagnostics screens
+#[derive(Clone, Debug)]
+pub struct CapabilityReport {
+  pub adapter_name: String,
+  pub backend: wgpu::Backend,
+  pub webgpu_compliant: bool,
+  pub shader_model: wgpu::ShaderModel,
+  pub compute_shaders: bool,
+  pub vertex_storage: bool,
+  pub indirect_execution: bool,
+  pub cube_array_textures: bool,
+  pub non_power_of_two_mipmaps: bool,
+  // HDR environment maps need a filterable and renderable Rgba16Float
+  pub environment_maps: bool,
+}
+
+impl CapabilityReport {
+  pub fn new(adapter: &wgpu::Adapter) -> Self {
+    let info = adapter.get_info();
+    let downlevel = adapter.get_downlevel_capabilities();
+    let flags = downlevel.flags;
+    let half_float = adapter.get_texture_format_features(wgpu::TextureFormat::Rgba16Float);
+
+    Self {
+      adapter_name: info.name,
+      backend: info.backend,
+      webgpu_compliant: downlevel.is_webgpu_compliant(),
+      shader_model: downlevel.shader_model,
+      compute_shaders: flags.contains(wgpu::DownlevelFlags::COMPUTE_SHADERS),
+      vertex_storage: flags.contains(wgpu::DownlevelFlags::VERTEX_STORAGE),
+      indirect_execution: flags.contains(wgpu::DownlevelFlags::INDIRECT_EXECUTION),
+      cube_array_textures: flags.contains(wgpu::DownlevelFlags::CUBE_ARRAY_TEXTURES),
+      non_power_of_two_mipmaps: flags.contains(wgpu::DownlevelFlags::NON_POWER_OF_TWO_MIPMAPPED_TEXTURES),
+      environment_maps: half_float.allowed_usages.contains(wgpu::TextureUsages::RENDER_ATTACHMENT)
+        && half_float.flags.contains(wgpu::TextureFormatFeatureFlags::FILTERABLE),
+    }
+  }
+}
+
+impl fmt::Display for CapabilityReport {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let yes_no = |supported: bool| if supported { "yes" } else { "no" };
+
+    writeln!(f, "adapter:                  {} ({:?})", self.adapter_name, self.backend)?;
+    writeln!(f, "webgpu compliant:         {}", yes_no(self.webgpu_compliant))?;
+    writeln!(f, "shader model:             {:?}", self.shader_model)?;
+    writeln!(f, "compute shaders:          {}", yes_no(self.compute_shaders))?;
+    writeln!(f, "vertex storage buffers:   {}", yes_no(self.vertex_storage))?;
+    writeln!(f, "indirect draws:           {}", yes_no(self.indirect_execution))?;
+    writeln!(f, "cube array textures:      {}", yes_no(self.cube_array_textures))?;
+    writeln!(f, "non-pow2 mipmaps:         {}", yes_no(self.non_power_of_two_mipmaps))?;
+    write!(f, "HDR environment maps:     {}", yes_no(self.environment_maps))
+  }
+}
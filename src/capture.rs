@@ -0,0 +1,129 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::limits::ExceedsLimit;
+use crate::poll::MapTracker;
+use crate::readback::{self, ReadbackId, ReadbackRing};
+
+#[derive(Debug)]
+pub enum CaptureError {
+  // only 8 bit RGBA/BGRA frames can be written as PNG
+  UnsupportedFormat(wgpu::TextureFormat),
+  // the frame is bigger than a buffer can be
+  TooLarge(ExceedsLimit),
+  Map(wgpu::BufferAsyncError),
+  Encode(image::ImageError),
+}
+
+impl fmt::Display for CaptureError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      CaptureError::UnsupportedFormat(format) => write!(f, "Can't capture frames in {:?}", format),
+      CaptureError::TooLarge(err) => write!(f, "Can't capture the frame: {}", err),
+      CaptureError::Map(err) => write!(f, "Failed to read back the captured frame: {}", err),
+      CaptureError::Encode(err) => write!(f, "Failed to write the captured frame: {}", err),
+    }
+  }
+}
+
+impl std::error::Error for CaptureError {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      CaptureError::UnsupportedFormat(_) => None,
+      CaptureError::TooLarge(err) => Some(err),
+      CaptureError::Map(err) => Some(err),
+      CaptureError::Encode(err) => Some(err),
+    }
+  }
+}
+
+// whether the texels need their red and blue channels swapped for PNG
+pub(crate) fn is_bgra(format: wgpu::TextureFormat) -> Result<bool, CaptureError> {
+  match format {
+    wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => Ok(false),
+    wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => Ok(true),
+    format => Err(CaptureError::UnsupportedFormat(format)),
+  }
+}
+
+// A frame rendered a second time into a COPY_SRC texture, since swapchain
+// textures usually can't be copied from. It's read back through a one slot
+// ReadbackRing and written out once the GPU is done.
+pub(crate) struct FrameCapture {
+  path: PathBuf,
+  pub(crate) view: wgpu::TextureView,
+  texture: wgpu::Texture,
+  readback: ReadbackRing<u8>,
+  // None until encode_copy()
+  id: Option<ReadbackId>,
+  bgra: bool,
+}
+
+impl FrameCapture {
+  // `format` is the format the pipelines render in, see State::render_format()
+  pub(crate) fn new(
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+    format: wgpu::TextureFormat,
+    path: &Path,
+    tracker: &MapTracker,
+  ) -> Result<Self, CaptureError> {
+    let bgra = is_bgra(format)?;
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+      label: Some("Capture Target"),
+      size: wgpu::Extent3d {
+        width: config.width,
+        height: config.height,
+        depth_or_array_layers: 1,
+      },
+      mip_level_count: 1,
+      sample_count: 1,
+      dimension: wgpu::TextureDimension::D2,
+      format,
+      usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+      view_formats: &[],
+    });
+    let slot_size = readback::padded_bytes_per_row(config.width * 4) as u64 * config.height as u64;
+    let readback = ReadbackRing::new(device, 1, slot_size).map_err(CaptureError::TooLarge)?.tracked_by(tracker.clone());
+
+    Ok(Self {
+      path: path.to_path_buf(),
+      view: texture.create_view(&wgpu::TextureViewDescriptor::default()),
+      texture,
+      readback,
+      id: None,
+      bgra,
+    })
+  }
+
+  pub(crate) fn encode_copy(&mut self, encoder: &mut wgpu::CommandEncoder) {
+    // the slot is sized for the whole texture, so this always fits
+    self.id = self.readback.request_texture(encoder, self.texture.as_image_copy(), self.texture.size());
+  }
+
+  // call once the copy was submitted
+  pub(crate) fn map(&mut self) {
+    self.readback.after_submit();
+  }
+
+  // None while the mapping is still pending
+  pub(crate) fn try_finish(&mut self) -> Option<Result<PathBuf, CaptureError>> {
+    let id = self.id?;
+    let readback = self.readback.poll().find(|readback| readback.id == id);
+    if let Some(readback) = readback {
+      return Some(self.write_png(readback.data));
+    }
+    self.readback.failed().contains(&id).then_some(Err(CaptureError::Map(wgpu::BufferAsyncError)))
+  }
+
+  fn write_png(&self, mut pixels: Vec<u8>) -> Result<PathBuf, CaptureError> {
+    let (width, height) = (self.texture.width(), self.texture.height());
+    if self.bgra {
+      for pixel in pixels.chunks_exact_mut(4) {
+        pixel.swap(0, 2);
+      }
+    }
+    image::save_buffer_with_format(&self.path, &pixels, width, height, image::ColorType::Rgba8, image::ImageFormat::Png).map_err(CaptureError::Encode)?;
+    Ok(self.path.clone())
+  }
+}
@@ -0,0 +1,72 @@
+// How linear shader output ends up sRGB encoded on screen
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum SrgbEncoding {
+  // the surface format is sRGB, the hardware encodes on write
+  SurfaceFormat,
+  // the surface isn't sRGB, but the frames are rendered through an sRGB
+  // view of it, so the hardware still encodes
+  SrgbView,
+  // no sRGB view either (WebGL), the shaders encode their output
+  // (OUTPUT_SRGB_ENCODE) and the clear color is converted on the CPU
+  Shader,
+  // formats without an sRGB variant, e.g. Rgba16Float, get the linear values
+  Linear,
+}
+
+// Returned by State::color_space_info(), to check the colors will come out
+// right on the negotiated surface.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ColorSpaceInfo {
+  pub surface_format: wgpu::TextureFormat,
+  // what the pipelines and render_with() overlays draw into
+  pub render_format: wgpu::TextureFormat,
+  pub encoding: SrgbEncoding,
+}
+
+impl ColorSpaceInfo {
+  pub(crate) fn new(config: &wgpu::SurfaceConfiguration) -> Self {
+    let render_format = render_format(config);
+    let encoding = if config.format.is_srgb() {
+      SrgbEncoding::SurfaceFormat
+    } else if render_format.is_srgb() {
+      SrgbEncoding::SrgbView
+    } else if needs_srgb_encode(render_format) {
+      SrgbEncoding::Shader
+    } else {
+      SrgbEncoding::Linear
+    };
+
+    Self {
+      surface_format: config.format,
+      render_format,
+      encoding,
+    }
+  }
+}
+
+// The sRGB variant of a non-sRGB surface format, to render through as a
+// view format. Needs DownlevelFlags::VIEW_FORMATS, which WebGL lacks.
+pub(crate) fn srgb_view_formats(adapter: &wgpu::Adapter, format: wgpu::TextureFormat) -> Vec<wgpu::TextureFormat> {
+  let view_formats = adapter.get_downlevel_capabilities().flags.contains(wgpu::DownlevelFlags::VIEW_FORMATS);
+  if needs_srgb_encode(format) && view_formats {
+    vec![format.add_srgb_suffix()]
+  } else {
+    Vec::new()
+  }
+}
+
+// the format of the views rendered into, the only view format if there is one
+pub(crate) fn render_format(config: &wgpu::SurfaceConfiguration) -> wgpu::TextureFormat {
+  config.view_formats.first().copied().unwrap_or(config.format)
+}
+
+// 8 bit formats that have an sRGB variant but aren't it, which is all WebGL
+// offers for the canvas. Rendered into directly, the shaders have to encode
+// their output themselves, otherwise all the colors come out darker.
+pub(crate) fn needs_srgb_encode(format: wgpu::TextureFormat) -> bool {
+  !format.is_srgb() && format.add_srgb_suffix() != format
+}
+
+pub(crate) fn linear_to_srgb(c: f64) -> f64 {
+  if c <= 0.0031308 { c * 12.92 } else { 1.055 * c.powf(1.0 / 2.4) - 0.055 }
+}
@@ -0,0 +1,23 @@
+// debug lines from DebugDraw, drawn as a line list in world space
+struct VertexInput {
+  @location(0) position: vec3<f32>,
+  @location(1) color: vec3<f32>,
+};
+
+struct VertexOutput {
+  @builtin(position) clip_position: vec4<f32>,
+  @location(0) color: vec3<f32>,
+}
+
+@vertex
+fn vs_main(model: VertexInput) -> VertexOutput {
+  var out: VertexOutput;
+  out.color = model.color;
+  out.clip_position = uniforms.view_proj * uniforms.transform * vec4<f32>(model.position, 1.0);
+  return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+  return encode_output(vec4<f32>(in.color, 1.0));
+}
@@ -0,0 +1,147 @@
+use wgpu::util::DeviceExt;
+
+use crate::{limits, DepthBias, ExceedsLimit, PipelineDesc, State, Uniforms, Vertex};
+
+// what the debug line pipeline is cached under, independent of the render mode
+pub(crate) const LINE_PIPELINE: PipelineDesc = PipelineDesc {
+  polygon_mode: wgpu::PolygonMode::Fill,
+  topology: wgpu::PrimitiveTopology::LineList,
+  cull_mode: None,
+  depth_bias: DepthBias::NONE,
+};
+
+// Immediate-mode lines for gizmos and bounding boxes, see State::debug().
+// Coordinates are in world space: the camera applies, the update_uniforms()
+// transform doesn't. Lines are depth-tested against the scene and stay
+// until clear() is called.
+#[derive(Default)]
+pub struct DebugDraw {
+  vertices: Vec<Vertex>,
+  // whether the vertex buffer is behind
+  changed: bool,
+}
+
+impl DebugDraw {
+  pub fn line(&mut self, a: [f32; 3], b: [f32; 3], color: [f32; 3]) -> &mut Self {
+    self.vertices.push(Vertex::new(a, color));
+    self.vertices.push(Vertex::new(b, color));
+    self.changed = true;
+    self
+  }
+
+  // the 12 edges of an axis-aligned box
+  pub fn aabb(&mut self, min: [f32; 3], max: [f32; 3], color: [f32; 3]) -> &mut Self {
+    let corner = |i: usize| [
+      if i & 1 == 0 { min[0] } else { max[0] },
+      if i & 2 == 0 { min[1] } else { max[1] },
+      if i & 4 == 0 { min[2] } else { max[2] },
+    ];
+    for i in 0..8 {
+      // connect each corner to the neighbours that differ in one higher bit
+      for bit in [1, 2, 4] {
+        if i & bit == 0 {
+          self.line(corner(i), corner(i | bit), color);
+        }
+      }
+    }
+    self
+  }
+
+  // red, green and blue lines along +x, +y and +z
+  pub fn axes(&mut self, origin: [f32; 3], length: f32) -> &mut Self {
+    let [x, y, z] = origin;
+    self.line(origin, [x + length, y, z], [1.0, 0.0, 0.0]);
+    self.line(origin, [x, y + length, z], [0.0, 1.0, 0.0]);
+    self.line(origin, [x, y, z + length], [0.0, 0.0, 1.0]);
+    self
+  }
+
+  pub fn clear(&mut self) {
+    if !self.vertices.is_empty() {
+      self.vertices.clear();
+      self.changed = true;
+    }
+  }
+
+  pub fn num_lines(&self) -> usize {
+    self.vertices.len() / 2
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.vertices.is_empty()
+  }
+
+  // the vertices, if they changed since the last call
+  pub(crate) fn take_changes(&mut self) -> Option<&[Vertex]> {
+    std::mem::take(&mut self.changed).then_some(&self.vertices[..])
+  }
+}
+
+// The GPU side of DebugDraw, created by the first render() with lines, so
+// the vertex buffer starts out with them
+pub(crate) struct DebugLines {
+  pub(crate) vertex_buffer: wgpu::Buffer,
+  pub(crate) num_vertices: u32,
+  // Uniforms with an identity transform, bound at @group(0)
+  uniform_buffer: wgpu::Buffer,
+  pub(crate) bind_group: wgpu::BindGroup,
+}
+
+impl DebugLines {
+  pub(crate) fn new(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    uniforms: Uniforms,
+    vertices: &[Vertex],
+  ) -> Result<Self, ExceedsLimit> {
+    let contents: &[u8] = bytemuck::cast_slice(vertices);
+    limits::check_buffer_size(&device.limits(), "Debug Vertex Buffer", contents.len() as u64)?;
+    let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+      label: Some("Debug Vertex Buffer"),
+      contents,
+      usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+    });
+    let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+      label: Some("Debug Uniform Buffer"),
+      contents: bytemuck::cast_slice(&[uniforms]),
+      usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+    });
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+      label: Some("Debug Bind Group"),
+      layout,
+      entries: &[wgpu::BindGroupEntry {
+        binding: 0,
+        resource: uniform_buffer.as_entire_binding(),
+      }],
+    });
+
+    Ok(Self {
+      vertex_buffer,
+      num_vertices: vertices.len() as u32,
+      uniform_buffer,
+      bind_group,
+    })
+  }
+
+  // grows the buffer like the mesh buffers, so a steady line count doesn't
+  // reallocate; nothing is drawn when the lines don't fit in a buffer
+  pub(crate) fn upload(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, vertices: &[Vertex]) -> Result<(), ExceedsLimit> {
+    self.num_vertices = 0;
+    if !vertices.is_empty() {
+      State::write_or_grow(
+        device,
+        queue,
+        &mut self.vertex_buffer,
+        "Debug Vertex Buffer",
+        wgpu::BufferUsages::VERTEX,
+        bytemuck::cast_slice(vertices),
+      )?;
+    }
+    self.num_vertices = vertices.len() as u32;
+    Ok(())
+  }
+
+  pub(crate) fn write_uniforms(&self, queue: &wgpu::Queue, uniforms: Uniforms) {
+    queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniforms]));
+  }
+}
@@ -0,0 +1,263 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use winit::{
+  application::ApplicationHandler,
+  event::*,
+  event_loop::ActiveEventLoop,
+  keyboard::{Key, NamedKey},
+  monitor::MonitorHandle,
+  window::{Window, WindowId}
+};
+
+use sotrh::{RenderMode, State, Vertex};
+
+const QUAD_VERTICES: &[Vertex] = &[
+  Vertex { position: [-0.5, 0.5, 0.0], color: [1.0, 0.0, 0.0] },
+  Vertex { position: [-0.5, -0.5, 0.0], color: [0.0, 1.0, 0.0] },
+  Vertex { position: [0.5, -0.5, 0.0], color: [0.0, 0.0, 1.0] },
+  Vertex { position: [0.5, 0.5, 0.0], color: [1.0, 1.0, 0.0] },
+];
+
+const QUAD_INDICES: &[u16] = &[
+  0, 1, 2,
+  0, 2, 3,
+];
+
+#[derive(Default)]
+pub struct App {
+  window: Option<Arc<Window>>,
+  object_state: Option<State>,
+  // a toggle flag used to control the size of the surface
+  flag: bool,
+  // the monitor the window was last seen on, to notice moves between monitors
+  monitor: Option<MonitorHandle>,
+  // for the camera's projection animation
+  last_redraw: Option<Instant>,
+}
+
+impl ApplicationHandler for App {
+  fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+    if self.window.is_none() {
+      let win_attr = Window::default_attributes().with_title("App Initialization");
+      // use Arc.
+      let window = Arc::new(
+        event_loop.create_window(win_attr).expect("create window err."),
+      );
+      self.monitor = window.current_monitor();
+      self.window = Some(window.clone());
+      match State::new(window.clone()) {
+        Ok(object_state) => {
+          log::info!("device capabilities:\n{}", object_state.capability_report());
+          self.object_state = Some(object_state);
+        }
+        Err(err) => {
+          log::error!("Failed to set up rendering: {}", err);
+          event_loop.exit();
+        }
+      }
+    } else if let (Some(object_state), Some(window)) = (self.object_state.as_mut(), self.window.as_ref()) {
+      // back from suspended(), the window is kept but needs a new surface
+      match object_state.resume(window.clone()) {
+        Ok(()) => window.request_redraw(),
+        Err(err) => {
+          log::error!("Failed to resume rendering: {}", err);
+          event_loop.exit();
+        }
+      }
+    }
+  }
+
+  fn suspended(&mut self, _event_loop: &ActiveEventLoop) {
+    // on Android the native window is destroyed after this
+    if let Some(object_state) = self.object_state.as_mut() {
+      object_state.suspend();
+    }
+  }
+
+  fn about_to_wait(&mut self, _event_loop: &ActiveEventLoop) {
+    // keep readbacks moving even when nothing is being redrawn
+    if let Some(object_state) = self.object_state.as_mut() {
+      object_state.poll_outstanding();
+    }
+  }
+
+  fn window_event(
+    &mut self,
+    event_loop: &ActiveEventLoop,
+    _window_id: WindowId,
+    event: WindowEvent,
+  ) {
+    match event {
+      WindowEvent::CloseRequested => {
+        event_loop.exit();
+      }
+      WindowEvent::Resized(new_size) => {
+        if let (Some(object_state), Some(window)) =  (self.object_state.as_mut(), self.window.as_ref()) {
+          object_state.resize((new_size.width, new_size.height).into());
+          if !object_state.is_minimized() {
+            window.request_redraw();
+          }
+        }
+      }
+      WindowEvent::Moved(_) => {
+        if let (Some(object_state), Some(window)) = (self.object_state.as_mut(), self.window.as_ref()) {
+          let monitor = window.current_monitor();
+          if monitor != self.monitor {
+            self.monitor = monitor;
+            object_state.renegotiate_surface();
+          }
+        }
+      }
+      WindowEvent::ScaleFactorChanged { .. } => {
+        if let Some(object_state) = self.object_state.as_mut() {
+          object_state.renegotiate_surface();
+        }
+      }
+      WindowEvent::KeyboardInput {
+        event: KeyEvent {
+          repeat: false,
+          state: ElementState::Pressed,
+          logical_key: Key::Named(NamedKey::Enter),
+          ..
+        },
+        ..
+      } => {
+        if let (Some(object_state), Some(window)) = (self.object_state.as_mut(), self.window.as_ref()) {
+          let size = window.inner_size();
+          let w = size.width.max(1);
+          let h = size.height.max(1);
+          if self.flag {
+            object_state.resize((w, h).into());
+          } else {
+            object_state.resize((w / 2, h / 2).into());
+          }
+          self.flag = !self.flag;
+          window.request_redraw();
+        }
+      }
+      WindowEvent::KeyboardInput {
+        event: KeyEvent {
+          repeat: false,
+          state: ElementState::Pressed,
+          logical_key: Key::Character(c),
+          ..
+        },
+        ..
+      } if c.as_str() == "v" => {
+        if let (Some(object_state), Some(window)) = (self.object_state.as_mut(), self.window.as_ref()) {
+          object_state.set_vsync(!object_state.vsync_enabled());
+          window.request_redraw();
+        }
+      }
+      WindowEvent::KeyboardInput {
+        event: KeyEvent {
+          repeat: false,
+          state: ElementState::Pressed,
+          logical_key: Key::Character(c),
+          ..
+        },
+        ..
+      } if c.as_str() == "m" => {
+        // toggle 4x MSAA
+        if let (Some(object_state), Some(window)) = (self.object_state.as_mut(), self.window.as_ref()) {
+          let samples = if object_state.options().msaa_samples > 1 { 1 } else { 4 };
+          object_state.set_msaa_samples(samples);
+          window.request_redraw();
+        }
+      }
+      WindowEvent::KeyboardInput {
+        event: KeyEvent {
+          repeat: false,
+          state: ElementState::Pressed,
+          logical_key: Key::Character(c),
+          ..
+        },
+        ..
+      } if c.as_str() == "w" => {
+        if let (Some(object_state), Some(window)) = (self.object_state.as_mut(), self.window.as_ref()) {
+          let mode = match object_state.render_mode() {
+            RenderMode::Fill => RenderMode::Wireframe,
+            RenderMode::Wireframe => RenderMode::Points,
+            RenderMode::Points => RenderMode::Fill,
+          };
+          if let Err(err) = object_state.set_render_mode(mode) {
+            log::warn!("{}", err);
+            // skip the unsupported mode
+            let _ = object_state.set_render_mode(RenderMode::Points);
+          }
+          window.request_redraw();
+        }
+      }
+      WindowEvent::KeyboardInput {
+        event: KeyEvent {
+          repeat: false,
+          state: ElementState::Pressed,
+          logical_key: Key::Character(c),
+          ..
+        },
+        ..
+      } if c.as_str() == "p" => {
+        // screenshot of the next frame into the working directory
+        if let (Some(object_state), Some(window)) = (self.object_state.as_mut(), self.window.as_ref()) {
+          let seconds = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+          let path = std::path::PathBuf::from(format!("screenshot-{}.png", seconds));
+          match object_state.capture_frame(&path) {
+            Ok(()) => window.request_redraw(),
+            Err(err) => log::warn!("{}", err),
+          }
+        }
+      }
+      WindowEvent::KeyboardInput {
+        event: KeyEvent {
+          repeat: false,
+          state: ElementState::Pressed,
+          logical_key: Key::Character(c),
+          ..
+        },
+        ..
+      } if c.as_str() == "q" => {
+        // swap the polygon for a quad made of 4 vertices and 6 indices
+        if let (Some(object_state), Some(window)) = (self.object_state.as_mut(), self.window.as_ref()) {
+          match object_state.set_indexed_mesh(QUAD_VERTICES, QUAD_INDICES) {
+            Ok(()) => window.request_redraw(),
+            Err(err) => log::warn!("{}", err),
+          }
+        }
+      }
+      WindowEvent::KeyboardInput {
+        event: KeyEvent {
+          repeat: false,
+          state: ElementState::Pressed,
+          logical_key: Key::Character(c),
+          ..
+        },
+        ..
+      } if c.as_str() == "o" => {
+        // blend between perspective and orthographic, timed from the next redraw
+        self.last_redraw = None;
+        if let (Some(object_state), Some(window)) = (self.object_state.as_mut(), self.window.as_ref()) {
+          object_state.camera_mut().toggle_projection(Duration::from_millis(300));
+          window.request_redraw();
+        }
+      }
+      WindowEvent::RedrawRequested => {
+        let now = Instant::now();
+        let dt = self.last_redraw.replace(now).map_or(Duration::ZERO, |last| now - last);
+        if let (Some(object_state), Some(window)) = (self.object_state.as_mut(), self.window.as_ref()) {
+          let animating = object_state.camera().is_animating();
+          object_state.camera_mut().update(dt);
+          if let Err(err) = object_state.render_or_recover() {
+            log::error!("Rendering failed: {}", err);
+            event_loop.exit();
+          }
+          if animating {
+            window.request_redraw();
+          }
+        }
+      }
+      _ => (),
+    }
+  }
+}
@@ -0,0 +1,95 @@
+use std::sync::Arc;
+
+use winit::{event::WindowEvent, window::Window};
+
+use crate::{RenderError, State};
+
+// egui drawn on top of the scene through State::render_with(). Give it each
+// window event first and skip the app's own handling when it returns true.
+// The renderer is set up for the surface format at creation.
+pub struct EguiOverlay {
+  context: egui::Context,
+  winit_state: egui_winit::State,
+  renderer: egui_wgpu::Renderer,
+  window: Arc<Window>,
+}
+
+impl EguiOverlay {
+  pub fn new(state: &State, window: Arc<Window>) -> Self {
+    let context = egui::Context::default();
+    let winit_state = egui_winit::State::new(
+      context.clone(),
+      egui::ViewportId::ROOT,
+      &window,
+      Some(window.scale_factor() as f32),
+      None,
+      Some(state.device().limits().max_texture_dimension_2d as usize),
+    );
+    // the overlay pass draws into the resolved frame, without MSAA or depth
+    let renderer = egui_wgpu::Renderer::new(state.device(), state.render_format(), None, 1, false);
+
+    Self {
+      context,
+      winit_state,
+      renderer,
+      window,
+    }
+  }
+
+  pub fn context(&self) -> &egui::Context {
+    &self.context
+  }
+
+  // true when egui consumed the event, e.g. a click on one of its windows
+  pub fn handle_event(&mut self, event: &WindowEvent) -> bool {
+    self.winit_state.on_window_event(&self.window, event).consumed
+  }
+
+  // Runs `ui` to build this frame's interface and renders the scene with
+  // it on top. Use instead of State::render().
+  pub fn render(&mut self, state: &mut State, ui: impl FnMut(&egui::Context)) -> Result<(), RenderError> {
+    let input = self.winit_state.take_egui_input(&self.window);
+    let output = self.context.run(input, ui);
+    self.winit_state.handle_platform_output(&self.window, output.platform_output);
+    let paint_jobs = self.context.tessellate(output.shapes, output.pixels_per_point);
+
+    // uploaded even when render() skips the frame, egui only sends each change once
+    for (id, delta) in &output.textures_delta.set {
+      self.renderer.update_texture(state.device(), state.queue(), *id, delta);
+    }
+
+    // the surface size, which is what the frame has while a resize is pending
+    let size = state.size();
+    let screen = egui_wgpu::ScreenDescriptor {
+      size_in_pixels: [size.width, size.height],
+      pixels_per_point: output.pixels_per_point,
+    };
+    let renderer = &mut self.renderer;
+    let result = state.render_with(|device, queue, encoder, view| {
+      // only non-empty for egui paint callbacks, which prepare their own data
+      let callback_buffers = renderer.update_buffers(device, queue, encoder, &paint_jobs, &screen);
+      queue.submit(callback_buffers);
+
+      let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some("Egui Pass"),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+          view,
+          resolve_target: None,
+          ops: wgpu::Operations {
+            load: wgpu::LoadOp::Load,
+            store: wgpu::StoreOp::Store,
+          },
+        })],
+        depth_stencil_attachment: None,
+        occlusion_query_set: None,
+        timestamp_writes: None,
+      }).forget_lifetime();
+      renderer.render(&mut render_pass, &paint_jobs, &screen);
+    });
+
+    for id in &output.textures_delta.free {
+      self.renderer.free_texture(id);
+    }
+    result
+  }
+}
@@ -0,0 +1,435 @@
+use anyhow::Context;
+
+use crate::limits;
+use crate::load::{Cancelled, LoadContext};
+
+// Rgba16Float is filterable everywhere, Rgba32Float needs an extra feature
+const ENVIRONMENT_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+const MAX_CUBE_SIZE: u32 = 1024;
+// the ambient term is very low frequency, a tiny cubemap is plenty
+const IRRADIANCE_SIZE: u32 = 32;
+
+// decoded Radiance HDR image, linear RGB with alpha set to 1
+pub struct HdrImage {
+  pub width: u32,
+  pub height: u32,
+  pub pixels: Vec<[f32; 4]>,
+}
+
+impl HdrImage {
+  // RGBE decoding (including the RLE scanlines) is done by the image crate
+  pub fn decode(bytes: &[u8]) -> anyhow::Result<Self> {
+    let image = image::load_from_memory_with_format(bytes, image::ImageFormat::Hdr)
+      .context("Failed to decode HDR image")?
+      .into_rgba32f();
+    let (width, height) = image.dimensions();
+    let pixels = image.pixels().map(|p| p.0).collect();
+    Ok(Self { width, height, pixels })
+  }
+
+  // bright spots (the sun) can be far above what f16 holds; clamped so they
+  // don't turn into inf and spread through the irradiance convolution
+  pub fn to_rgba16f(&self) -> Vec<[half::f16; 4]> {
+    self.pixels.iter()
+      .map(|p| p.map(|c| half::f16::from_f32(c.clamp(-half::f16::MAX.to_f32(), half::f16::MAX.to_f32()))))
+      .collect()
+  }
+}
+
+// equirectangular environment converted to a cubemap, plus its irradiance convolution
+pub struct Environment {
+  pub cubemap: wgpu::Texture,
+  pub cubemap_view: wgpu::TextureView,
+  pub irradiance: wgpu::Texture,
+  pub irradiance_view: wgpu::TextureView,
+  pub sampler: wgpu::Sampler,
+}
+
+impl Environment {
+  pub fn from_hdr(device: &wgpu::Device, queue: &wgpu::Queue, hdr_bytes: &[u8]) -> anyhow::Result<Self> {
+    Self::from_hdr_with(device, queue, hdr_bytes, &LoadContext::new())
+  }
+
+  pub fn from_hdr_with(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    hdr_bytes: &[u8],
+    context: &LoadContext,
+  ) -> anyhow::Result<Self> {
+    context.step("decode", 0.0)?;
+    let hdr = HdrImage::decode(hdr_bytes)?;
+    context.step("decode", 1.0)?;
+    Self::from_image_with(device, queue, &hdr, context)
+  }
+
+  pub fn from_image(device: &wgpu::Device, queue: &wgpu::Queue, hdr: &HdrImage) -> anyhow::Result<Self> {
+    Self::from_image_with(device, queue, hdr, &LoadContext::new())
+  }
+
+  // Nothing is submitted until every pass was recorded, so a cancelled load
+  // only drops resources the GPU never used.
+  pub fn from_image_with(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    hdr: &HdrImage,
+    context: &LoadContext,
+  ) -> anyhow::Result<Self> {
+    limits::check_texture_2d(&device.limits(), "HDR image size", hdr.width, hdr.height)?;
+
+    context.step("upload", 0.0)?;
+    let equirect = device.create_texture(&wgpu::TextureDescriptor {
+      label: Some("Equirect Texture"),
+      size: wgpu::Extent3d {
+        width: hdr.width,
+        height: hdr.height,
+        depth_or_array_layers: 1,
+      },
+      mip_level_count: 1,
+      sample_count: 1,
+      dimension: wgpu::TextureDimension::D2,
+      format: ENVIRONMENT_FORMAT,
+      usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+      view_formats: &[],
+    });
+    queue.write_texture(
+      wgpu::ImageCopyTexture {
+        texture: &equirect,
+        mip_level: 0,
+        origin: wgpu::Origin3d::ZERO,
+        aspect: wgpu::TextureAspect::All,
+      },
+      bytemuck::cast_slice(&hdr.to_rgba16f()),
+      wgpu::ImageDataLayout {
+        offset: 0,
+        bytes_per_row: Some(hdr.width * 8),
+        rows_per_image: Some(hdr.height),
+      },
+      equirect.size(),
+    );
+    let equirect_view = equirect.create_view(&wgpu::TextureViewDescriptor::default());
+
+    let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+      label: Some("Environment Sampler"),
+      address_mode_u: wgpu::AddressMode::Repeat,
+      address_mode_v: wgpu::AddressMode::ClampToEdge,
+      address_mode_w: wgpu::AddressMode::ClampToEdge,
+      mag_filter: wgpu::FilterMode::Linear,
+      min_filter: wgpu::FilterMode::Linear,
+      mipmap_filter: wgpu::FilterMode::Nearest,
+      ..Default::default()
+    });
+
+    let pipelines = EnvironmentPipelines::new(device);
+    let cube_size = (hdr.height / 2).clamp(1, MAX_CUBE_SIZE);
+    let cubemap = new_cube_texture(device, "Environment Cubemap", cube_size);
+    let irradiance = new_cube_texture(device, "Irradiance Cubemap", IRRADIANCE_SIZE);
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+      label: Some("Environment Encoder"),
+    });
+
+    let equirect_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+      label: Some("Equirect Bind Group"),
+      layout: &pipelines.equirect_layout,
+      entries: &[
+        wgpu::BindGroupEntry {
+          binding: 0,
+          resource: wgpu::BindingResource::TextureView(&equirect_view),
+        },
+        wgpu::BindGroupEntry {
+          binding: 1,
+          resource: wgpu::BindingResource::Sampler(&sampler),
+        },
+      ],
+    });
+    render_faces(&mut encoder, &pipelines.equirect_to_cube, &equirect_bind_group, &cubemap, "cubemap", context)?;
+
+    let cubemap_view = cube_view(&cubemap);
+    let cube_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+      label: Some("Environment Cube Bind Group"),
+      layout: &pipelines.cube_layout,
+      entries: &[
+        wgpu::BindGroupEntry {
+          binding: 1,
+          resource: wgpu::BindingResource::Sampler(&sampler),
+        },
+        wgpu::BindGroupEntry {
+          binding: 2,
+          resource: wgpu::BindingResource::TextureView(&cubemap_view),
+        },
+      ],
+    });
+    render_faces(&mut encoder, &pipelines.irradiance, &cube_bind_group, &irradiance, "irradiance", context)?;
+
+    context.check()?;
+    queue.submit(std::iter::once(encoder.finish()));
+
+    let irradiance_view = cube_view(&irradiance);
+    Ok(Self {
+      cubemap,
+      cubemap_view,
+      irradiance,
+      irradiance_view,
+      sampler,
+    })
+  }
+}
+
+impl Environment {
+  // a cubemap and its sampler: the irradiance as bound by lit.wgsl at
+  // @group(2), the environment itself by skybox.wgsl at @group(1)
+  pub(crate) fn bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+      label: Some("Irradiance Bind Group Layout"),
+      entries: &[
+        wgpu::BindGroupLayoutEntry {
+          binding: 0,
+          visibility: wgpu::ShaderStages::FRAGMENT,
+          ty: wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            view_dimension: wgpu::TextureViewDimension::Cube,
+            multisampled: false,
+          },
+          count: None,
+        },
+        wgpu::BindGroupLayoutEntry {
+          binding: 1,
+          visibility: wgpu::ShaderStages::FRAGMENT,
+          ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+          count: None,
+        },
+      ],
+    })
+  }
+
+  pub(crate) fn bind_group(&self, device: &wgpu::Device, layout: &wgpu::BindGroupLayout) -> wgpu::BindGroup {
+    cube_bind_group(device, "Irradiance Bind Group", layout, &self.irradiance_view, &self.sampler)
+  }
+
+  pub(crate) fn skybox_bind_group(&self, device: &wgpu::Device, layout: &wgpu::BindGroupLayout) -> wgpu::BindGroup {
+    cube_bind_group(device, "Skybox Environment Bind Group", layout, &self.cubemap_view, &self.sampler)
+  }
+}
+
+// Until an environment is set lit meshes get a constant white irradiance,
+// so they look like textured ones. Rgba8Unorm because Rgba16Float isn't
+// necessarily renderable, and this one is only ever written by the queue.
+pub(crate) fn default_bind_group(
+  device: &wgpu::Device,
+  queue: &wgpu::Queue,
+  layout: &wgpu::BindGroupLayout,
+) -> wgpu::BindGroup {
+  let size = wgpu::Extent3d {
+    width: 1,
+    height: 1,
+    depth_or_array_layers: 6,
+  };
+  let texture = device.create_texture(&wgpu::TextureDescriptor {
+    label: Some("Default Irradiance Cubemap"),
+    size,
+    mip_level_count: 1,
+    sample_count: 1,
+    dimension: wgpu::TextureDimension::D2,
+    format: wgpu::TextureFormat::Rgba8Unorm,
+    usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+    view_formats: &[],
+  });
+  queue.write_texture(
+    wgpu::ImageCopyTexture {
+      texture: &texture,
+      mip_level: 0,
+      origin: wgpu::Origin3d::ZERO,
+      aspect: wgpu::TextureAspect::All,
+    },
+    &[255; 6 * 4],
+    wgpu::ImageDataLayout {
+      offset: 0,
+      bytes_per_row: Some(4),
+      rows_per_image: Some(1),
+    },
+    size,
+  );
+  let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+    label: Some("Default Irradiance Sampler"),
+    ..Default::default()
+  });
+  cube_bind_group(device, "Irradiance Bind Group", layout, &cube_view(&texture), &sampler)
+}
+
+fn cube_bind_group(
+  device: &wgpu::Device,
+  label: &str,
+  layout: &wgpu::BindGroupLayout,
+  view: &wgpu::TextureView,
+  sampler: &wgpu::Sampler,
+) -> wgpu::BindGroup {
+  device.create_bind_group(&wgpu::BindGroupDescriptor {
+    label: Some(label),
+    layout,
+    entries: &[
+      wgpu::BindGroupEntry {
+        binding: 0,
+        resource: wgpu::BindingResource::TextureView(view),
+      },
+      wgpu::BindGroupEntry {
+        binding: 1,
+        resource: wgpu::BindingResource::Sampler(sampler),
+      },
+    ],
+  })
+}
+
+struct EnvironmentPipelines {
+  equirect_layout: wgpu::BindGroupLayout,
+  cube_layout: wgpu::BindGroupLayout,
+  equirect_to_cube: wgpu::RenderPipeline,
+  irradiance: wgpu::RenderPipeline,
+}
+
+impl EnvironmentPipelines {
+  fn new(device: &wgpu::Device) -> Self {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+      label: Some("Environment Shader"),
+      source: wgpu::ShaderSource::Wgsl(include_str!("environment.wgsl").into()),
+    });
+
+    let sampler_entry = wgpu::BindGroupLayoutEntry {
+      binding: 1,
+      visibility: wgpu::ShaderStages::FRAGMENT,
+      ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+      count: None,
+    };
+    let texture_entry = |binding, view_dimension| wgpu::BindGroupLayoutEntry {
+      binding,
+      visibility: wgpu::ShaderStages::FRAGMENT,
+      ty: wgpu::BindingType::Texture {
+        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+        view_dimension,
+        multisampled: false,
+      },
+      count: None,
+    };
+
+    let equirect_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+      label: Some("Equirect Bind Group Layout"),
+      entries: &[texture_entry(0, wgpu::TextureViewDimension::D2), sampler_entry],
+    });
+    let cube_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+      label: Some("Environment Cube Bind Group Layout"),
+      entries: &[sampler_entry, texture_entry(2, wgpu::TextureViewDimension::Cube)],
+    });
+
+    let equirect_to_cube = face_pipeline(device, &shader, &equirect_layout, "fs_equirect_to_cube");
+    let irradiance = face_pipeline(device, &shader, &cube_layout, "fs_irradiance");
+
+    Self {
+      equirect_layout,
+      cube_layout,
+      equirect_to_cube,
+      irradiance,
+    }
+  }
+}
+
+fn face_pipeline(
+  device: &wgpu::Device,
+  shader: &wgpu::ShaderModule,
+  bind_group_layout: &wgpu::BindGroupLayout,
+  fragment_entry: &str,
+) -> wgpu::RenderPipeline {
+  let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+    label: Some(fragment_entry),
+    bind_group_layouts: &[bind_group_layout],
+    push_constant_ranges: &[],
+  });
+
+  device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+    label: Some(fragment_entry),
+    layout: Some(&layout),
+    vertex: wgpu::VertexState {
+      module: shader,
+      entry_point: "vs_face",
+      compilation_options: wgpu::PipelineCompilationOptions::default(),
+      buffers: &[],
+    },
+    fragment: Some(wgpu::FragmentState {
+      module: shader,
+      entry_point: fragment_entry,
+      compilation_options: wgpu::PipelineCompilationOptions::default(),
+      targets: &[Some(wgpu::ColorTargetState {
+        format: ENVIRONMENT_FORMAT,
+        blend: None,
+        write_mask: wgpu::ColorWrites::ALL,
+      })],
+    }),
+    primitive: wgpu::PrimitiveState::default(),
+    depth_stencil: None,
+    multisample: wgpu::MultisampleState::default(),
+    multiview: None,
+    cache: None,
+  })
+}
+
+fn new_cube_texture(device: &wgpu::Device, label: &str, size: u32) -> wgpu::Texture {
+  device.create_texture(&wgpu::TextureDescriptor {
+    label: Some(label),
+    size: wgpu::Extent3d {
+      width: size,
+      height: size,
+      depth_or_array_layers: 6,
+    },
+    mip_level_count: 1,
+    sample_count: 1,
+    dimension: wgpu::TextureDimension::D2,
+    format: ENVIRONMENT_FORMAT,
+    usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::RENDER_ATTACHMENT,
+    view_formats: &[],
+  })
+}
+
+fn cube_view(texture: &wgpu::Texture) -> wgpu::TextureView {
+  texture.create_view(&wgpu::TextureViewDescriptor {
+    dimension: Some(wgpu::TextureViewDimension::Cube),
+    ..Default::default()
+  })
+}
+
+// one render pass per face, the face index is encoded in the vertex range
+fn render_faces(
+  encoder: &mut wgpu::CommandEncoder,
+  pipeline: &wgpu::RenderPipeline,
+  bind_group: &wgpu::BindGroup,
+  target: &wgpu::Texture,
+  stage: &str,
+  context: &LoadContext,
+) -> Result<(), Cancelled> {
+  for face in 0..6 {
+    context.step(stage, face as f32 / 6.0)?;
+    let face_view = target.create_view(&wgpu::TextureViewDescriptor {
+      dimension: Some(wgpu::TextureViewDimension::D2),
+      base_array_layer: face,
+      array_layer_count: Some(1),
+      ..Default::default()
+    });
+
+    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+      label: Some("Environment Face Pass"),
+      color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+        view: &face_view,
+        resolve_target: None,
+        ops: wgpu::Operations {
+          load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+          store: wgpu::StoreOp::Store,
+        },
+      })],
+      depth_stencil_attachment: None,
+      occlusion_query_set: None,
+      timestamp_writes: None,
+    });
+    render_pass.set_pipeline(pipeline);
+    render_pass.set_bind_group(0, bind_group, &[]);
+    render_pass.draw(face * 3..face * 3 + 3, 0..1);
+  }
+  context.report(stage, 1.0);
+  Ok(())
+}
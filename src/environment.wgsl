@@ -0,0 +1,81 @@
+// shared vertex stage: one oversized triangle per cube face,
+// vertices 3 * face .. 3 * face + 3 belong to the given face
+struct FaceOutput {
+  @builtin(position) clip_position: vec4<f32>,
+  @location(0) uv: vec2<f32>,
+  @location(1) @interpolate(flat) face: u32,
+}
+
+@vertex
+fn vs_face(@builtin(vertex_index) vertex_index: u32) -> FaceOutput {
+  let face = vertex_index / 3u;
+  let corner = vertex_index % 3u;
+  let uv = vec2<f32>(f32((corner << 1u) & 2u), f32(corner & 2u));
+  var out: FaceOutput;
+  out.clip_position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+  // texture space has v pointing down, clip space has y pointing up
+  out.uv = vec2<f32>(uv.x, 1.0 - uv.y);
+  out.face = face;
+  return out;
+}
+
+// direction through a texel of the given face (+X, -X, +Y, -Y, +Z, -Z)
+fn face_direction(face: u32, uv: vec2<f32>) -> vec3<f32> {
+  let st = uv * 2.0 - 1.0;
+  var dir: vec3<f32>;
+  switch face {
+    case 0u: { dir = vec3<f32>(1.0, -st.y, -st.x); }
+    case 1u: { dir = vec3<f32>(-1.0, -st.y, st.x); }
+    case 2u: { dir = vec3<f32>(st.x, 1.0, st.y); }
+    case 3u: { dir = vec3<f32>(st.x, -1.0, -st.y); }
+    case 4u: { dir = vec3<f32>(st.x, -st.y, 1.0); }
+    default: { dir = vec3<f32>(-st.x, -st.y, -1.0); }
+  }
+  return normalize(dir);
+}
+
+const PI: f32 = 3.14159265359;
+
+@group(0) @binding(0)
+var equirect: texture_2d<f32>;
+@group(0) @binding(1)
+var env_sampler: sampler;
+@group(0) @binding(2)
+var env_cube: texture_cube<f32>;
+
+// equirectangular -> cubemap
+@fragment
+fn fs_equirect_to_cube(in: FaceOutput) -> @location(0) vec4<f32> {
+  let dir = face_direction(in.face, in.uv);
+  let uv = vec2<f32>(
+    atan2(dir.z, dir.x) / (2.0 * PI) + 0.5,
+    acos(clamp(dir.y, -1.0, 1.0)) / PI,
+  );
+  return vec4<f32>(textureSampleLevel(equirect, env_sampler, uv, 0.0).rgb, 1.0);
+}
+
+// cosine weighted hemisphere convolution of the cubemap
+const SAMPLE_DELTA: f32 = 0.05;
+
+@fragment
+fn fs_irradiance(in: FaceOutput) -> @location(0) vec4<f32> {
+  let normal = face_direction(in.face, in.uv);
+  var up = vec3<f32>(0.0, 1.0, 0.0);
+  if abs(normal.y) > 0.999 {
+    up = vec3<f32>(0.0, 0.0, 1.0);
+  }
+  let right = normalize(cross(up, normal));
+  up = normalize(cross(normal, right));
+
+  var irradiance = vec3<f32>(0.0);
+  var samples = 0.0;
+  for (var phi = 0.0; phi < 2.0 * PI; phi += SAMPLE_DELTA) {
+    for (var theta = 0.0; theta < 0.5 * PI; theta += SAMPLE_DELTA) {
+      let tangent = vec3<f32>(sin(theta) * cos(phi), sin(theta) * sin(phi), cos(theta));
+      let dir = tangent.x * right + tangent.y * up + tangent.z * normal;
+      irradiance += textureSampleLevel(env_cube, env_sampler, dir, 0.0).rgb * cos(theta) * sin(theta);
+      samples += 1.0;
+    }
+  }
+  return vec4<f32>(PI * irradiance / samples, 1.0);
+}
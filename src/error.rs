@@ -0,0 +1,134 @@
+use std::fmt;
+
+use crate::limits::ExceedsLimit;
+
+// Everything that can go wrong while creating a State. Most of these mean
+// the machine has no usable GPU, so the messages say what was attempted.
+#[derive(Debug)]
+pub enum StateError {
+  SurfaceCreation(wgpu::CreateSurfaceError),
+  NoAdapter { backends: wgpu::Backends },
+  DeviceRequest { source: wgpu::RequestDeviceError, limits: Box<wgpu::Limits> },
+  // FeatureRequest::Required features the adapter doesn't have
+  MissingFeature(wgpu::Features),
+  // the surface reports no formats for the chosen adapter
+  NoSupportedFormat,
+  // headless only, the requested target size is too big for the device
+  TargetTooLarge(ExceedsLimit),
+  // StateOptions::surface_usages asked for more than the surface supports
+  UnsupportedSurfaceUsages { requested: wgpu::TextureUsages, supported: wgpu::TextureUsages },
+  // GpuContext only, e.g. WebGL2
+  NoComputeShaders { adapter: String, backend: wgpu::Backend },
+}
+
+impl fmt::Display for StateError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      StateError::SurfaceCreation(err) => write!(f, "Failed to create a surface for the window: {}", err),
+      StateError::NoAdapter { backends } => write!(f, "No compatible GPU adapter found (tried backends: {:?})", backends),
+      StateError::DeviceRequest { source, limits } => write!(
+        f,
+        "Failed to create a device with {} (max_texture_dimension_2d {}, max_bind_groups {}, max_buffer_size {}): {}",
+        limits_name(limits), limits.max_texture_dimension_2d, limits.max_bind_groups, limits.max_buffer_size, source,
+      ),
+      StateError::MissingFeature(features) => write!(f, "The adapter doesn't support the required features {:?}", features),
+      StateError::NoSupportedFormat => write!(f, "The surface supports no texture formats on this adapter"),
+      StateError::TargetTooLarge(err) => write!(f, "{}", err),
+      StateError::UnsupportedSurfaceUsages { requested, supported } => write!(
+        f,
+        "The surface doesn't support the usages {:?} (supported: {:?})",
+        *requested - *supported, supported,
+      ),
+      StateError::NoComputeShaders { adapter, backend } => write!(f, "{} ({:?}) does not support compute shaders", adapter, backend),
+    }
+  }
+}
+
+impl std::error::Error for StateError {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      StateError::SurfaceCreation(err) => Some(err),
+      StateError::NoAdapter { .. } => None,
+      StateError::DeviceRequest { source, .. } => Some(source),
+      StateError::MissingFeature(_)
+      | StateError::NoSupportedFormat
+      | StateError::UnsupportedSurfaceUsages { .. }
+      | StateError::NoComputeShaders { .. } => None,
+      StateError::TargetTooLarge(err) => Some(err),
+    }
+  }
+}
+
+impl From<wgpu::CreateSurfaceError> for StateError {
+  fn from(err: wgpu::CreateSurfaceError) -> Self {
+    StateError::SurfaceCreation(err)
+  }
+}
+
+impl From<ExceedsLimit> for StateError {
+  fn from(err: ExceedsLimit) -> Self {
+    StateError::TargetTooLarge(err)
+  }
+}
+
+// names the well known presets so WebGL2 limit problems are easy to spot,
+// ignoring the texture sizes which are taken from the adapter
+fn limits_name(limits: &wgpu::Limits) -> &'static str {
+  let is = |preset: wgpu::Limits| preset.using_resolution(limits.clone()) == *limits;
+  if is(wgpu::Limits::downlevel_webgl2_defaults()) {
+    "the WebGL2 downlevel limits"
+  } else if is(wgpu::Limits::downlevel_defaults()) {
+    "the downlevel limits"
+  } else if is(wgpu::Limits::default()) {
+    "the default limits"
+  } else {
+    "custom limits"
+  }
+}
+
+// Returned by State::render(), see State::recover() for which are fatal
+#[derive(Clone, Debug)]
+pub enum RenderError {
+  Surface(wgpu::SurfaceError),
+  // between State::suspend() and State::resume(), there is no surface
+  Suspended,
+}
+
+impl fmt::Display for RenderError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      RenderError::Surface(err) => write!(f, "{}", err),
+      RenderError::Suspended => write!(f, "Rendering is suspended until the surface is resumed"),
+    }
+  }
+}
+
+impl std::error::Error for RenderError {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      RenderError::Surface(err) => Some(err),
+      RenderError::Suspended => None,
+    }
+  }
+}
+
+impl From<wgpu::SurfaceError> for RenderError {
+  fn from(err: wgpu::SurfaceError) -> Self {
+    RenderError::Surface(err)
+  }
+}
+
+// returned by State::set_present_mode()
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnsupportedMode {
+  pub mode: wgpu::PresentMode,
+  pub supported: Vec<wgpu::PresentMode>,
+}
+
+impl fmt::Display for UnsupportedMode {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "Present mode {:?} isn't supported by the surface (supported: {:?})", self.mode, self.supported)
+  }
+}
+
+impl std::error::Error for UnsupportedMode {}
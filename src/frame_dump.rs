@@ -0,0 +1,133 @@
+use std::fmt;
+
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+// Everything a captured frame contained, in submission order. Only built when
+// State::capture_next_frame_dump() was called, so normal frames don't allocate.
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct FrameDump {
+  pub passes: Vec<PassDump>,
+  pub uploads: Vec<UploadDump>,
+}
+
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct PassDump {
+  pub label: String,
+  pub draws: Vec<DrawDump>,
+  pub pipeline_switches: u32,
+  pub bind_group_switches: u32,
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct DrawDump {
+  pub mesh: String,
+  pub pipeline: String,
+  pub vertex_count: u32,
+  pub index_count: Option<u32>,
+  pub instance_count: u32,
+  pub topology: wgpu::PrimitiveTopology,
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct UploadDump {
+  pub label: String,
+  pub bytes: u64,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct FrameTotals {
+  pub draws: u32,
+  pub triangles: u64,
+  pub lines: u64,
+  pub points: u64,
+  pub state_changes: u32,
+  pub bytes_uploaded: u64,
+}
+
+impl DrawDump {
+  // triangles, lines or points depending on the topology
+  pub fn primitives(&self) -> u64 {
+    let elements = self.index_count.unwrap_or(self.vertex_count) as u64;
+    let per_instance = match self.topology {
+      wgpu::PrimitiveTopology::PointList => elements,
+      wgpu::PrimitiveTopology::LineList => elements / 2,
+      wgpu::PrimitiveTopology::LineStrip => elements.saturating_sub(1),
+      wgpu::PrimitiveTopology::TriangleList => elements / 3,
+      wgpu::PrimitiveTopology::TriangleStrip => elements.saturating_sub(2),
+    };
+    per_instance * self.instance_count as u64
+  }
+}
+
+impl FrameDump {
+  pub fn begin_pass(&mut self, label: &str) -> &mut PassDump {
+    self.passes.push(PassDump {
+      label: label.to_string(),
+      ..Default::default()
+    });
+    self.passes.last_mut().unwrap()
+  }
+
+  pub fn record_upload(&mut self, label: &str, bytes: u64) {
+    self.uploads.push(UploadDump {
+      label: label.to_string(),
+      bytes,
+    });
+  }
+
+  pub fn totals(&self) -> FrameTotals {
+    let mut totals = FrameTotals {
+      bytes_uploaded: self.uploads.iter().map(|upload| upload.bytes).sum(),
+      ..Default::default()
+    };
+    for pass in &self.passes {
+      totals.draws += pass.draws.len() as u32;
+      for draw in &pass.draws {
+        let count = match draw.topology {
+          wgpu::PrimitiveTopology::PointList => &mut totals.points,
+          wgpu::PrimitiveTopology::LineList | wgpu::PrimitiveTopology::LineStrip => &mut totals.lines,
+          wgpu::PrimitiveTopology::TriangleList | wgpu::PrimitiveTopology::TriangleStrip => &mut totals.triangles,
+        };
+        *count += draw.primitives();
+      }
+      totals.state_changes += pass.pipeline_switches + pass.bind_group_switches;
+    }
+    totals
+  }
+}
+
+impl fmt::Display for FrameDump {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    for pass in &self.passes {
+      writeln!(
+        f,
+        "pass \"{}\" ({} pipeline switches, {} bind group switches)",
+        pass.label, pass.pipeline_switches, pass.bind_group_switches,
+      )?;
+      writeln!(f, "  {:<24} {:<24} {:>10} {:>10} {:>10}", "mesh", "pipeline", "vertices", "indices", "instances")?;
+      for draw in &pass.draws {
+        let indices = draw.index_count.map_or_else(|| "-".to_string(), |count| count.to_string());
+        writeln!(
+          f,
+          "  {:<24} {:<24} {:>10} {:>10} {:>10}",
+          draw.mesh, draw.pipeline, draw.vertex_count, indices, draw.instance_count,
+        )?;
+      }
+    }
+    for upload in &self.uploads {
+      writeln!(f, "upload \"{}\": {} bytes", upload.label, upload.bytes)?;
+    }
+    let totals = self.totals();
+    write!(
+      f,
+      "total: {} draws, {} triangles, {} lines, {} points, {} state changes, {} bytes uploaded",
+      totals.draws, totals.triangles, totals.lines, totals.points, totals.state_changes, totals.bytes_uploaded,
+    )
+  }
+}
@@ -0,0 +1,115 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+// std::time::Instant panics on wasm32
+use web_time::Instant;
+
+use crate::FrameDump;
+
+const HISTORY_LEN: usize = 240;
+// an interval this much longer than the median counts as a missed vsync
+const MISSED_VSYNC_FACTOR: f64 = 1.5;
+
+// a present-to-present interval above the configured hitch threshold
+pub struct Hitch<'a> {
+  pub interval: Duration,
+  pub median: Duration,
+  // the frame's debug dump, when capture was armed for it
+  pub dump: Option<&'a FrameDump>,
+}
+
+type HitchCallback = Box<dyn FnMut(&Hitch)>;
+
+// Tracks the distribution of present-to-present intervals. Averages hide
+// stutter, so this keeps the raw history and derives jitter from it.
+#[derive(Default)]
+pub struct FramePacing {
+  intervals: VecDeque<Duration>,
+  last_present: Option<Instant>,
+  hitch_threshold: Duration,
+  on_hitch: Option<HitchCallback>,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct PacingStats {
+  pub samples: usize,
+  pub median: Duration,
+  // standard deviation of the intervals
+  pub jitter: Duration,
+  pub max_deviation: Duration,
+  pub missed_vsync: usize,
+}
+
+impl FramePacing {
+  // call right after present(), returns the interval since the previous one
+  pub fn record_present(&mut self, dump: Option<&FrameDump>) -> Option<Duration> {
+    let now = Instant::now();
+    let interval = now - self.last_present.replace(now)?;
+
+    if self.intervals.len() == HISTORY_LEN {
+      self.intervals.pop_front();
+    }
+    self.intervals.push_back(interval);
+
+    if let Some(on_hitch) = self.on_hitch.as_mut() {
+      if interval > self.hitch_threshold {
+        let median = median(&self.intervals);
+        on_hitch(&Hitch { interval, median, dump });
+      }
+    }
+
+    Some(interval)
+  }
+
+  // Forget the last present time, so an intentional pause (waiting for
+  // input in on-demand redraw mode, being minimized) isn't counted as a hitch.
+  pub fn pause(&mut self) {
+    self.last_present = None;
+  }
+
+  pub fn set_hitch_callback(&mut self, threshold: Duration, callback: impl FnMut(&Hitch) + 'static) {
+    self.hitch_threshold = threshold;
+    self.on_hitch = Some(Box::new(callback));
+  }
+
+  pub fn clear_hitch_callback(&mut self) {
+    self.on_hitch = None;
+  }
+
+  pub fn intervals(&self) -> impl Iterator<Item = Duration> + '_ {
+    self.intervals.iter().copied()
+  }
+
+  pub fn stats(&self) -> PacingStats {
+    if self.intervals.is_empty() {
+      return PacingStats::default();
+    }
+
+    let median = median(&self.intervals);
+    let median_secs = median.as_secs_f64();
+    let count = self.intervals.len() as f64;
+    let mean = self.intervals.iter().map(Duration::as_secs_f64).sum::<f64>() / count;
+    let variance = self.intervals.iter()
+      .map(|interval| (interval.as_secs_f64() - mean).powi(2))
+      .sum::<f64>() / count;
+    let max_deviation = self.intervals.iter()
+      .map(|interval| (interval.as_secs_f64() - median_secs).abs())
+      .fold(0.0, f64::max);
+    let missed_vsync = self.intervals.iter()
+      .filter(|interval| interval.as_secs_f64() > median_secs * MISSED_VSYNC_FACTOR)
+      .count();
+
+    PacingStats {
+      samples: self.intervals.len(),
+      median,
+      jitter: Duration::from_secs_f64(variance.sqrt()),
+      max_deviation: Duration::from_secs_f64(max_deviation),
+      missed_vsync,
+    }
+  }
+}
+
+fn median(intervals: &VecDeque<Duration>) -> Duration {
+  let mut sorted: Vec<_> = intervals.iter().copied().collect();
+  sorted.sort_unstable();
+  sorted[sorted.len() / 2]
+}
@@ -0,0 +1,154 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use crate::{FramePacing, MapTracker, ReadbackRing};
+
+// frames the rolling averages are taken over
+pub const STATS_WINDOW: usize = 120;
+
+// Where one frame's time went, see State::last_frame_stats().
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct FrameStats {
+  // recording the command buffers on the CPU
+  pub cpu_time: Duration,
+  // wall time from queue.submit() until present() returned
+  pub present_time: Duration,
+  // the main render pass on the GPU, None without Features::TIMESTAMP_QUERY.
+  // Read back asynchronously, so it belongs to a frame or two earlier.
+  pub gpu_time: Option<Duration>,
+}
+
+// FrameStats averaged over the last STATS_WINDOW frames
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct AverageFrameStats {
+  pub frames: usize,
+  pub cpu_time: Duration,
+  pub present_time: Duration,
+  pub gpu_time: Option<Duration>,
+  // from the present-to-present intervals of FramePacing
+  pub fps: f64,
+}
+
+#[derive(Default)]
+pub(crate) struct FrameTimings {
+  history: VecDeque<FrameStats>,
+  latest_gpu_time: Option<Duration>,
+}
+
+impl FrameTimings {
+  pub(crate) fn record(&mut self, cpu_time: Duration, present_time: Duration) {
+    if self.history.len() == STATS_WINDOW {
+      self.history.pop_front();
+    }
+    self.history.push_back(FrameStats {
+      cpu_time,
+      present_time,
+      gpu_time: self.latest_gpu_time,
+    });
+  }
+
+  pub(crate) fn record_gpu_time(&mut self, gpu_time: Duration) {
+    self.latest_gpu_time = Some(gpu_time);
+    if let Some(last) = self.history.back_mut() {
+      last.gpu_time = Some(gpu_time);
+    }
+  }
+
+  pub(crate) fn last(&self) -> Option<FrameStats> {
+    self.history.back().copied()
+  }
+
+  pub(crate) fn average(&self, pacing: &FramePacing) -> AverageFrameStats {
+    let frames = self.history.len();
+    if frames == 0 {
+      return AverageFrameStats::default();
+    }
+
+    let cpu_time = self.history.iter().map(|stats| stats.cpu_time).sum::<Duration>() / frames as u32;
+    let present_time = self.history.iter().map(|stats| stats.present_time).sum::<Duration>() / frames as u32;
+    let gpu_times: Vec<_> = self.history.iter().filter_map(|stats| stats.gpu_time).collect();
+    let gpu_time = (!gpu_times.is_empty()).then(|| gpu_times.iter().sum::<Duration>() / gpu_times.len() as u32);
+
+    let intervals: Vec<_> = pacing.intervals().collect();
+    let recent = &intervals[intervals.len().saturating_sub(STATS_WINDOW)..];
+    let elapsed = recent.iter().sum::<Duration>().as_secs_f64();
+    let fps = if elapsed > 0.0 { recent.len() as f64 / elapsed } else { 0.0 };
+
+    AverageFrameStats {
+      frames,
+      cpu_time,
+      present_time,
+      gpu_time,
+      fps,
+    }
+  }
+}
+
+// Timestamps written at the start and end of the main render pass, resolved
+// into a buffer and read back through a one slot ReadbackRing without
+// stalling. While a readback is still in flight the following frames aren't
+// timed.
+pub(crate) struct GpuTimer {
+  query_set: wgpu::QuerySet,
+  resolve_buffer: wgpu::Buffer,
+  readback: ReadbackRing<u64>,
+  // nanoseconds per timestamp tick
+  period: f32,
+}
+
+const QUERY_BYTES: u64 = 2 * wgpu::QUERY_SIZE as u64;
+
+impl GpuTimer {
+  // None when the device wasn't created with TIMESTAMP_QUERY
+  pub(crate) fn new(device: &wgpu::Device, queue: &wgpu::Queue, tracker: &MapTracker) -> Option<Self> {
+    if !device.features().contains(wgpu::Features::TIMESTAMP_QUERY) {
+      return None;
+    }
+    let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+      label: Some("Frame Timestamps"),
+      ty: wgpu::QueryType::Timestamp,
+      count: 2,
+    });
+    let resolve_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+      label: Some("Timestamp Resolve Buffer"),
+      size: QUERY_BYTES,
+      usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+      mapped_at_creation: false,
+    });
+    let readback = ReadbackRing::new(device, 1, QUERY_BYTES).ok()?.tracked_by(tracker.clone());
+
+    Some(Self {
+      query_set,
+      resolve_buffer,
+      readback,
+      period: queue.get_timestamp_period(),
+    })
+  }
+
+  // None while the previous readback hasn't come back yet
+  pub(crate) fn timestamp_writes(&self) -> Option<wgpu::RenderPassTimestampWrites<'_>> {
+    (self.readback.in_flight() == 0).then_some(wgpu::RenderPassTimestampWrites {
+      query_set: &self.query_set,
+      beginning_of_pass_write_index: Some(0),
+      end_of_pass_write_index: Some(1),
+    })
+  }
+
+  // call after the timed pass, in the same encoder
+  pub(crate) fn encode_resolve(&mut self, encoder: &mut wgpu::CommandEncoder) {
+    encoder.resolve_query_set(&self.query_set, 0..2, &self.resolve_buffer, 0);
+    self.readback.request_buffer(encoder, &self.resolve_buffer, 0, QUERY_BYTES);
+  }
+
+  // call once the resolve was submitted
+  pub(crate) fn map(&mut self) {
+    self.readback.after_submit();
+  }
+
+  // None while the mapping is still pending or when it failed
+  pub(crate) fn try_read(&mut self) -> Option<Duration> {
+    let timestamps = self.readback.poll().last()?.data;
+    let ticks = timestamps[1].saturating_sub(timestamps[0]);
+    Some(Duration::from_nanos((ticks as f64 * self.period as f64) as u64))
+  }
+}
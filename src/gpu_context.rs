@@ -0,0 +1,310 @@
+use wgpu::util::DeviceExt;
+
+use crate::limits::{self, ExceedsLimit};
+use crate::readback::{self, Readback, ReadbackId, ReadbackRing};
+use crate::{CapabilityReport, MapTracker, State, StateError, StateOptions};
+
+// A device without any window, surface or render pipeline, for compute-only
+// jobs such as image processing in command line tools.
+pub struct GpuContext {
+  adapter: wgpu::Adapter,
+  device: wgpu::Device,
+  queue: wgpu::Queue,
+  capabilities: CapabilityReport,
+  // for the rings of create_readback_ring()
+  map_tracker: MapTracker,
+}
+
+impl GpuContext {
+  // any backend, like State::new_headless()
+  pub fn new_compute() -> Result<GpuContext, StateError> {
+    let options = StateOptions::default()
+      .backends(wgpu::Backends::all())
+      .power_preference(wgpu::PowerPreference::HighPerformance);
+    GpuContext::new_compute_with_options(options)
+  }
+
+  pub fn new_compute_with_options(options: StateOptions) -> Result<GpuContext, StateError> {
+    pollster::block_on(GpuContext::new_compute_async_with_options(options))
+  }
+
+  // The adapter and device are requested like a headless State's, except
+  // that the limits are the adapter's own: tools want whatever buffer sizes
+  // the hardware can do. Only the backends, power preference and features
+  // of `options` apply.
+  pub async fn new_compute_async_with_options(options: StateOptions) -> Result<GpuContext, StateError> {
+    let (_, adapter) = State::request_headless_adapter(&options).await?;
+
+    let capabilities = CapabilityReport::new(&adapter);
+    if !capabilities.compute_shaders {
+      return Err(StateError::NoComputeShaders {
+        adapter: capabilities.adapter_name.clone(),
+        backend: capabilities.backend,
+      });
+    }
+
+    let (device, queue) = State::request_device_with_limits(&adapter, &options, adapter.limits()).await?;
+
+    Ok(Self {
+      adapter,
+      device,
+      queue,
+      capabilities,
+      map_tracker: MapTracker::default(),
+    })
+  }
+
+  pub fn adapter(&self) -> &wgpu::Adapter {
+    &self.adapter
+  }
+
+  pub fn device(&self) -> &wgpu::Device {
+    &self.device
+  }
+
+  pub fn queue(&self) -> &wgpu::Queue {
+    &self.queue
+  }
+
+  pub fn capability_report(&self) -> &CapabilityReport {
+    &self.capabilities
+  }
+
+  // storage buffer that can also be copied from (for readback) and written to
+  pub fn create_storage_buffer<T: bytemuck::Pod>(&self, label: &str, contents: &[T]) -> Result<wgpu::Buffer, ExceedsLimit> {
+    let size = std::mem::size_of_val(contents) as u64;
+    limits::check_storage_buffer(&self.device.limits(), "storage buffer size", size)?;
+
+    Ok(self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+      label: Some(label),
+      contents: bytemuck::cast_slice(contents),
+      usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+    }))
+  }
+
+  pub fn create_compute_pipeline(&self, label: &str, wgsl: &str, entry_point: &str) -> wgpu::ComputePipeline {
+    let module = self.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+      label: Some(label),
+      source: wgpu::ShaderSource::Wgsl(wgsl.into()),
+    });
+
+    // the layout is derived from the shader
+    self.device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+      label: Some(label),
+      layout: None,
+      module: &module,
+      entry_point,
+      compilation_options: wgpu::PipelineCompilationOptions::default(),
+      cache: None,
+    })
+  }
+
+  // binds each buffer at the matching binding index of group 0
+  pub fn bind_buffers(&self, pipeline: &wgpu::ComputePipeline, buffers: &[&wgpu::Buffer]) -> Result<wgpu::BindGroup, ExceedsLimit> {
+    limits::check(
+      "storage buffers per shader stage",
+      buffers.len() as u64,
+      self.device.limits().max_storage_buffers_per_shader_stage as u64,
+      "pack the data into fewer buffers",
+    )?;
+
+    let resources: Vec<_> = buffers.iter().map(|buffer| buffer.as_entire_binding()).collect();
+    Ok(self.bind_resources(pipeline, &resources))
+  }
+
+  // binds each resource, e.g. a storage texture's view, at the matching
+  // binding index of group 0
+  pub fn bind_resources(&self, pipeline: &wgpu::ComputePipeline, resources: &[wgpu::BindingResource]) -> wgpu::BindGroup {
+    let entries: Vec<_> = resources.iter()
+      .enumerate()
+      .map(|(binding, resource)| wgpu::BindGroupEntry {
+        binding: binding as u32,
+        resource: resource.clone(),
+      })
+      .collect();
+
+    self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+      label: Some("Compute Bind Group"),
+      layout: &pipeline.get_bind_group_layout(0),
+      entries: &entries,
+    })
+  }
+
+  pub fn dispatch(&self, pipeline: &wgpu::ComputePipeline, bind_group: &wgpu::BindGroup, workgroups: [u32; 3]) {
+    let encoder = self.encode_dispatch(pipeline, bind_group, workgroups);
+    self.queue.submit(std::iter::once(encoder.finish()));
+  }
+
+  fn encode_dispatch(
+    &self,
+    pipeline: &wgpu::ComputePipeline,
+    bind_group: &wgpu::BindGroup,
+    workgroups: [u32; 3],
+  ) -> wgpu::CommandEncoder {
+    let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+      label: Some("Compute Encoder"),
+    });
+    {
+      let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+        label: Some("Compute Pass"),
+        timestamp_writes: None,
+      });
+      compute_pass.set_pipeline(pipeline);
+      compute_pass.set_bind_group(0, bind_group, &[]);
+      compute_pass.dispatch_workgroups(workgroups[0], workgroups[1], workgroups[2]);
+    }
+    encoder
+  }
+
+  // Blocking readback of a whole COPY_SRC buffer. Fine for tools; on a render
+  // thread use a ReadbackRing instead.
+  pub fn read_buffer<T: bytemuck::Pod>(&self, buffer: &wgpu::Buffer) -> Result<Vec<T>, wgpu::BufferAsyncError> {
+    let staging = self.staging_buffer(buffer.size());
+    let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+      label: Some("Readback Encoder"),
+    });
+    encoder.copy_buffer_to_buffer(buffer, 0, &staging, 0, buffer.size());
+    self.queue.submit(std::iter::once(encoder.finish()));
+
+    let data = bytemuck::pod_collect_to_vec(&self.map_blocking(&staging)?);
+    staging.unmap();
+    Ok(data)
+  }
+
+  // 2D texture a compute shader can write and read_texture() can read back;
+  // formats without storage support fail validation when created
+  pub fn create_storage_texture(
+    &self,
+    label: &str,
+    width: u32,
+    height: u32,
+    format: wgpu::TextureFormat,
+  ) -> Result<wgpu::Texture, ExceedsLimit> {
+    limits::check_texture_2d(&self.device.limits(), "storage texture size", width, height)?;
+
+    Ok(self.device.create_texture(&wgpu::TextureDescriptor {
+      label: Some(label),
+      size: wgpu::Extent3d {
+        width,
+        height,
+        depth_or_array_layers: 1,
+      },
+      mip_level_count: 1,
+      sample_count: 1,
+      dimension: wgpu::TextureDimension::D2,
+      format,
+      usage: wgpu::TextureUsages::STORAGE_BINDING
+        | wgpu::TextureUsages::TEXTURE_BINDING
+        | wgpu::TextureUsages::COPY_SRC
+        | wgpu::TextureUsages::COPY_DST,
+      view_formats: &[],
+    }))
+  }
+
+  // replaces the whole of mip level 0, `texels` are tightly packed rows
+  pub fn write_texture<T: bytemuck::Pod>(&self, texture: &wgpu::Texture, texels: &[T]) {
+    let texel_size = texture.format().block_copy_size(None).expect("a color format");
+    self.queue.write_texture(
+      texture.as_image_copy(),
+      bytemuck::cast_slice(texels),
+      wgpu::ImageDataLayout {
+        offset: 0,
+        bytes_per_row: Some(texture.width() * texel_size),
+        rows_per_image: Some(texture.height()),
+      },
+      texture.size(),
+    );
+  }
+
+  // Blocking readback of mip level 0 of a COPY_SRC texture as tightly packed
+  // rows, like read_buffer()
+  pub fn read_texture<T: bytemuck::Pod>(&self, texture: &wgpu::Texture) -> Result<Vec<T>, wgpu::BufferAsyncError> {
+    let texel_size = texture.format().block_copy_size(None).expect("a color format");
+    let row_bytes = texture.width() * texel_size;
+    let padded_row_bytes = readback::padded_bytes_per_row(row_bytes);
+    let staging = self.staging_buffer(padded_row_bytes as u64 * texture.height() as u64);
+    let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+      label: Some("Readback Encoder"),
+    });
+    encoder.copy_texture_to_buffer(
+      texture.as_image_copy(),
+      wgpu::ImageCopyBuffer {
+        buffer: &staging,
+        layout: wgpu::ImageDataLayout {
+          offset: 0,
+          // copies need rows padded to 256 bytes
+          bytes_per_row: Some(padded_row_bytes),
+          rows_per_image: Some(texture.height()),
+        },
+      },
+      texture.size(),
+    );
+    self.queue.submit(std::iter::once(encoder.finish()));
+
+    let packed: Vec<u8> = self.map_blocking(&staging)?
+      .chunks(padded_row_bytes as usize)
+      .flat_map(|row| &row[..row_bytes as usize])
+      .copied()
+      .collect();
+    staging.unmap();
+    Ok(bytemuck::pod_collect_to_vec(&packed))
+  }
+
+  // a ring whose mappings poll_readbacks() keeps polling for
+  pub fn create_readback_ring<T: bytemuck::Pod>(
+    &self,
+    slot_count: usize,
+    slot_size: wgpu::BufferAddress,
+  ) -> Result<ReadbackRing<T>, ExceedsLimit> {
+    Ok(ReadbackRing::new(&self.device, slot_count, slot_size)?.tracked_by(self.map_tracker.clone()))
+  }
+
+  // Like dispatch(), then copies `size` bytes of `output` into `ring` in the
+  // same submission. None, with the dispatch still submitted, when the ring
+  // has no free slot; see ReadbackRing::request_buffer().
+  pub fn dispatch_with_readback<T: bytemuck::Pod>(
+    &self,
+    pipeline: &wgpu::ComputePipeline,
+    bind_group: &wgpu::BindGroup,
+    workgroups: [u32; 3],
+    ring: &mut ReadbackRing<T>,
+    output: &wgpu::Buffer,
+    size: wgpu::BufferAddress,
+  ) -> Option<ReadbackId> {
+    let mut encoder = self.encode_dispatch(pipeline, bind_group, workgroups);
+    let id = ring.request_buffer(&mut encoder, output, 0, size);
+    self.queue.submit(std::iter::once(encoder.finish()));
+    ring.after_submit();
+    id
+  }
+
+  // polls the device without blocking while mappings are outstanding, then
+  // hands back every readback of `ring` that finished, like State::poll_readbacks()
+  pub fn poll_readbacks<'a, T: bytemuck::Pod>(&self, ring: &'a mut ReadbackRing<T>) -> impl Iterator<Item = Readback<T>> + 'a {
+    if self.map_tracker.outstanding() > 0 {
+      self.device.poll(wgpu::Maintain::Poll);
+    }
+    ring.poll()
+  }
+
+  fn staging_buffer(&self, size: wgpu::BufferAddress) -> wgpu::Buffer {
+    self.device.create_buffer(&wgpu::BufferDescriptor {
+      label: Some("Readback Staging Buffer"),
+      size,
+      usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+      mapped_at_creation: false,
+    })
+  }
+
+  // waits for the submitted copy into `staging`, which the caller unmaps
+  fn map_blocking<'a>(&self, staging: &'a wgpu::Buffer) -> Result<wgpu::BufferView<'a>, wgpu::BufferAsyncError> {
+    let (sender, receiver) = futures::channel::oneshot::channel();
+    staging.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+      let _ = sender.send(result);
+    });
+    self.device.poll(wgpu::Maintain::Wait);
+    // a dropped callback means the mapping never happened
+    pollster::block_on(receiver).unwrap_or(Err(wgpu::BufferAsyncError))?;
+    Ok(staging.slice(..).get_mapped_range())
+  }
+}
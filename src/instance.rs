@@ -0,0 +1,105 @@
+use std::ops::Range;
+
+use cgmath::{Matrix4, Quaternion, Vector3};
+
+// One copy of the mesh, see State::set_instances().
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Instance {
+  pub position: Vector3<f32>,
+  pub rotation: Quaternion<f32>,
+}
+
+impl Default for Instance {
+  fn default() -> Self {
+    Self {
+      position: Vector3::new(0.0, 0.0, 0.0),
+      rotation: Quaternion::new(1.0, 0.0, 0.0, 0.0),
+    }
+  }
+}
+
+impl Instance {
+  pub fn new(position: Vector3<f32>, rotation: Quaternion<f32>) -> Self {
+    Self { position, rotation }
+  }
+
+  pub fn to_raw(&self) -> InstanceRaw {
+    InstanceRaw {
+      model: (Matrix4::from_translation(self.position) * Matrix4::from(self.rotation)).into(),
+    }
+  }
+}
+
+// what actually goes into the instance buffer, cgmath types aren't Pod
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct InstanceRaw {
+  pub model: [[f32; 4]; 4],
+}
+
+impl InstanceRaw {
+  pub(crate) fn desc() -> wgpu::VertexBufferLayout<'static> {
+    wgpu::VertexBufferLayout {
+      array_stride: std::mem::size_of::<InstanceRaw>() as wgpu::BufferAddress,
+      // the shader only moves on to the next instance's data when it starts a new instance
+      step_mode: wgpu::VertexStepMode::Instance,
+      // a mat4 takes up four vec4 slots; locations 5 to 8 leave room for
+      // more per-vertex attributes later
+      attributes: &[
+        wgpu::VertexAttribute {
+          offset: 0,
+          shader_location: 5,
+          format: wgpu::VertexFormat::Float32x4,
+        },
+        wgpu::VertexAttribute {
+          offset: std::mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
+          shader_location: 6,
+          format: wgpu::VertexFormat::Float32x4,
+        },
+        wgpu::VertexAttribute {
+          offset: std::mem::size_of::<[f32; 8]>() as wgpu::BufferAddress,
+          shader_location: 7,
+          format: wgpu::VertexFormat::Float32x4,
+        },
+        wgpu::VertexAttribute {
+          offset: std::mem::size_of::<[f32; 12]>() as wgpu::BufferAddress,
+          shader_location: 8,
+          format: wgpu::VertexFormat::Float32x4,
+        },
+      ],
+    }
+  }
+}
+
+// Instances written since the last upload, as index ranges, see
+// State::update_instances_range()
+#[derive(Default)]
+pub(crate) struct DirtyRanges {
+  ranges: Vec<Range<usize>>,
+}
+
+impl DirtyRanges {
+  pub(crate) fn mark(&mut self, range: Range<usize>) {
+    if !range.is_empty() {
+      self.ranges.push(range);
+    }
+  }
+
+  pub(crate) fn clear(&mut self) {
+    self.ranges.clear();
+  }
+
+  // sorted, with overlapping and adjacent ranges merged into one write
+  pub(crate) fn take_coalesced(&mut self) -> Vec<Range<usize>> {
+    let mut ranges = std::mem::take(&mut self.ranges);
+    ranges.sort_by_key(|range| range.start);
+    let mut merged: Vec<Range<usize>> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+      match merged.last_mut() {
+        Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+        _ => merged.push(range),
+      }
+    }
+    merged
+  }
+}
@@ -0,0 +1,56 @@
+use std::fmt;
+
+// A requested size or count the device can't handle. Checked up front so
+// callers get an actionable error instead of a wgpu validation panic.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExceedsLimit {
+  pub what: &'static str,
+  pub requested: u64,
+  pub limit: u64,
+  pub suggestion: &'static str,
+}
+
+impl fmt::Display for ExceedsLimit {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+      f,
+      "{} of {} exceeds the device limit of {}: {}",
+      self.what, self.requested, self.limit, self.suggestion,
+    )
+  }
+}
+
+impl std::error::Error for ExceedsLimit {}
+
+pub(crate) fn check(what: &'static str, requested: u64, limit: u64, suggestion: &'static str) -> Result<(), ExceedsLimit> {
+  if requested > limit {
+    return Err(ExceedsLimit {
+      what,
+      requested,
+      limit,
+      suggestion,
+    });
+  }
+  Ok(())
+}
+
+pub(crate) fn check_texture_2d(limits: &wgpu::Limits, what: &'static str, width: u32, height: u32) -> Result<(), ExceedsLimit> {
+  let suggestion = "downscale the image or split it into tiles";
+  let limit = limits.max_texture_dimension_2d as u64;
+  check(what, width as u64, limit, suggestion)?;
+  check(what, height as u64, limit, suggestion)
+}
+
+pub(crate) fn check_buffer_size(limits: &wgpu::Limits, what: &'static str, size: u64) -> Result<(), ExceedsLimit> {
+  check(what, size, limits.max_buffer_size, "split the data across several buffers")
+}
+
+pub(crate) fn check_storage_buffer(limits: &wgpu::Limits, what: &'static str, size: u64) -> Result<(), ExceedsLimit> {
+  check_buffer_size(limits, what, size)?;
+  check(
+    what,
+    size,
+    limits.max_storage_buffer_binding_size as u64,
+    "bind the buffer in smaller ranges or split it",
+  )
+}
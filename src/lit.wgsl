@@ -0,0 +1,64 @@
+// vertex shader
+struct VertexInput {
+  @location(0) position: vec3<f32>,
+  @location(1) normal: vec3<f32>,
+  @location(2) tex_coords: vec2<f32>,
+  @location(3) tangent: vec4<f32>,
+};
+
+struct VertexOutput {
+  @builtin(position) clip_position: vec4<f32>,
+  @location(0) tex_coords: vec2<f32>,
+  @location(1) world_normal: vec3<f32>,
+  @location(2) world_tangent: vec3<f32>,
+  @location(3) world_bitangent: vec3<f32>,
+}
+
+@vertex
+fn vs_main(model: VertexInput, instance: InstanceInput) -> VertexOutput {
+  let model_matrix = uniforms.transform * mat4x4<f32>(
+    instance.model_matrix_0,
+    instance.model_matrix_1,
+    instance.model_matrix_2,
+    instance.model_matrix_3,
+  );
+  var out: VertexOutput;
+  out.tex_coords = model.tex_coords;
+  // no inverse transpose, so non-uniform scales skew the normals a little
+  out.world_normal = (model_matrix * vec4<f32>(model.normal, 0.0)).xyz;
+  out.world_tangent = (model_matrix * vec4<f32>(model.tangent.xyz, 0.0)).xyz;
+  // w is the handedness, see mesh::compute_tangents()
+  out.world_bitangent = cross(out.world_normal, out.world_tangent) * model.tangent.w;
+  out.clip_position = uniforms.view_proj * model_matrix * vec4<f32>(model.position, 1.0);
+
+  return out;
+}
+
+// fragment shader
+
+// see Material
+@group(1) @binding(0)
+var t_diffuse: texture_2d<f32>;
+@group(1) @binding(1)
+var s_diffuse: sampler;
+@group(1) @binding(2)
+var t_normal: texture_2d<f32>;
+@group(1) @binding(3)
+var s_normal: sampler;
+
+// see State::set_environment()
+@group(2) @binding(0)
+var t_irradiance: texture_cube<f32>;
+@group(2) @binding(1)
+var s_irradiance: sampler;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+  let base = textureSample(t_diffuse, s_diffuse, in.tex_coords);
+  // tangent space normal from the map, into world space through the TBN basis
+  let tangent_normal = textureSample(t_normal, s_normal, in.tex_coords).xyz * 2.0 - 1.0;
+  let tbn = mat3x3<f32>(normalize(in.world_tangent), normalize(in.world_bitangent), normalize(in.world_normal));
+  let normal = normalize(tbn * tangent_normal);
+  let irradiance = textureSample(t_irradiance, s_irradiance, normal).rgb;
+  return encode_output(vec4<f32>(base.rgb * irradiance, base.a));
+}
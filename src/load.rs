@@ -0,0 +1,82 @@
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+// Shared flag a UI thread can set to abort a running load.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn cancel(&self) {
+    self.0.store(true, Ordering::Release);
+  }
+
+  pub fn is_cancelled(&self) -> bool {
+    self.0.load(Ordering::Acquire)
+  }
+}
+
+// Returned (inside the anyhow error) when a load was cancelled, check with
+// `err.is::<Cancelled>()`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "Load was cancelled")
+  }
+}
+
+impl std::error::Error for Cancelled {}
+
+type ProgressCallback<'a> = Box<dyn Fn(&str, f32) + 'a>;
+
+// Progress reporting and cancellation for long asset loads. Loaders report
+// (stage name, fraction of that stage) and check for cancellation between
+// steps; anything created before the cancellation is dropped with the error.
+#[derive(Default)]
+pub struct LoadContext<'a> {
+  progress: Option<ProgressCallback<'a>>,
+  cancellation: CancellationToken,
+}
+
+impl<'a> LoadContext<'a> {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn with_progress(mut self, progress: impl Fn(&str, f32) + 'a) -> Self {
+    self.progress = Some(Box::new(progress));
+    self
+  }
+
+  pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+    self.cancellation = token;
+    self
+  }
+
+  pub fn report(&self, stage: &str, fraction: f32) {
+    if let Some(progress) = &self.progress {
+      progress(stage, fraction.clamp(0.0, 1.0));
+    }
+  }
+
+  pub fn check(&self) -> Result<(), Cancelled> {
+    if self.cancellation.is_cancelled() {
+      Err(Cancelled)
+    } else {
+      Ok(())
+    }
+  }
+
+  // report progress and bail out if the load was cancelled meanwhile
+  pub fn step(&self, stage: &str, fraction: f32) -> Result<(), Cancelled> {
+    self.check()?;
+    self.report(stage, fraction);
+    Ok(())
+  }
+}
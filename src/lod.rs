@@ -0,0 +1,118 @@
+// A level of detail group: meshes ordered from most to least detailed, each
+// used up to its switch distance. Beyond the last distance nothing is drawn.
+#[derive(Clone, Debug)]
+pub struct LodGroup<M> {
+  levels: Vec<LodLevel<M>>,
+  hysteresis: f32,
+  forced_level: Option<usize>,
+}
+
+#[derive(Clone, Debug)]
+pub struct LodLevel<M> {
+  pub mesh: M,
+  pub max_distance: f32,
+}
+
+impl<M> Default for LodGroup<M> {
+  fn default() -> Self {
+    Self {
+      levels: Vec::new(),
+      hysteresis: 0.0,
+      forced_level: None,
+    }
+  }
+}
+
+impl<M> LodGroup<M> {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  // levels are kept sorted by switch distance, so they can be added in any order
+  pub fn with_level(mut self, mesh: M, max_distance: f32) -> Self {
+    let position = self.levels.partition_point(|level| level.max_distance <= max_distance);
+    self.levels.insert(position, LodLevel { mesh, max_distance });
+    self
+  }
+
+  // fraction of the switch distance an instance has to move past a boundary
+  // before it changes level, e.g. 0.1 for a 10% band
+  pub fn with_hysteresis(mut self, hysteresis: f32) -> Self {
+    self.hysteresis = hysteresis.clamp(0.0, 1.0);
+    self
+  }
+
+  // debug override: always pick this level (clamped to the coarsest one)
+  pub fn force_level(&mut self, level: Option<usize>) {
+    self.forced_level = level;
+  }
+
+  pub fn levels(&self) -> &[LodLevel<M>] {
+    &self.levels
+  }
+
+  pub fn mesh(&self, level: usize) -> &M {
+    &self.levels[level].mesh
+  }
+
+  // the same levels and settings with every mesh converted, e.g. uploaded
+  pub fn try_map<N, E>(&self, mut f: impl FnMut(&M) -> Result<N, E>) -> Result<LodGroup<N>, E> {
+    let levels = self.levels.iter()
+      .map(|level| Ok(LodLevel { mesh: f(&level.mesh)?, max_distance: level.max_distance }))
+      .collect::<Result<_, E>>()?;
+    Ok(LodGroup {
+      levels,
+      hysteresis: self.hysteresis,
+      forced_level: self.forced_level,
+    })
+  }
+
+  // `previous` is the level the instance used last frame, if any, and
+  // is what the hysteresis band is measured against
+  pub fn select(&self, distance: f32, previous: Option<usize>) -> Option<usize> {
+    if self.levels.is_empty() {
+      return None;
+    }
+    if let Some(level) = self.forced_level {
+      return Some(level.min(self.levels.len() - 1));
+    }
+
+    self.levels.iter().enumerate()
+      .find(|(index, level)| {
+        let boundary = match previous {
+          // moving back to a finer level has to clear the band on the near side
+          Some(previous) if previous > *index => level.max_distance * (1.0 - self.hysteresis),
+          Some(_) => level.max_distance * (1.0 + self.hysteresis),
+          None => level.max_distance,
+        };
+        distance <= boundary
+      })
+      .map(|(index, _)| index)
+  }
+}
+
+// how many instances were drawn at each level, for culling statistics
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LodStats {
+  pub drawn_per_level: Vec<u32>,
+  pub culled: u32,
+}
+
+impl LodStats {
+  pub fn record(&mut self, level: Option<usize>) {
+    match level {
+      Some(level) => {
+        if self.drawn_per_level.len() <= level {
+          self.drawn_per_level.resize(level + 1, 0);
+        }
+        self.drawn_per_level[level] += 1;
+      }
+      None => self.culled += 1,
+    }
+  }
+
+  pub fn clear(&mut self) {
+    self.drawn_per_level.clear();
+    self.culled = 0;
+  }
+}
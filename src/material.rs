@@ -0,0 +1,85 @@
+use crate::texture::{ColorSpace, Texture};
+use crate::TextureId;
+
+// The textures of a lit mesh, see State::set_lit_mesh(). Normal maps are
+// tangent space (+Z out of the surface) and should be loaded with
+// ColorSpace::Linear.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Material {
+  pub base_color: TextureId,
+  // None keeps the vertex normals
+  pub normal_map: Option<TextureId>,
+}
+
+impl Material {
+  pub fn new(base_color: TextureId) -> Self {
+    Self { base_color, normal_map: None }
+  }
+
+  pub fn with_normal_map(mut self, normal_map: TextureId) -> Self {
+    self.normal_map = Some(normal_map);
+    self
+  }
+}
+
+// lit.wgsl's @group(1): the base color, then the normal map
+pub(crate) fn bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+  let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+    binding,
+    visibility: wgpu::ShaderStages::FRAGMENT,
+    ty: wgpu::BindingType::Texture {
+      sample_type: wgpu::TextureSampleType::Float { filterable: true },
+      view_dimension: wgpu::TextureViewDimension::D2,
+      multisampled: false,
+    },
+    count: None,
+  };
+  let sampler_entry = |binding| wgpu::BindGroupLayoutEntry {
+    binding,
+    visibility: wgpu::ShaderStages::FRAGMENT,
+    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+    count: None,
+  };
+
+  device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+    label: Some("Material Bind Group Layout"),
+    entries: &[texture_entry(0), sampler_entry(1), texture_entry(2), sampler_entry(3)],
+  })
+}
+
+pub(crate) fn bind_group(
+  device: &wgpu::Device,
+  layout: &wgpu::BindGroupLayout,
+  base_color: &Texture,
+  normal_map: &Texture,
+) -> wgpu::BindGroup {
+  device.create_bind_group(&wgpu::BindGroupDescriptor {
+    label: Some("Material Bind Group"),
+    layout,
+    entries: &[
+      wgpu::BindGroupEntry {
+        binding: 0,
+        resource: wgpu::BindingResource::TextureView(&base_color.view),
+      },
+      wgpu::BindGroupEntry {
+        binding: 1,
+        resource: wgpu::BindingResource::Sampler(&base_color.sampler),
+      },
+      wgpu::BindGroupEntry {
+        binding: 2,
+        resource: wgpu::BindingResource::TextureView(&normal_map.view),
+      },
+      wgpu::BindGroupEntry {
+        binding: 3,
+        resource: wgpu::BindingResource::Sampler(&normal_map.sampler),
+      },
+    ],
+  })
+}
+
+// bound for materials without a normal map, decodes to the vertex normal
+pub(crate) fn flat_normal_map(device: &wgpu::Device, queue: &wgpu::Queue) -> Texture {
+  let image = image::RgbaImage::from_pixel(1, 1, image::Rgba([128, 128, 255, 255]));
+  Texture::from_image(device, queue, &image.into(), "Flat Normal Map", ColorSpace::Linear)
+    .expect("a 1x1 texture is within every limit")
+}
@@ -0,0 +1,264 @@
+use std::collections::HashMap;
+
+use cgmath::{InnerSpace, Vector3};
+
+// Sine of the smallest angle two edges can enclose and still count as a
+// triangle. Relative to the edge lengths, so tiny but valid triangles are
+// kept whatever the scale of the mesh.
+const DEGENERATE_EPSILON: f32 = 1e-6;
+// used for vertices that only touch degenerate triangles
+const FALLBACK_NORMAL: [f32; 3] = [0.0, 1.0, 0.0];
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum NormalMode {
+  // one normal per face, vertices shared by faces with different normals
+  // get duplicated
+  Flat,
+  // area weighted average of the faces around each vertex
+  Smooth,
+}
+
+pub struct NormalMesh {
+  pub positions: Vec<[f32; 3]>,
+  pub normals: Vec<[f32; 3]>,
+  pub indices: Vec<u32>,
+  // for each output vertex the input vertex it came from, so callers can
+  // carry over colors or texture coordinates after Flat splits vertices
+  pub source_vertices: Vec<u32>,
+}
+
+pub fn compute_normals(positions: &[[f32; 3]], indices: &[u32], mode: NormalMode) -> NormalMesh {
+  match mode {
+    NormalMode::Flat => flat_normals(positions, indices),
+    NormalMode::Smooth => smooth_normals(positions, indices),
+  }
+}
+
+// unnormalized face normal, its length is twice the triangle's area; None
+// for degenerate triangles
+fn face_normal(positions: &[[f32; 3]], triangle: &[u32]) -> Option<Vector3<f32>> {
+  let a = Vector3::from(positions[triangle[0] as usize]);
+  let edge1 = Vector3::from(positions[triangle[1] as usize]) - a;
+  let edge2 = Vector3::from(positions[triangle[2] as usize]) - a;
+  let normal = edge1.cross(edge2);
+  (normal.magnitude() > DEGENERATE_EPSILON * edge1.magnitude() * edge2.magnitude()).then_some(normal)
+}
+
+fn flat_normals(positions: &[[f32; 3]], indices: &[u32]) -> NormalMesh {
+  let mut mesh = NormalMesh {
+    positions: Vec::with_capacity(indices.len()),
+    normals: Vec::with_capacity(indices.len()),
+    indices: Vec::with_capacity(indices.len()),
+    source_vertices: Vec::with_capacity(indices.len()),
+  };
+
+  // output vertex for each (input vertex, normal) pair, so the triangles of
+  // a planar face keep sharing their vertices
+  let mut split = HashMap::new();
+  for triangle in indices.chunks_exact(3) {
+    let Some(normal) = face_normal(positions, triangle) else { continue };
+    let normal: [f32; 3] = normal.normalize().into();
+
+    for &index in triangle {
+      // + 0.0 turns -0.0 into 0.0, which compare equal but have different bits
+      let key = (index, normal.map(|n| (n + 0.0).to_bits()));
+      let vertex = *split.entry(key).or_insert_with(|| {
+        mesh.positions.push(positions[index as usize]);
+        mesh.normals.push(normal);
+        mesh.source_vertices.push(index);
+        mesh.positions.len() as u32 - 1
+      });
+      mesh.indices.push(vertex);
+    }
+  }
+
+  mesh
+}
+
+fn smooth_normals(positions: &[[f32; 3]], indices: &[u32]) -> NormalMesh {
+  let mut sums = vec![Vector3::new(0.0, 0.0, 0.0); positions.len()];
+  // total length of the face normals added up, to tell cancelling faces
+  // apart from small ones
+  let mut weights = vec![0.0; positions.len()];
+
+  for triangle in indices.chunks_exact(3) {
+    // not normalizing here is what weights each face by its area
+    let Some(normal) = face_normal(positions, triangle) else { continue };
+    for &index in triangle {
+      sums[index as usize] += normal;
+      weights[index as usize] += normal.magnitude();
+    }
+  }
+
+  let normals = sums.into_iter().zip(weights)
+    .map(|(sum, weight)| {
+      if sum.magnitude() <= DEGENERATE_EPSILON * weight {
+        FALLBACK_NORMAL
+      } else {
+        sum.normalize().into()
+      }
+    })
+    .collect();
+
+  NormalMesh {
+    positions: positions.to_vec(),
+    normals,
+    indices: indices.to_vec(),
+    source_vertices: (0..positions.len() as u32).collect(),
+  }
+}
+
+// per-vertex tangents for normal mapping, xyz is the tangent and w the
+// handedness of the bitangent (bitangent = cross(normal, tangent) * w).
+// Panics unless there's a normal and texture coordinate per position.
+pub fn compute_tangents(
+  positions: &[[f32; 3]],
+  normals: &[[f32; 3]],
+  tex_coords: &[[f32; 2]],
+  indices: &[u32],
+) -> Vec<[f32; 4]> {
+  assert_eq!(normals.len(), positions.len(), "compute_tangents() needs a normal per position");
+  assert_eq!(tex_coords.len(), positions.len(), "compute_tangents() needs texture coordinates per position");
+  let mut tangents = vec![Vector3::new(0.0, 0.0, 0.0); positions.len()];
+  let mut bitangents = vec![Vector3::new(0.0, 0.0, 0.0); positions.len()];
+
+  for triangle in indices.chunks_exact(3) {
+    let [i0, i1, i2] = [triangle[0] as usize, triangle[1] as usize, triangle[2] as usize];
+    let p0 = Vector3::from(positions[i0]);
+    let edge1 = Vector3::from(positions[i1]) - p0;
+    let edge2 = Vector3::from(positions[i2]) - p0;
+    let uv0 = tex_coords[i0];
+    let delta_uv1 = [tex_coords[i1][0] - uv0[0], tex_coords[i1][1] - uv0[1]];
+    let delta_uv2 = [tex_coords[i2][0] - uv0[0], tex_coords[i2][1] - uv0[1]];
+
+    // zero when the UVs of the triangle are collinear or collapsed
+    let det = delta_uv1[0] * delta_uv2[1] - delta_uv2[0] * delta_uv1[1];
+    let uv_lengths = delta_uv1[0].hypot(delta_uv1[1]) * delta_uv2[0].hypot(delta_uv2[1]);
+    if det.abs() <= DEGENERATE_EPSILON * uv_lengths {
+      continue;
+    }
+    let r = 1.0 / det;
+    let tangent = (edge1 * delta_uv2[1] - edge2 * delta_uv1[1]) * r;
+    let bitangent = (edge2 * delta_uv1[0] - edge1 * delta_uv2[0]) * r;
+
+    for index in [i0, i1, i2] {
+      tangents[index] += tangent;
+      bitangents[index] += bitangent;
+    }
+  }
+
+  normals.iter().zip(tangents).zip(bitangents)
+    .map(|((&normal, tangent), bitangent)| {
+      let normal = Vector3::from(normal);
+      // Gram-Schmidt against the vertex normal
+      let orthogonal = tangent - normal * normal.dot(tangent);
+      let tangent = if orthogonal.magnitude() <= DEGENERATE_EPSILON * tangent.magnitude() {
+        arbitrary_tangent(normal)
+      } else {
+        orthogonal.normalize()
+      };
+      let handedness = if normal.cross(tangent).dot(bitangent) < 0.0 { -1.0 } else { 1.0 };
+      [tangent.x, tangent.y, tangent.z, handedness]
+    })
+    .collect()
+}
+
+// any unit vector perpendicular to the normal
+fn arbitrary_tangent(normal: Vector3<f32>) -> Vector3<f32> {
+  let axis = if normal.x.abs() < 0.9 { Vector3::unit_x() } else { Vector3::unit_y() };
+  let tangent = axis - normal * normal.dot(axis);
+  if tangent.magnitude() <= DEGENERATE_EPSILON {
+    Vector3::unit_x()
+  } else {
+    tangent.normalize()
+  }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum WindingPolicy {
+  #[default]
+  Keep,
+  // the source is known to be clockwise, flip every triangle
+  ForceCcw,
+  // flip when the majority of the mesh looks clockwise
+  AutoDetect,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum WindingDetection {
+  NotChecked,
+  // triangles whose face normal points away from their vertex normals
+  Normals { disagreeing: usize, total: usize },
+  // negative for inside-out closed meshes, only meaningful for closed meshes
+  SignedVolume(f32),
+}
+
+// what fix_winding() did, so asset pipelines can be fixed upstream
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct WindingReport {
+  pub flipped: bool,
+  pub detection: WindingDetection,
+}
+
+// Makes triangles counter-clockwise, as expected by the pipeline's
+// FrontFace::Ccw with back-face culling. Normals are preferred for detection
+// when available, otherwise the signed volume of the mesh is used.
+pub fn fix_winding(
+  positions: &[[f32; 3]],
+  normals: Option<&[[f32; 3]]>,
+  indices: &mut [u32],
+  policy: WindingPolicy,
+) -> WindingReport {
+  let (flip, detection) = match policy {
+    WindingPolicy::Keep => (false, WindingDetection::NotChecked),
+    WindingPolicy::ForceCcw => (true, WindingDetection::NotChecked),
+    WindingPolicy::AutoDetect => match normals {
+      Some(normals) => {
+        let (disagreeing, total) = count_disagreeing_faces(positions, normals, indices);
+        (disagreeing * 2 > total, WindingDetection::Normals { disagreeing, total })
+      }
+      None => {
+        let volume = signed_volume(positions, indices);
+        (volume < 0.0, WindingDetection::SignedVolume(volume))
+      }
+    },
+  };
+
+  if flip {
+    for triangle in indices.chunks_exact_mut(3) {
+      triangle.swap(1, 2);
+    }
+  }
+
+  WindingReport {
+    flipped: flip,
+    detection,
+  }
+}
+
+fn count_disagreeing_faces(positions: &[[f32; 3]], normals: &[[f32; 3]], indices: &[u32]) -> (usize, usize) {
+  let mut disagreeing = 0;
+  let mut total = 0;
+  for triangle in indices.chunks_exact(3) {
+    let Some(face) = face_normal(positions, triangle) else { continue };
+    let vertex_normals: Vector3<f32> = triangle.iter()
+      .map(|&index| Vector3::from(normals[index as usize]))
+      .sum();
+    total += 1;
+    if face.dot(vertex_normals) < 0.0 {
+      disagreeing += 1;
+    }
+  }
+  (disagreeing, total)
+}
+
+fn signed_volume(positions: &[[f32; 3]], indices: &[u32]) -> f32 {
+  indices.chunks_exact(3)
+    .map(|triangle| {
+      let a = Vector3::from(positions[triangle[0] as usize]);
+      let b = Vector3::from(positions[triangle[1] as usize]);
+      let c = Vector3::from(positions[triangle[2] as usize]);
+      a.dot(b.cross(c)) / 6.0
+    })
+    .sum()
+}
@@ -0,0 +1,281 @@
+use std::fmt;
+use std::io::BufRead;
+
+use crate::load::{Cancelled, LoadContext};
+use crate::mesh::{self, NormalMode, WindingPolicy, WindingReport};
+use crate::{LitVertex, TexturedVertex, Vertex};
+
+// What the model loaders produce, the attributes both formats have
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ModelVertex {
+  pub position: [f32; 3],
+  pub normal: [f32; 3],
+  // zero when the file has none
+  pub tex_coords: [f32; 2],
+  // see mesh::compute_tangents(), computed unless a glTF file has them
+  pub tangent: [f32; 4],
+}
+
+// the normal remapped to 0..1 as the color, so the shape shows without lighting
+impl From<ModelVertex> for Vertex {
+  fn from(vertex: ModelVertex) -> Self {
+    Vertex::new(vertex.position, vertex.normal.map(|n| n * 0.5 + 0.5))
+  }
+}
+
+impl From<ModelVertex> for TexturedVertex {
+  fn from(vertex: ModelVertex) -> Self {
+    TexturedVertex::new(vertex.position, vertex.tex_coords)
+  }
+}
+
+impl From<ModelVertex> for LitVertex {
+  fn from(vertex: ModelVertex) -> Self {
+    LitVertex::new(vertex.position, vertex.normal, vertex.tex_coords, vertex.tangent)
+  }
+}
+
+// A triangle list with u32 indices, see State::upload_mesh(). Faces are
+// triangulated while loading and missing normals are computed from them.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Mesh {
+  pub name: Option<String>,
+  pub vertices: Vec<ModelVertex>,
+  pub indices: Vec<u32>,
+  // what the loader's winding policy found and did, None for meshes built
+  // by hand. Merged OBJ objects report whether any of them was flipped.
+  pub winding: Option<WindingReport>,
+}
+
+impl Mesh {
+  // All objects and groups of the file merged into one mesh. Material
+  // libraries aren't loaded. Texture coordinates are flipped to wgpu's
+  // top-left origin.
+  pub fn from_obj(reader: impl BufRead) -> Result<Mesh, ModelError> {
+    Self::from_obj_with(reader, WindingPolicy::default(), &LoadContext::new())
+  }
+
+  pub fn from_obj_with(mut reader: impl BufRead, winding: WindingPolicy, context: &LoadContext) -> Result<Mesh, ModelError> {
+    let options = tobj::LoadOptions {
+      single_index: true,
+      triangulate: true,
+      ignore_points: true,
+      ignore_lines: true,
+    };
+    context.step("parse", 0.0)?;
+    let (models, _) = tobj::load_obj_buf(&mut reader, &options, |_| Ok(Default::default()))?;
+    context.step("parse", 1.0)?;
+
+    // merged meshes have no single name
+    let mut mesh = Mesh {
+      name: (models.len() == 1).then(|| models[0].name.clone()),
+      ..Default::default()
+    };
+    let count = models.len();
+    for (i, model) in models.into_iter().enumerate() {
+      context.step("normals", i as f32 / count as f32)?;
+      let mut obj = model.mesh;
+      let offset = mesh.vertices.len() as u32;
+      let has_normals = !obj.normals.is_empty();
+      let mut vertices: Vec<_> = (0..obj.positions.len() / 3).map(|i| ModelVertex {
+        position: [obj.positions[i * 3], obj.positions[i * 3 + 1], obj.positions[i * 3 + 2]],
+        normal: match obj.normals.get(i * 3..i * 3 + 3) {
+          Some(n) => [n[0], n[1], n[2]],
+          None => [0.0; 3],
+        },
+        tex_coords: match obj.texcoords.get(i * 2..i * 2 + 2) {
+          Some(uv) => [uv[0], 1.0 - uv[1]],
+          None => [0.0; 2],
+        },
+        tangent: [0.0; 4],
+      }).collect();
+      let report = prepare(&mut vertices, &mut obj.indices, has_normals, false, winding)?;
+      mesh.winding = Some(match mesh.winding {
+        Some(merged) if merged.flipped || !report.flipped => merged,
+        _ => report,
+      });
+      mesh.vertices.extend(vertices);
+      mesh.indices.extend(obj.indices.iter().map(|index| index + offset));
+    }
+    if mesh.indices.is_empty() {
+      return Err(ModelError::NoTriangles);
+    }
+    context.step("normals", 1.0)?;
+    Ok(mesh)
+  }
+
+  // One mesh per triangle primitive of every mesh in a .glb or a .gltf with
+  // embedded buffers, in file order. Node transforms aren't applied.
+  pub fn from_gltf(bytes: &[u8]) -> Result<Vec<Mesh>, ModelError> {
+    Self::from_gltf_with(bytes, WindingPolicy::default(), &LoadContext::new())
+  }
+
+  pub fn from_gltf_with(bytes: &[u8], winding: WindingPolicy, context: &LoadContext) -> Result<Vec<Mesh>, ModelError> {
+    context.step("parse", 0.0)?;
+    let gltf = gltf::Gltf::from_slice(bytes)?;
+    let buffers = gltf::import_buffers(&gltf.document, None, gltf.blob)?;
+    context.step("parse", 1.0)?;
+
+    let count = gltf.document.meshes().len();
+    let mut meshes = Vec::new();
+    for (i, gltf_mesh) in gltf.document.meshes().enumerate() {
+      context.step("normals", i as f32 / count as f32)?;
+      for primitive in gltf_mesh.primitives() {
+        if primitive.mode() != gltf::mesh::Mode::Triangles {
+          log::warn!("Skipping a {:?} primitive of mesh {:?}", primitive.mode(), gltf_mesh.name());
+          continue;
+        }
+        let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+        let Some(positions) = reader.read_positions() else { continue };
+        let mut vertices: Vec<_> = positions
+          .map(|position| ModelVertex { position, ..Default::default() })
+          .collect();
+        let mut indices: Vec<u32> = match reader.read_indices() {
+          Some(indices) => indices.into_u32().collect(),
+          None => (0..vertices.len() as u32).collect(),
+        };
+
+        let normals = reader.read_normals();
+        let has_normals = normals.is_some();
+        if let Some(normals) = normals {
+          vertices.iter_mut().zip(normals).for_each(|(vertex, normal)| vertex.normal = normal);
+        }
+        if let Some(tex_coords) = reader.read_tex_coords(0) {
+          vertices.iter_mut().zip(tex_coords.into_f32()).for_each(|(vertex, uv)| vertex.tex_coords = uv);
+        }
+        let tangents = reader.read_tangents();
+        let has_tangents = tangents.is_some();
+        if let Some(tangents) = tangents {
+          vertices.iter_mut().zip(tangents).for_each(|(vertex, tangent)| vertex.tangent = tangent);
+        }
+        let report = prepare(&mut vertices, &mut indices, has_normals, has_tangents, winding)?;
+
+        meshes.push(Mesh {
+          name: gltf_mesh.name().map(str::to_string),
+          vertices,
+          indices,
+          winding: Some(report),
+        });
+      }
+    }
+    if meshes.is_empty() {
+      return Err(ModelError::NoTriangles);
+    }
+    context.step("normals", 1.0)?;
+    Ok(meshes)
+  }
+
+  // vertex colors from the normals, for set_indexed_mesh()
+  pub fn vertices(&self) -> Vec<Vertex> {
+    self.vertices.iter().map(|&vertex| vertex.into()).collect()
+  }
+
+  pub fn textured_vertices(&self) -> Vec<TexturedVertex> {
+    self.vertices.iter().map(|&vertex| vertex.into()).collect()
+  }
+
+  // for set_lit_mesh(), tangents included
+  pub fn lit_vertices(&self) -> Vec<LitVertex> {
+    self.vertices.iter().map(|&vertex| vertex.into()).collect()
+  }
+}
+
+// Checks the indices, fixes the winding and fills in missing normals, then
+// tangents. The winding goes first so computed normals point out of the fixed
+// faces; without normals in the file AutoDetect falls back to the signed volume.
+fn prepare(
+  vertices: &mut [ModelVertex],
+  indices: &mut [u32],
+  has_normals: bool,
+  has_tangents: bool,
+  winding: WindingPolicy,
+) -> Result<WindingReport, ModelError> {
+  if let Some(&index) = indices.iter().find(|&&index| index as usize >= vertices.len()) {
+    return Err(ModelError::IndexOutOfRange { index, vertices: vertices.len() });
+  }
+  let positions: Vec<_> = vertices.iter().map(|vertex| vertex.position).collect();
+  let normals: Option<Vec<_>> = has_normals.then(|| vertices.iter().map(|vertex| vertex.normal).collect());
+  let report = mesh::fix_winding(&positions, normals.as_deref(), indices, winding);
+  if !has_normals {
+    smooth_normals(vertices, indices);
+  }
+  if !has_tangents {
+    tangents(vertices, indices);
+  }
+  Ok(report)
+}
+
+// smooth normals keep the vertices as they are, so they can be copied over
+fn smooth_normals(vertices: &mut [ModelVertex], indices: &[u32]) {
+  let positions: Vec<_> = vertices.iter().map(|vertex| vertex.position).collect();
+  let normals = mesh::compute_normals(&positions, indices, NormalMode::Smooth).normals;
+  for (vertex, normal) in vertices.iter_mut().zip(normals) {
+    vertex.normal = normal;
+  }
+}
+
+fn tangents(vertices: &mut [ModelVertex], indices: &[u32]) {
+  let positions: Vec<_> = vertices.iter().map(|vertex| vertex.position).collect();
+  let normals: Vec<_> = vertices.iter().map(|vertex| vertex.normal).collect();
+  let tex_coords: Vec<_> = vertices.iter().map(|vertex| vertex.tex_coords).collect();
+  let tangents = mesh::compute_tangents(&positions, &normals, &tex_coords, indices);
+  for (vertex, tangent) in vertices.iter_mut().zip(tangents) {
+    vertex.tangent = tangent;
+  }
+}
+
+// Returned by the Mesh loaders
+#[derive(Debug)]
+pub enum ModelError {
+  Obj(tobj::LoadError),
+  Gltf(gltf::Error),
+  // nothing drawable, e.g. only points and lines
+  NoTriangles,
+  // a face refers to a vertex the file doesn't have
+  IndexOutOfRange { index: u32, vertices: usize },
+  Cancelled(Cancelled),
+}
+
+impl fmt::Display for ModelError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      ModelError::Obj(err) => write!(f, "Failed to parse the OBJ file: {}", err),
+      ModelError::Gltf(err) => write!(f, "Failed to load the glTF file: {}", err),
+      ModelError::NoTriangles => write!(f, "The model has no triangles"),
+      ModelError::IndexOutOfRange { index, vertices } => {
+        write!(f, "Index {} is out of range for a mesh with {} vertices", index, vertices)
+      }
+      ModelError::Cancelled(err) => write!(f, "{}", err),
+    }
+  }
+}
+
+impl std::error::Error for ModelError {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      ModelError::Obj(err) => Some(err),
+      ModelError::Gltf(err) => Some(err),
+      ModelError::Cancelled(err) => Some(err),
+      ModelError::NoTriangles | ModelError::IndexOutOfRange { .. } => None,
+    }
+  }
+}
+
+impl From<tobj::LoadError> for ModelError {
+  fn from(err: tobj::LoadError) -> Self {
+    ModelError::Obj(err)
+  }
+}
+
+impl From<gltf::Error> for ModelError {
+  fn from(err: gltf::Error) -> Self {
+    ModelError::Gltf(err)
+  }
+}
+
+impl From<Cancelled> for ModelError {
+  fn from(err: Cancelled) -> Self {
+    ModelError::Cancelled(err)
+  }
+}
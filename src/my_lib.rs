@@ -1,12 +1,136 @@
+use std::collections::HashMap;
+use std::path::Path;
+use anyhow::Context;
+use cgmath::MetricSpace;
+use color_space::{linear_to_srgb, needs_srgb_encode};
 use std::sync::Arc;
+use web_time::Instant;
+use instance::DirtyRanges;
+use object::{DrawObject, ObjectMesh, ObjectStore};
+use pipeline_cache::{PipelineCache, PipelineCacheFile, PipelineKind};
 use winit::window::Window;
 use wgpu::util::DeviceExt;
 
+// the browser build starts through web::start() instead
+#[cfg(not(target_arch = "wasm32"))]
+pub mod app;
+mod camera;
+mod capture;
+mod color_space;
+mod capabilities;
+mod debug_draw;
+#[cfg(feature = "egui")]
+mod egui_overlay;
+mod environment;
+mod error;
+mod frame_dump;
+mod frame_pacing;
+mod frame_stats;
+mod gpu_context;
+mod instance;
+mod limits;
+mod object;
+pub mod load;
+mod lod;
+mod material;
+#[cfg(feature = "models")]
+mod model;
+mod options;
+mod pipeline_cache;
+mod poll;
+mod push_constants;
+mod readback;
+mod rng;
+mod shader;
+mod skybox;
+mod text_input;
+mod texture;
+#[cfg(all(target_arch = "wasm32", feature = "web"))]
+pub mod web;
+pub mod mesh;
+pub use camera::{Camera, Frustum, Projection, Ray};
+pub use capture::CaptureError;
+pub use color_space::{ColorSpaceInfo, SrgbEncoding};
+pub use capabilities::CapabilityReport;
+pub use debug_draw::DebugDraw;
+#[cfg(feature = "egui")]
+pub use egui_overlay::EguiOverlay;
+pub use environment::{Environment, HdrImage};
+pub use error::{RenderError, StateError, UnsupportedMode};
+pub use frame_dump::{DrawDump, FrameDump, FrameTotals, PassDump, UploadDump};
+pub use frame_pacing::{FramePacing, Hitch, PacingStats};
+pub use frame_stats::{AverageFrameStats, FrameStats, STATS_WINDOW};
+pub use gpu_context::GpuContext;
+pub use instance::{Instance, InstanceRaw};
+pub use limits::ExceedsLimit;
+pub use object::ObjectId;
+pub use lod::{LodGroup, LodLevel, LodStats};
+pub use material::Material;
+#[cfg(feature = "models")]
+pub use model::{Mesh, ModelError, ModelVertex};
+pub use options::{FeatureRequest, StateBuilder, StateOptions, DEFAULT_OPTIONAL_FEATURES};
+pub use pipeline_cache::{DepthBias, PipelineDesc, RenderMode, UnsupportedRenderMode};
+pub use poll::MapTracker;
+pub use push_constants::MAX_PUSH_CONSTANT_SIZE;
+pub use readback::{Readback, ReadbackId, ReadbackRing};
+pub use rng::{Pcg32, RngStreams};
+pub use shader::ShaderError;
+pub use text_input::{Composition, TextInput};
+pub use texture::{ColorSpace, Texture, DEPTH_FORMAT};
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Vertex {
+  pub position: [f32; 3],
+  pub color: [f32; 3],
+}
+
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
-struct Vertex {
-  position: [f32; 3],
-  color: [f32; 3],
+pub struct TexturedVertex {
+  pub position: [f32; 3],
+  pub tex_coords: [f32; 2],
+}
+
+// for State::set_lit_mesh(), lit by the environment's irradiance
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LitVertex {
+  pub position: [f32; 3],
+  pub normal: [f32; 3],
+  pub tex_coords: [f32; 2],
+  // for the normal map, see mesh::compute_tangents()
+  pub tangent: [f32; 4],
+}
+
+// handle returned by State::load_texture()
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TextureId(usize);
+
+// per-frame shader inputs, bound at @group(0) @binding(0)
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Uniforms {
+  // from the camera
+  pub view_proj: [[f32; 4]; 4],
+  // applied before the camera, see State::update_uniforms()
+  pub transform: [[f32; 4]; 4],
+}
+
+pub(crate) const IDENTITY: [[f32; 4]; 4] = [
+  [1.0, 0.0, 0.0, 0.0],
+  [0.0, 1.0, 0.0, 0.0],
+  [0.0, 0.0, 1.0, 0.0],
+  [0.0, 0.0, 0.0, 1.0],
+];
+
+impl Default for Uniforms {
+  fn default() -> Self {
+    Self {
+      view_proj: IDENTITY,
+      transform: IDENTITY,
+    }
+  }
 }
 
 // for cases where struct may contain types that don't implement POD and Zeroable
@@ -22,201 +146,2118 @@ const POLYGON_VERTICES: &[Vertex] = &[
   Vertex { position: [0.44147372, 0.2347359, 0.0], color: [0.5, 0.0, 0.5] }, // E
 ];
 
-const POLYGON_INDICES: &[u16] = &[
-  0, 1, 4,
-  1, 2, 4,
-  2, 3, 4,
-];
+const POLYGON_INDICES: &[u16] = &[
+  0, 1, 4,
+  1, 2, 4,
+  2, 3, 4,
+];
+
+// triangular shapes
+// const VERTICES: &[Vertex] = &[
+//   Vertex { position: [0.0, 0.5, 0.0], color: [1.0, 0.0, 0.0] },
+//   Vertex { position: [-0.5, -0.5, 0.0], color: [0.0, 1.0, 0.0] },
+//   Vertex { position: [0.5, -0.5, 0.0], color: [0.0, 0.0, 1.0] },
+// ];
+
+// what the current vertices are and the bind groups they're drawn with
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum MeshKind {
+  Color,
+  Textured(TextureId),
+  // drawn with material_bind_group
+  Lit,
+}
+
+impl MeshKind {
+  fn pipeline(self) -> PipelineKind {
+    match self {
+      MeshKind::Color => PipelineKind::Color,
+      MeshKind::Textured(_) => PipelineKind::Textured,
+      MeshKind::Lit => PipelineKind::Lit,
+    }
+  }
+
+  // including the uniforms at @group(0)
+  fn bind_groups(self) -> u32 {
+    match self {
+      MeshKind::Color => 1,
+      MeshKind::Textured(_) => 2,
+      MeshKind::Lit => 3,
+    }
+  }
+}
+
+// where render() draws to
+enum RenderTarget {
+  // owns its window through the Arc it was created from
+  Surface(wgpu::Surface<'static>),
+  // the surface was dropped by suspend(), until resume()
+  Suspended,
+  // RENDER_ATTACHMENT | COPY_SRC texture for headless rendering
+  Offscreen(wgpu::Texture),
+}
+
+// sRGB like the surface formats we prefer, so both paths produce the same colors
+const HEADLESS_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+// what the offscreen target allows for StateOptions::surface_usages
+const HEADLESS_USAGES: wgpu::TextureUsages = wgpu::TextureUsages::RENDER_ATTACHMENT
+  .union(wgpu::TextureUsages::COPY_SRC)
+  .union(wgpu::TextureUsages::COPY_DST)
+  .union(wgpu::TextureUsages::TEXTURE_BINDING);
+// output encoding and other declarations shared by the render shaders
+const SHADER_PRELUDE: &str = include_str!("prelude.wgsl");
+
+fn with_prelude(source: &str) -> String {
+  format!("{}\n{}", SHADER_PRELUDE, source)
+}
+
+pub struct State {
+  target: RenderTarget,
+  // kept to recreate the surface in resume()
+  instance: wgpu::Instance,
+  adapter: wgpu::Adapter,
+  device: Arc<wgpu::Device>,
+  queue: wgpu::Queue,
+  config: wgpu::SurfaceConfiguration,
+  size: winit::dpi::PhysicalSize<u32>,
+  // set by resize(), applied at the start of the next render()
+  needs_reconfigure: bool,
+  options: StateOptions,
+  // None when StateOptions::depth is off
+  depth_texture: Option<Texture>,
+  // multisampled color target resolved into the frame, None without MSAA
+  msaa_target: Option<wgpu::TextureView>,
+  pipelines: PipelineCache,
+  // None without StateOptions::pipeline_cache_dir or Features::PIPELINE_CACHE
+  pipeline_cache: Option<PipelineCacheFile>,
+  render_mode: RenderMode,
+  // of the main mesh, objects have their own
+  depth_bias: DepthBias,
+  // WGSL of the vertex color pipelines, replaced by load_shader_from_path()
+  shader_source: String,
+  #[cfg(feature = "hot-reload")]
+  shader_watcher: Option<shader::ShaderWatcher>,
+  texture_bind_group_layout: wgpu::BindGroupLayout,
+  // loaded textures and their bind groups, indexed by TextureId
+  textures: Vec<(Texture, wgpu::BindGroup)>,
+  mesh_kind: MeshKind,
+  material_bind_group_layout: wgpu::BindGroupLayout,
+  // bound for materials without a normal map
+  flat_normal_map: Texture,
+  // set by set_lit_mesh()
+  material_bind_group: Option<wgpu::BindGroup>,
+  camera: Camera,
+  uniforms: Uniforms,
+  uniform_buffer: wgpu::Buffer,
+  uniform_bind_group_layout: wgpu::BindGroupLayout,
+  uniform_bind_group: wgpu::BindGroup,
+  vertex_buffer: wgpu::Buffer,
+  index_buffer: wgpu::Buffer,
+  num_vertices: u32,
+  // None when drawing the vertices without an index buffer
+  num_indices: Option<u32>,
+  index_format: wgpu::IndexFormat,
+  instance_buffer: wgpu::Buffer,
+  // what the instance buffer holds once dirty_instances are uploaded
+  instances: Vec<InstanceRaw>,
+  dirty_instances: DirtyRanges,
+  objects: ObjectStore,
+  // from the last render(), see select_lods()
+  lod_stats: LodStats,
+  push_constants: push_constants::PushConstants,
+  // a single untransformed instance, objects aren't instanced
+  identity_instance: wgpu::Buffer,
+  debug_draw: DebugDraw,
+  // created once there are lines to draw
+  debug_lines: Option<debug_draw::DebugLines>,
+  environment: Option<Environment>,
+  environment_bind_group_layout: wgpu::BindGroupLayout,
+  // the environment's irradiance, or constant white without one
+  environment_bind_group: wgpu::BindGroup,
+  // drawn behind the scene once there is an environment
+  skybox: Option<skybox::Skybox>,
+  downlevel: wgpu::DownlevelCapabilities,
+  capabilities: CapabilityReport,
+  // queried once at init and again by renegotiate_surface()
+  surface_caps: wgpu::SurfaceCapabilities,
+  capture_frame_dump: bool,
+  frame_dump: Option<FrameDump>,
+  // bytes written since the last render() per buffer label, summed so it
+  // stays small when nothing is rendered; goes into the next frame dump
+  pending_uploads: Vec<(&'static str, u64)>,
+  // screenshot requested for the next render()
+  capture_request: Option<std::path::PathBuf>,
+  frame_capture: Option<capture::FrameCapture>,
+  capture_result: Option<Result<std::path::PathBuf, CaptureError>>,
+  // applied at the start of the next frame, see set_vsync()
+  pending_present_mode: Option<wgpu::PresentMode>,
+  frame_pacing: FramePacing,
+  frame_timings: frame_stats::FrameTimings,
+  // None without Features::TIMESTAMP_QUERY
+  gpu_timer: Option<frame_stats::GpuTimer>,
+  map_tracker: MapTracker,
+  rng: RngStreams,
+  #[cfg(feature = "poll-thread")]
+  poll_thread: Option<poll::PollThread>,
+}
+
+impl State {
+  // Creating some of the wgpu types requires async code
+  pub fn new(window: Arc<Window>) -> Result<State, StateError> {
+    pollster::block_on(State::new_async(window))
+  }
+
+  // the old infallible constructor, for callers that can't handle errors
+  pub fn new_or_panic(window: Arc<Window>) -> State {
+    State::new(window).unwrap_or_else(|err| panic!("{}", err))
+  }
+
+  pub fn new_with_options(window: Arc<Window>, options: StateOptions) -> Result<State, StateError> {
+    pollster::block_on(State::new_async_with_options(window, options))
+  }
+
+  pub async fn new_async(window: Arc<Window>) -> Result<State, StateError> {
+    State::new_async_with_options(window, StateOptions::default()).await
+  }
+
+  pub async fn new_async_with_options(window: Arc<Window>, options: StateOptions) -> Result<State, StateError> {
+    let size = window.inner_size();
+    // The instance is a handle to our GPU
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+      backends: options.backends,
+      ..Default::default()
+    });
+
+    let surface = instance.create_surface(Arc::clone(&window))?;
+    let adapter = instance.request_adapter(&wgpu::RequestAdapterOptions {
+      power_preference: options.power_preference,
+      force_fallback_adapter: false,
+      // Request an adapter which can render to our surface
+      compatible_surface: Some(&surface),
+    })
+    .await
+    .ok_or(StateError::NoAdapter { backends: options.backends })?;
+
+    let (device, queue) = State::request_device(&adapter, &options).await?;
+
+    let surface_caps = surface.get_capabilities(&adapter);
+    // Shader code in this tutorial assumes an sRGB surface texture. Without one
+    // the frames are rendered through an sRGB view of the surface, or where
+    // that isn't possible (WebGL) the pipelines encode the fragment output.
+    let surface_format = surface_caps.formats.iter()
+    .find(|f| f.is_srgb())
+    .or(surface_caps.formats.first())
+    .copied()
+    .ok_or(StateError::NoSupportedFormat)?;
+
+    let config = wgpu::SurfaceConfiguration {
+      usage: State::surface_usage(surface_caps.usages, options.surface_usages)?,
+      format: surface_format,
+      width: size.width.min(device.limits().max_texture_dimension_2d),
+      height: size.height.min(device.limits().max_texture_dimension_2d),
+      present_mode: State::supported_present_mode(&surface_caps, options.present_mode),
+      alpha_mode: surface_caps.alpha_modes[0],
+      view_formats: color_space::srgb_view_formats(&adapter, surface_format),
+      desired_maximum_frame_latency: 2,
+    };
+    log::info!(
+      "Surface configured as {:?} with {:?} (supported formats: {:?}, present modes: {:?})",
+      config.format, config.present_mode, surface_caps.formats, surface_caps.present_modes,
+    );
+    let color_space = ColorSpaceInfo::new(&config);
+    if color_space.encoding != SrgbEncoding::SurfaceFormat {
+      log::info!("No sRGB surface format, rendering as {:?} with {:?} encoding", color_space.render_format, color_space.encoding);
+    }
+
+    Ok(State::from_parts(RenderTarget::Surface(surface), instance, adapter, device, queue, config, size, options))
+  }
+
+  // Renders into an offscreen RGBA8 texture instead of a window surface,
+  // for tests and screenshots. read_pixels() returns the last frame.
+  // Any backend is accepted, like GpuContext, since CI machines often only
+  // have a GL software rasterizer.
+  pub fn new_headless(width: u32, height: u32) -> Result<State, StateError> {
+    let options = StateOptions::default().backends(wgpu::Backends::all());
+    State::new_headless_with_options(width, height, options)
+  }
+
+  pub fn new_headless_with_options(width: u32, height: u32, options: StateOptions) -> Result<State, StateError> {
+    pollster::block_on(State::new_headless_async_with_options(width, height, options))
+  }
+
+  pub async fn new_headless_async_with_options(width: u32, height: u32, options: StateOptions) -> Result<State, StateError> {
+    State::new_headless_with_format(width, height, options, HEADLESS_FORMAT).await
+  }
+
+  // Renders into plain Rgba8Unorm without an sRGB view, like drawing to a
+  // WebGL canvas, so the shaders encode their output themselves. Lets the
+  // WebGL color path be checked against the usual one natively.
+  pub fn new_headless_without_srgb(width: u32, height: u32, options: StateOptions) -> Result<State, StateError> {
+    pollster::block_on(State::new_headless_with_format(width, height, options, wgpu::TextureFormat::Rgba8Unorm))
+  }
+
+  // `format` has 4 bytes per texel, as read_pixels() expects
+  async fn new_headless_with_format(
+    width: u32,
+    height: u32,
+    options: StateOptions,
+    format: wgpu::TextureFormat,
+  ) -> Result<State, StateError> {
+    let (instance, adapter) = State::request_headless_adapter(&options).await?;
+    let (device, queue) = State::request_device(&adapter, &options).await?;
+
+    limits::check_texture_2d(&device.limits(), "Headless target size", width, height)?;
+    let config = wgpu::SurfaceConfiguration {
+      // COPY_SRC for read_pixels()
+      usage: State::surface_usage(HEADLESS_USAGES, options.surface_usages)? | wgpu::TextureUsages::COPY_SRC,
+      format,
+      width: width.max(1),
+      height: height.max(1),
+      // not used without a surface
+      present_mode: wgpu::PresentMode::Fifo,
+      alpha_mode: wgpu::CompositeAlphaMode::Auto,
+      // the sRGB default needs no view, new_headless_without_srgb() mustn't have one
+      view_formats: Vec::new(),
+      desired_maximum_frame_latency: 2,
+    };
+    let target = RenderTarget::Offscreen(State::create_offscreen_target(&device, &config));
+    let size = winit::dpi::PhysicalSize::new(config.width, config.height);
+
+    Ok(State::from_parts(target, instance, adapter, device, queue, config, size, options))
+  }
+
+  // RENDER_ATTACHMENT plus the requested extras, if all of them are supported
+  fn surface_usage(supported: wgpu::TextureUsages, requested: wgpu::TextureUsages) -> Result<wgpu::TextureUsages, StateError> {
+    let usage = wgpu::TextureUsages::RENDER_ATTACHMENT | requested;
+    if !supported.contains(usage) {
+      return Err(StateError::UnsupportedSurfaceUsages { requested: usage, supported });
+    }
+    Ok(usage)
+  }
+
+  // the adapter for a State or GpuContext without a surface
+  pub(crate) async fn request_headless_adapter(options: &StateOptions) -> Result<(wgpu::Instance, wgpu::Adapter), StateError> {
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+      backends: options.backends,
+      ..Default::default()
+    });
+    let adapter = instance.request_adapter(&wgpu::RequestAdapterOptions {
+      power_preference: options.power_preference,
+      force_fallback_adapter: false,
+      compatible_surface: None,
+    })
+    .await
+    .ok_or(StateError::NoAdapter { backends: options.backends })?;
+    Ok((instance, adapter))
+  }
+
+  async fn request_device(adapter: &wgpu::Adapter, options: &StateOptions) -> Result<(wgpu::Device, wgpu::Queue), StateError> {
+    // WebGL and older GPUs can't meet the default limits, so fall back to
+    // the first downlevel preset the adapter actually supports. Make sure we
+    // use the texture resolution limits from the adapter in that case, so we
+    // can support images the size of the swapchain.
+    let supported = adapter.limits();
+    let limits = if wgpu::Limits::default().check_limits(&supported) {
+      wgpu::Limits::default()
+    } else {
+      let downlevel = wgpu::Limits::downlevel_defaults();
+      let preset = if downlevel.check_limits(&supported) { downlevel } else { wgpu::Limits::downlevel_webgl2_defaults() };
+      preset.using_resolution(supported)
+    };
+    State::request_device_with_limits(adapter, options, limits).await
+  }
+
+  // the features of `options` on top of `limits`, which the adapter must support
+  pub(crate) async fn request_device_with_limits(
+    adapter: &wgpu::Adapter,
+    options: &StateOptions,
+    mut limits: wgpu::Limits,
+  ) -> Result<(wgpu::Device, wgpu::Queue), StateError> {
+    let supported = adapter.limits();
+    let (required, mut optional) = FeatureRequest::split(&options.features);
+    if options.pipeline_cache_dir.is_some() {
+      optional |= wgpu::Features::PIPELINE_CACHE;
+    }
+    let missing = required - adapter.features();
+    if !missing.is_empty() {
+      return Err(StateError::MissingFeature(missing));
+    }
+    // GL only emulates push constants with uniforms, and wgpu-hal 22 reads
+    // their data unaligned there; the uniform fallback does the same safely
+    if adapter.get_info().backend == wgpu::Backend::Gl {
+      optional -= wgpu::Features::PUSH_CONSTANTS;
+    }
+    let features = required | (optional & adapter.features());
+    if features.contains(wgpu::Features::PUSH_CONSTANTS) {
+      limits.max_push_constant_size = supported.max_push_constant_size.min(MAX_PUSH_CONSTANT_SIZE);
+    }
+    adapter.request_device(
+      &wgpu::DeviceDescriptor {
+        label: Some("Device Setup"),
+        memory_hints: wgpu::MemoryHints::default(),
+        // see enabled_features()
+        required_features: features,
+        required_limits: limits.clone(),
+      },
+      None,
+    ).await
+    .map_err(|source| StateError::DeviceRequest { source, limits: Box::new(limits) })
+  }
+
+  fn create_offscreen_target(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+      label: Some("Offscreen Target"),
+      size: wgpu::Extent3d {
+        width: config.width,
+        height: config.height,
+        depth_or_array_layers: 1,
+      },
+      mip_level_count: 1,
+      sample_count: 1,
+      dimension: wgpu::TextureDimension::D2,
+      format: config.format,
+      usage: config.usage,
+      view_formats: &config.view_formats,
+    })
+  }
+
+  // everything after the device and the render target, shared by the windowed and headless paths
+  #[allow(clippy::too_many_arguments)]
+  fn from_parts(
+    target: RenderTarget,
+    instance: wgpu::Instance,
+    adapter: wgpu::Adapter,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    config: wgpu::SurfaceConfiguration,
+    size: winit::dpi::PhysicalSize<u32>,
+    mut options: StateOptions,
+  ) -> State {
+    // only shared with the poll thread, which doesn't exist on wasm
+    #[cfg_attr(target_arch = "wasm32", allow(clippy::arc_with_non_send_sync))]
+    let device = Arc::new(device);
+    let map_tracker = MapTracker::default();
+    let gpu_timer = frame_stats::GpuTimer::new(&device, &queue, &map_tracker);
+    let push_constants = push_constants::PushConstants::new(&device);
+
+    let pipeline_cache = options.pipeline_cache_dir.as_deref().and_then(|dir| {
+      let file = PipelineCacheFile::open(&device, &adapter.get_info(), dir);
+      if file.is_none() {
+        log::info!("The device doesn't support pipeline caches, not using {}", dir.display());
+      }
+      file
+    });
+
+    let downlevel = adapter.get_downlevel_capabilities();
+    let capabilities = CapabilityReport::new(&adapter);
+    let surface_caps = match &target {
+      RenderTarget::Surface(surface) => surface.get_capabilities(&adapter),
+      // nothing is presented, so just what the offscreen target uses
+      RenderTarget::Offscreen(_) | RenderTarget::Suspended => wgpu::SurfaceCapabilities {
+        formats: vec![config.format],
+        present_modes: vec![config.present_mode],
+        alpha_modes: vec![config.alpha_mode],
+        usages: config.usage,
+      },
+    };
+
+    let camera = Camera::new(config.width as f32 / config.height.max(1) as f32);
+    let uniforms = Uniforms {
+      view_proj: camera.build_view_projection_matrix().into(),
+      ..Default::default()
+    };
+    let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+      label: Some("Uniform Buffer"),
+      contents: bytemuck::cast_slice(&[uniforms]),
+      usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+    });
+    let uniform_bind_group_layout = State::uniform_bind_group_layout(&device);
+    let uniform_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+      label: Some("Uniform Bind Group"),
+      layout: &uniform_bind_group_layout,
+      entries: &[wgpu::BindGroupEntry {
+        binding: 0,
+        resource: uniform_buffer.as_entire_binding(),
+      }],
+    });
+
+    options.msaa_samples = State::supported_sample_count(&adapter, color_space::render_format(&config), &options);
+    let depth_texture = options.depth
+      .then(|| Texture::create_depth_texture(&device, config.width, config.height, options.msaa_samples, "Depth Texture"));
+    let msaa_target = State::create_msaa_target(&device, &config, options.msaa_samples);
+
+    let texture_bind_group_layout = Texture::bind_group_layout(&device);
+    let material_bind_group_layout = material::bind_group_layout(&device);
+    let flat_normal_map = material::flat_normal_map(&device, &queue);
+    let environment_bind_group_layout = Environment::bind_group_layout(&device);
+    let environment_bind_group = environment::default_bind_group(&device, &queue, &environment_bind_group_layout);
+    let vertex_buffer = State::new_vertex_buffer(&device);
+
+    let index_buffer = State::new_index_buffer(&device);
+    // a single untransformed instance until set_instances() is called
+    let instance_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+      label: Some("Instance Buffer"),
+      contents: bytemuck::cast_slice(&[Instance::default().to_raw()]),
+      usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+    });
+    let identity_instance = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+      label: Some("Identity Instance Buffer"),
+      contents: bytemuck::cast_slice(&[Instance::default().to_raw()]),
+      usage: wgpu::BufferUsages::VERTEX,
+    });
+    let num_vertices = POLYGON_VERTICES.len() as u32;
+    let num_indices = Some(POLYGON_INDICES.len() as u32);
+    // let num_vertices: u32 = VERTICES.len() as u32;
+
+    Self {
+      target,
+      instance,
+      adapter,
+      device,
+      queue,
+      config,
+      size,
+      // the surface starts out unconfigured
+      needs_reconfigure: true,
+      options,
+      depth_texture,
+      msaa_target,
+      pipelines: PipelineCache::default(),
+      pipeline_cache,
+      render_mode: RenderMode::Fill,
+      depth_bias: DepthBias::NONE,
+      shader_source: include_str!("shader.wgsl").to_string(),
+      #[cfg(feature = "hot-reload")]
+      shader_watcher: None,
+      texture_bind_group_layout,
+      textures: Vec::new(),
+      mesh_kind: MeshKind::Color,
+      material_bind_group_layout,
+      flat_normal_map,
+      material_bind_group: None,
+      camera,
+      uniforms,
+      uniform_buffer,
+      uniform_bind_group_layout,
+      uniform_bind_group,
+      vertex_buffer,
+      index_buffer,
+      num_vertices,
+      num_indices,
+      index_format: wgpu::IndexFormat::Uint16,
+      instance_buffer,
+      instances: vec![Instance::default().to_raw()],
+      dirty_instances: DirtyRanges::default(),
+      objects: ObjectStore::default(),
+      lod_stats: LodStats::default(),
+      push_constants,
+      identity_instance,
+      debug_draw: DebugDraw::default(),
+      debug_lines: None,
+      environment: None,
+      environment_bind_group_layout,
+      environment_bind_group,
+      skybox: None,
+      downlevel,
+      surface_caps,
+      capabilities,
+      capture_frame_dump: false,
+      frame_dump: None,
+      pending_uploads: Vec::new(),
+      capture_request: None,
+      frame_capture: None,
+      capture_result: None,
+      pending_present_mode: None,
+      frame_pacing: FramePacing::default(),
+      frame_timings: frame_stats::FrameTimings::default(),
+      gpu_timer,
+      map_tracker,
+      // random unless the application asks for a fixed seed
+      rng: RngStreams::new(rand::random()),
+      #[cfg(feature = "poll-thread")]
+      poll_thread: None,
+    }
+  }
+
+  // options as applied, e.g. msaa_samples is 1 if the requested count wasn't supported
+  pub fn options(&self) -> &StateOptions {
+    &self.options
+  }
+
+  // Replaces the shader of the vertex color pipeline with WGSL read at
+  // runtime. It has to keep the entry points and bindings of shader.wgsl.
+  // prelude.wgsl is prepended like for the built-in shaders, so its
+  // declarations mustn't be repeated. On any error the current pipeline
+  // stays in place.
+  pub fn load_shader_from_path(&mut self, path: &Path) -> Result<(), ShaderError> {
+    let source = std::fs::read_to_string(path).map_err(|source| ShaderError::Io {
+      path: path.to_path_buf(),
+      source,
+    })?;
+    self.load_shader(&source)
+  }
+
+  pub fn load_shader(&mut self, source: &str) -> Result<(), ShaderError> {
+    push_constants::check_uniform_layout(&with_prelude(source), 1).map_err(ShaderError::PushConstantLayout)?;
+    let previous = std::mem::replace(&mut self.shader_source, source.to_string());
+    let desc = self.render_mode.desc().with_depth_bias(self.depth_bias);
+    // validation errors are caught here instead of going to the
+    // uncaptured error handler, which panics by default
+    self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+    let render_pipeline = self.create_pipeline(PipelineKind::Color, desc);
+    if let Some(err) = pollster::block_on(self.device.pop_error_scope()) {
+      self.shader_source = previous;
+      return Err(ShaderError::Validation(err.to_string()));
+    }
+    // pipelines for the other render modes are rebuilt on first use
+    self.pipelines.remove_kind(PipelineKind::Color);
+    self.pipelines.insert(PipelineKind::Color, desc, render_pipeline);
+    Ok(())
+  }
+
+  // Picks the pipeline variant used from the next render() on. Wireframe
+  // needs Features::POLYGON_MODE_LINE, see enabled_features().
+  pub fn set_render_mode(&mut self, mode: RenderMode) -> Result<(), UnsupportedRenderMode> {
+    let missing = mode.required_features() - self.enabled_features();
+    if !missing.is_empty() {
+      return Err(UnsupportedRenderMode { mode, missing });
+    }
+    self.render_mode = mode;
+    Ok(())
+  }
+
+  pub fn render_mode(&self) -> RenderMode {
+    self.render_mode
+  }
+
+  // Depth bias of the main mesh from the next render() on, e.g.
+  // DepthBias::DECAL. Ignored in RenderMode::Points.
+  pub fn set_depth_bias(&mut self, depth_bias: DepthBias) {
+    self.depth_bias = depth_bias;
+  }
+
+  pub fn depth_bias(&self) -> DepthBias {
+    self.depth_bias
+  }
+
+  // Reloads the shader whenever the file changes, checked at the start of
+  // each render(). A broken shader is logged and the last good one kept.
+  #[cfg(feature = "hot-reload")]
+  pub fn watch_shader(&mut self, path: &Path) -> Result<(), ShaderError> {
+    self.load_shader_from_path(path)?;
+    self.shader_watcher = Some(shader::ShaderWatcher::new(path)?);
+    Ok(())
+  }
+
+  #[cfg(feature = "hot-reload")]
+  fn reload_changed_shader(&mut self) {
+    let Some(path) = self.shader_watcher.as_ref().and_then(|watcher| watcher.changed()) else { return };
+    match self.load_shader_from_path(&path) {
+      Ok(()) => log::info!("Reloaded shader {}", path.display()),
+      Err(err) => log::error!("Keeping the previous shader: {}", err),
+    }
+  }
+
+  // takes effect from the next render()
+  pub fn set_clear_color(&mut self, color: wgpu::Color) {
+    self.options.clear_color = color;
+  }
+
+  // Rebuilds the pipelines and render targets for the new sample count.
+  // Returns the count actually in use.
+  pub fn set_msaa_samples(&mut self, samples: u32) -> u32 {
+    self.options.msaa_samples = samples;
+    self.rebuild_for_format();
+    self.options.msaa_samples
+  }
+
+  fn rebuild_for_format(&mut self) {
+    self.options.msaa_samples = State::supported_sample_count(&self.adapter, self.render_format(), &self.options);
+    self.pipelines.clear();
+    self.recreate_targets();
+  }
+
+  // depth and MSAA targets follow the surface size and format
+  fn recreate_targets(&mut self) {
+    let samples = self.options.msaa_samples;
+    self.depth_texture = self.options.depth
+      .then(|| Texture::create_depth_texture(&self.device, self.config.width, self.config.height, samples, "Depth Texture"));
+    self.msaa_target = State::create_msaa_target(&self.device, &self.config, samples);
+  }
+
+  // the Auto modes are resolved by wgpu itself, anything else has to be listed
+  fn supported_present_mode(caps: &wgpu::SurfaceCapabilities, mode: wgpu::PresentMode) -> wgpu::PresentMode {
+    let auto = matches!(mode, wgpu::PresentMode::AutoVsync | wgpu::PresentMode::AutoNoVsync);
+    if auto || caps.present_modes.contains(&mode) {
+      mode
+    } else {
+      log::warn!("Present mode {:?} isn't supported by the surface, falling back to Fifo", mode);
+      wgpu::PresentMode::Fifo
+    }
+  }
+
+  fn supported_sample_count(adapter: &wgpu::Adapter, format: wgpu::TextureFormat, options: &StateOptions) -> u32 {
+    let samples = options.msaa_samples;
+    let supported = |format| adapter.get_texture_format_features(format).flags.sample_count_supported(samples);
+    if supported(format) && (!options.depth || supported(texture::DEPTH_FORMAT)) {
+      samples
+    } else {
+      log::warn!("{}x MSAA isn't supported for {:?}, falling back to no MSAA", samples, format);
+      1
+    }
+  }
+
+  fn create_msaa_target(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, sample_count: u32) -> Option<wgpu::TextureView> {
+    if sample_count <= 1 {
+      return None;
+    }
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+      label: Some("MSAA Target"),
+      size: wgpu::Extent3d {
+        width: config.width.max(1),
+        height: config.height.max(1),
+        depth_or_array_layers: 1,
+      },
+      mip_level_count: 1,
+      sample_count,
+      dimension: wgpu::TextureDimension::D2,
+      format: color_space::render_format(config),
+      usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+      view_formats: &[],
+    });
+    Some(texture.create_view(&wgpu::TextureViewDescriptor::default()))
+  }
+
+  // makes every crate-internal random stream reproducible, see RngStreams
+  pub fn set_rng_seed(&mut self, seed: u64) {
+    self.rng.reseed(seed);
+  }
+
+  pub fn rng_seed(&self) -> u64 {
+    self.rng.seed()
+  }
+
+  pub fn rng(&mut self, subsystem: &'static str) -> &mut Pcg32 {
+    self.rng.stream(subsystem)
+  }
+
+  // Buffer map callbacks only run when the device is polled. render() polls
+  // once per frame; call this when waiting on a readback without rendering.
+  // On the web the browser drives this and polling is a no-op.
+  pub fn poll(&self, wait: bool) {
+    let maintain = if wait { wgpu::Maintain::Wait } else { wgpu::Maintain::Poll };
+    self.device.poll(maintain);
+  }
+
+  // polls without blocking only if some mapping is still outstanding,
+  // cheap enough to call from every event loop iteration
+  pub fn poll_outstanding(&mut self) -> bool {
+    let outstanding = self.map_tracker.outstanding() > 0;
+    if outstanding {
+      self.device.poll(wgpu::Maintain::Poll);
+    }
+    self.finish_capture();
+    self.finish_gpu_timing();
+    outstanding
+  }
+
+  // Polls the device without blocking, then hands back every readback of
+  // `ring` that finished mapping. Safe to call from the render thread every
+  // frame; a readback recorded in frame F shows up a frame or two later.
+  pub fn poll_readbacks<'a, T: bytemuck::Pod>(&mut self, ring: &'a mut ReadbackRing<T>) -> impl Iterator<Item = Readback<T>> + 'a {
+    self.poll_outstanding();
+    ring.poll()
+  }
+
+  // hand this to mapped-buffer utilities (ReadbackRing::tracked_by) so the
+  // outstanding map count stays accurate
+  pub fn map_tracker(&self) -> MapTracker {
+    self.map_tracker.clone()
+  }
+
+  // keeps the device polled from a background thread while mappings are
+  // outstanding, for apps that stop rendering while they wait
+  #[cfg(feature = "poll-thread")]
+  pub fn spawn_poll_thread(&mut self) {
+    if self.poll_thread.is_none() {
+      self.poll_thread = Some(poll::PollThread::spawn(Arc::clone(&self.device), self.map_tracker.clone()));
+    }
+  }
+
+  // Replaces the drawn geometry with a non-indexed triangle list. The vertex
+  // buffer is reused while the data fits and reallocated when it doesn't.
+  // Fails without changing anything if the data is over max_buffer_size.
+  pub fn set_vertices(&mut self, vertices: &[Vertex]) -> Result<(), ExceedsLimit> {
+    self.write_vertices(bytemuck::cast_slice(vertices), vertices.len())?;
+    self.num_indices = None;
+    self.mesh_kind = MeshKind::Color;
+    Ok(())
+  }
+
+  // For geometry that changes every frame. Unlike set_vertices() the current
+  // indices are kept, so an indexed mesh keeps its topology. The data is
+  // written in place and the buffer only reallocates when it has to grow, so
+  // see vertex_buffer_size() for the capacity. Zero vertices skip the draw.
+  pub fn update_vertices(&mut self, vertices: &[Vertex]) -> Result<(), ExceedsLimit> {
+    self.write_vertices(bytemuck::cast_slice(vertices), vertices.len())?;
+    self.mesh_kind = MeshKind::Color;
+    Ok(())
+  }
+
+  // Like set_vertices() but drawn through an index buffer. An empty index
+  // slice falls back to drawing the vertices as a plain triangle list.
+  pub fn set_indexed_mesh<'a>(&mut self, vertices: &[Vertex], indices: impl Into<Indices<'a>>) -> Result<(), ExceedsLimit> {
+    let indices = indices.into();
+    self.check_mesh_size(bytemuck::cast_slice(vertices), indices)?;
+    self.write_vertices(bytemuck::cast_slice(vertices), vertices.len())?;
+    self.write_indices(indices)?;
+    self.mesh_kind = MeshKind::Color;
+    Ok(())
+  }
+
+  // textured counterpart of set_indexed_mesh(), `texture` comes from load_texture()
+  pub fn set_textured_mesh<'a>(
+    &mut self,
+    vertices: &[TexturedVertex],
+    indices: impl Into<Indices<'a>>,
+    texture: TextureId,
+  ) -> Result<(), ExceedsLimit> {
+    let indices = indices.into();
+    self.check_mesh_size(bytemuck::cast_slice(vertices), indices)?;
+    self.write_vertices(bytemuck::cast_slice(vertices), vertices.len())?;
+    self.write_indices(indices)?;
+    self.mesh_kind = MeshKind::Textured(texture);
+    Ok(())
+  }
+
+  // Like set_textured_mesh(), but the base color is lit by the irradiance of
+  // set_environment() in the direction of the normal, perturbed by the
+  // material's normal map. Without an environment the light is constant white.
+  pub fn set_lit_mesh<'a>(
+    &mut self,
+    vertices: &[LitVertex],
+    indices: impl Into<Indices<'a>>,
+    material: Material,
+  ) -> Result<(), ExceedsLimit> {
+    let indices = indices.into();
+    self.check_mesh_size(bytemuck::cast_slice(vertices), indices)?;
+    self.write_vertices(bytemuck::cast_slice(vertices), vertices.len())?;
+    self.write_indices(indices)?;
+    let normal_map = material.normal_map.map_or(&self.flat_normal_map, |id| &self.textures[id.0].0);
+    self.material_bind_group = Some(material::bind_group(
+      &self.device,
+      &self.material_bind_group_layout,
+      &self.textures[material.base_color.0].0,
+      normal_map,
+    ));
+    self.mesh_kind = MeshKind::Lit;
+    Ok(())
+  }
+
+  // both buffers are checked before either is written, so a failed call
+  // doesn't leave new vertices with the old indices
+  fn check_mesh_size(&self, vertices: &[u8], indices: Indices) -> Result<(), ExceedsLimit> {
+    let limits = self.device.limits();
+    limits::check_buffer_size(&limits, "Vertex Buffer", vertices.len() as u64)?;
+    limits::check_buffer_size(&limits, "Index Buffer", indices.as_bytes().len() as u64)
+  }
+
+  // Draws one copy of the mesh per instance. An empty slice draws nothing;
+  // pass a single Instance::default() to get back to the untransformed mesh.
+  // Uploads all of them; for a few changes use update_instances_range().
+  pub fn set_instances(&mut self, instances: &[Instance]) -> Result<(), ExceedsLimit> {
+    // before converting, which would allocate all of it
+    let size = (instances.len() * std::mem::size_of::<InstanceRaw>()) as u64;
+    limits::check_buffer_size(&self.device.limits(), "Instance Buffer", size)?;
+    self.instances = instances.iter().map(Instance::to_raw).collect();
+    self.write_all_instances()
+  }
+
+  // Replaces the instances from `start` on, appending past the current end.
+  // Only the changed instances are uploaded, at the next render(), where
+  // adjacent and overlapping changes become one write. Appending stays in
+  // the buffer up to instance_capacity(), beyond that it reallocates and
+  // uploads everything. Panics if `start` is past num_instances().
+  pub fn update_instances_range(&mut self, start: usize, instances: &[Instance]) -> Result<(), ExceedsLimit> {
+    assert!(start <= self.instances.len(), "Instance {} is past the end ({} instances)", start, self.instances.len());
+    let end = start + instances.len();
+    let size = (end.max(self.instances.len()) * std::mem::size_of::<InstanceRaw>()) as u64;
+    limits::check_buffer_size(&self.device.limits(), "Instance Buffer", size)?;
+    let overlap = instances.len().min(self.instances.len() - start);
+    for (raw, instance) in self.instances[start..].iter_mut().zip(instances) {
+      *raw = instance.to_raw();
+    }
+    self.instances.extend(instances[overlap..].iter().map(Instance::to_raw));
+    if size > self.instance_buffer.size() {
+      return self.write_all_instances();
+    }
+    self.dirty_instances.mark(start..end);
+    Ok(())
+  }
+
+  // like update_instances_range() for a single instance
+  pub fn update_instance(&mut self, index: usize, instance: Instance) -> Result<(), ExceedsLimit> {
+    self.update_instances_range(index, &[instance])
+  }
+
+  // drops the instances from `len` on, keeping the buffer for later appends
+  pub fn truncate_instances(&mut self, len: usize) {
+    self.instances.truncate(len);
+  }
+
+  // grows the buffer so up to `capacity` instances fit without reallocating
+  pub fn reserve_instances(&mut self, capacity: usize) -> Result<(), ExceedsLimit> {
+    let size = (capacity * std::mem::size_of::<InstanceRaw>()) as u64;
+    if size <= self.instance_buffer.size() {
+      return Ok(());
+    }
+    limits::check_buffer_size(&self.device.limits(), "Instance Buffer", size)?;
+    self.instance_buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+      label: Some("Instance Buffer"),
+      size,
+      usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+      mapped_at_creation: false,
+    });
+    self.write_all_instances()
+  }
+
+  pub fn num_instances(&self) -> u32 {
+    self.instances.len() as u32
+  }
+
+  // how many instances fit in the buffer as it is
+  pub fn instance_capacity(&self) -> u32 {
+    (self.instance_buffer.size() / std::mem::size_of::<InstanceRaw>() as u64) as u32
+  }
+
+  fn write_all_instances(&mut self) -> Result<(), ExceedsLimit> {
+    self.dirty_instances.clear();
+    // keep the old buffer around, binding a zero-sized one isn't allowed
+    if self.instances.is_empty() {
+      return Ok(());
+    }
+    State::write_or_grow(
+      &self.device,
+      &self.queue,
+      &mut self.instance_buffer,
+      "Instance Buffer",
+      wgpu::BufferUsages::VERTEX,
+      bytemuck::cast_slice(&self.instances),
+    )?;
+    self.note_upload("Instance Buffer", std::mem::size_of_val(self.instances.as_slice()) as u64);
+    Ok(())
+  }
+
+  // the changes since the last frame, submitted with it
+  fn upload_instances(&mut self) {
+    let stride = std::mem::size_of::<InstanceRaw>();
+    for range in self.dirty_instances.take_coalesced() {
+      // truncate_instances() may have dropped some since
+      let range = range.start..range.end.min(self.instances.len());
+      if range.is_empty() {
+        continue;
+      }
+      let bytes: &[u8] = bytemuck::cast_slice(&self.instances[range.clone()]);
+      self.queue.write_buffer(&self.instance_buffer, (range.start * stride) as u64, bytes);
+      self.note_upload("Instance Buffer", bytes.len() as u64);
+    }
+  }
+
+  // Adds a mesh with its own buffers and transform, identity to start with.
+  // Objects are drawn after the main mesh in insertion order. With the depth
+  // buffer on, where they overlap at the same depth the earlier one stays on
+  // top; without it the later one does. Fails if either
+  // buffer would be over max_buffer_size.
+  pub fn create_object<'a>(&mut self, vertices: &[Vertex], indices: impl Into<Indices<'a>>) -> Result<ObjectId, ExceedsLimit> {
+    let mesh = self.object_mesh(vertices, indices.into())?;
+    Ok(self.insert_object(LodGroup::new().with_level(mesh, f32::INFINITY)))
+  }
+
+  // Like create_object(), with one mesh per level of detail. Each render()
+  // picks the level from the distance between the camera's eye and the
+  // object's origin, see LodGroup::select() and lod_stats(); beyond the last
+  // switch distance the object isn't drawn.
+  pub fn create_lod_object(&mut self, lod: &LodGroup<(Vec<Vertex>, Vec<u32>)>) -> Result<ObjectId, ExceedsLimit> {
+    let lod = lod.try_map(|(vertices, indices)| self.object_mesh(vertices, indices[..].into()))?;
+    Ok(self.insert_object(lod))
+  }
+
+  // debug override for an object's level, see LodGroup::force_level(); false
+  // if the object was removed
+  pub fn force_object_lod(&mut self, id: ObjectId, level: Option<usize>) -> bool {
+    let Some(object) = self.objects.get_mut(id) else { return false };
+    object.lod.force_level(level);
+    true
+  }
+
+  // how many objects the last render() drew at each level and how many it culled
+  pub fn lod_stats(&self) -> &LodStats {
+    &self.lod_stats
+  }
+
+  fn object_mesh(&mut self, vertices: &[Vertex], indices: Indices) -> Result<ObjectMesh, ExceedsLimit> {
+    let device_limits = self.device.limits();
+    limits::check_buffer_size(&device_limits, "Object Vertex Buffer", std::mem::size_of_val(vertices) as u64)?;
+    limits::check_buffer_size(&device_limits, "Object Index Buffer", indices.as_bytes().len() as u64)?;
+    let vertex_buffer = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+      label: Some("Object Vertex Buffer"),
+      contents: bytemuck::cast_slice(vertices),
+      usage: wgpu::BufferUsages::VERTEX,
+    });
+    let indices = (!indices.is_empty()).then(|| {
+      let buffer = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Object Index Buffer"),
+        contents: indices.as_bytes(),
+        usage: wgpu::BufferUsages::INDEX,
+      });
+      (buffer, indices.format(), indices.len() as u32)
+    });
+
+    self.note_upload("Object Vertex Buffer", std::mem::size_of_val(vertices) as u64);
+    if let Some((buffer, _, _)) = &indices {
+      self.note_upload("Object Index Buffer", buffer.size());
+    }
+    Ok(ObjectMesh {
+      vertex_buffer,
+      num_vertices: vertices.len() as u32,
+      indices,
+    })
+  }
+
+  fn insert_object(&mut self, lod: LodGroup<ObjectMesh>) -> ObjectId {
+    let uniform_buffer = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+      label: Some("Object Uniform Buffer"),
+      contents: bytemuck::cast_slice(&[self.object_uniforms(IDENTITY)]),
+      usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+    });
+    let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+      label: Some("Object Bind Group"),
+      layout: &self.uniform_bind_group_layout,
+      entries: &[wgpu::BindGroupEntry {
+        binding: 0,
+        resource: uniform_buffer.as_entire_binding(),
+      }],
+    });
+    self.note_upload("Object Uniform Buffer", std::mem::size_of::<Uniforms>() as u64);
+
+    self.objects.insert(DrawObject {
+      lod,
+      level: None,
+      transform: IDENTITY,
+      depth_bias: DepthBias::NONE,
+      uniform_buffer,
+      bind_group,
+    })
+  }
+
+  // picks every object's level for this frame
+  fn select_lods(&mut self) {
+    self.lod_stats.clear();
+    let eye = self.camera.eye;
+    let scene = cgmath::Matrix4::from(self.uniforms.transform);
+    for object in self.objects.iter_mut() {
+      let origin = scene * cgmath::Matrix4::from(object.transform) * cgmath::Vector4::unit_w();
+      let distance = cgmath::Point3::from_homogeneous(origin).distance(eye);
+      object.level = object.lod.select(distance, object.level);
+      self.lod_stats.record(object.level);
+    }
+  }
+
+  // a loaded model as an object, colored by its normals
+  #[cfg(feature = "models")]
+  pub fn upload_mesh(&mut self, mesh: &Mesh) -> Result<ObjectId, ExceedsLimit> {
+    self.create_object(&mesh.vertices(), &mesh.indices)
+  }
+
+  // applied after the update_uniforms() transform; false if the object was removed
+  pub fn set_object_transform(&mut self, id: ObjectId, transform: [[f32; 4]; 4]) -> bool {
+    let uniforms = self.object_uniforms(transform);
+    let Some(object) = self.objects.get_mut(id) else { return false };
+    object.transform = transform;
+    self.queue.write_buffer(&object.uniform_buffer, 0, bytemuck::cast_slice(&[uniforms]));
+    self.note_upload("Object Uniform Buffer", std::mem::size_of::<Uniforms>() as u64);
+    true
+  }
+
+  // like set_depth_bias(), for one object; false if the object was removed
+  pub fn set_object_depth_bias(&mut self, id: ObjectId, depth_bias: DepthBias) -> bool {
+    let Some(object) = self.objects.get_mut(id) else { return false };
+    object.depth_bias = depth_bias;
+    true
+  }
+
+  // other ids stay valid; false if the object was already removed
+  pub fn remove_object(&mut self, id: ObjectId) -> bool {
+    self.objects.remove(id).is_some()
+  }
+
+  pub fn num_objects(&self) -> usize {
+    self.objects.len()
+  }
+
+  // Small per-draw data like a tint or model matrix, for shaders that declare
+  // `var<push_constant> pc: T;`, used by every draw from the next render() on.
+  // Real push constants when the device has Features::PUSH_CONSTANTS, a uniform
+  // buffer otherwise (WebGL), which the shader can't tell apart. T has to
+  // follow the uniform layout rules either way (16 byte array strides, so
+  // vec4 rather than f32 arrays), load_shader() checks that on every device.
+  // Bytes past the data are zero. Fails for more than push_constant_size() bytes.
+  pub fn set_push_constants(&mut self, bytes: &[u8]) -> Result<(), ExceedsLimit> {
+    self.push_constants.set(&self.queue, bytes)
+  }
+
+  // MAX_PUSH_CONSTANT_SIZE, unless the device's push constant limit is lower
+  pub fn push_constant_size(&self) -> u32 {
+    self.push_constants.size()
+  }
+
+  // Lines drawn on top of the mesh and objects every frame until cleared,
+  // e.g. `state.debug().aabb(min, max, color)`, see DebugDraw.
+  pub fn debug(&mut self) -> &mut DebugDraw {
+    &mut self.debug_draw
+  }
+
+  // the camera without the update_uniforms() transform, lines are in world space
+  fn debug_uniforms(&self) -> Uniforms {
+    Uniforms {
+      view_proj: self.uniforms.view_proj,
+      transform: IDENTITY,
+    }
+  }
+
+  // uploads the lines if they changed since the last frame; the buffers are
+  // only created once there is a line to put in them
+  fn upload_debug_lines(&mut self) {
+    let uniforms = self.debug_uniforms();
+    let Some(vertices) = self.debug_draw.take_changes() else { return };
+    let result = match self.debug_lines.as_mut() {
+      Some(lines) => lines.upload(&self.device, &self.queue, vertices),
+      None if vertices.is_empty() => return,
+      None => debug_draw::DebugLines::new(&self.device, &self.uniform_bind_group_layout, uniforms, vertices)
+        .map(|lines| self.debug_lines = Some(lines)),
+    };
+    if let Err(err) = result {
+      log::error!("Not drawing the debug lines: {}", err);
+      return;
+    }
+    let bytes = std::mem::size_of_val(vertices) as u64;
+    self.note_upload("Debug Vertex Buffer", bytes);
+  }
+
+  fn note_upload(&mut self, label: &'static str, bytes: u64) {
+    match self.pending_uploads.iter_mut().find(|(pending, _)| *pending == label) {
+      Some((_, total)) => *total += bytes,
+      None => self.pending_uploads.push((label, bytes)),
+    }
+  }
+
+  fn object_uniforms(&self, transform: [[f32; 4]; 4]) -> Uniforms {
+    let transform = cgmath::Matrix4::from(self.uniforms.transform) * cgmath::Matrix4::from(transform);
+    Uniforms {
+      view_proj: self.uniforms.view_proj,
+      transform: transform.into(),
+    }
+  }
+
+  // decodes a PNG/JPEG as an sRGB color texture
+  pub fn load_texture(&mut self, bytes: &[u8]) -> anyhow::Result<TextureId> {
+    self.load_texture_with(bytes, ColorSpace::Srgb)
+  }
+
+  pub fn load_texture_with(&mut self, bytes: &[u8], color_space: ColorSpace) -> anyhow::Result<TextureId> {
+    let id = TextureId(self.textures.len());
+    let texture = Texture::from_bytes_with(&self.device, &self.queue, bytes, &format!("Texture {}", id.0), color_space)?;
+    let bind_group = texture.bind_group(&self.device, &self.texture_bind_group_layout);
+    let size = texture.texture.size();
+    self.note_upload("Texture", size.width as u64 * size.height as u64 * 4);
+    self.textures.push((texture, bind_group));
+    Ok(id)
+  }
+
+  pub fn texture(&self, id: TextureId) -> &Texture {
+    &self.textures[id.0].0
+  }
+
+  fn write_vertices(&mut self, contents: &[u8], count: usize) -> Result<(), ExceedsLimit> {
+    State::write_or_grow(
+      &self.device,
+      &self.queue,
+      &mut self.vertex_buffer,
+      "Vertex Buffer",
+      wgpu::BufferUsages::VERTEX,
+      contents,
+    )?;
+    self.note_upload("Vertex Buffer", contents.len() as u64);
+    self.num_vertices = count as u32;
+    Ok(())
+  }
+
+  fn write_indices(&mut self, indices: Indices) -> Result<(), ExceedsLimit> {
+    if indices.is_empty() {
+      self.num_indices = None;
+      return Ok(());
+    }
+    State::write_or_grow(
+      &self.device,
+      &self.queue,
+      &mut self.index_buffer,
+      "Index Buffer",
+      wgpu::BufferUsages::INDEX,
+      indices.as_bytes(),
+    )?;
+    self.note_upload("Index Buffer", indices.as_bytes().len() as u64);
+    self.index_format = indices.format();
+    self.num_indices = Some(indices.len() as u32);
+    Ok(())
+  }
+
+  // the model transform applied to every vertex before the camera,
+  // identity by default
+  pub fn update_uniforms(&mut self, transform: [[f32; 4]; 4]) {
+    self.uniforms.transform = transform;
+    self.write_uniforms();
+  }
+
+  pub fn camera(&self) -> &Camera {
+    &self.camera
+  }
+
+  // changes are picked up by the next render()
+  pub fn camera_mut(&mut self) -> &mut Camera {
+    &mut self.camera
+  }
+
+  fn write_uniforms(&mut self) {
+    self.uniforms.view_proj = self.camera.build_view_projection_matrix().into();
+    self.queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[self.uniforms]));
+    for object in self.objects.iter() {
+      let uniforms = self.object_uniforms(object.transform);
+      self.queue.write_buffer(&object.uniform_buffer, 0, bytemuck::cast_slice(&[uniforms]));
+    }
+    let uniforms_size = std::mem::size_of::<Uniforms>() as u64;
+    self.note_upload("Uniform Buffer", uniforms_size);
+    if !self.objects.is_empty() {
+      self.note_upload("Object Uniform Buffer", self.objects.len() as u64 * uniforms_size);
+    }
+    if let Some(lines) = &self.debug_lines {
+      lines.write_uniforms(&self.queue, self.debug_uniforms());
+      self.note_upload("Debug Uniform Buffer", uniforms_size);
+    }
+    if let Some(skybox) = &self.skybox {
+      skybox.write_uniforms(&self.queue, &self.camera);
+      self.note_upload("Skybox Uniform Buffer", uniforms_size);
+    }
+  }
+
+  pub fn uniforms(&self) -> &Uniforms {
+    &self.uniforms
+  }
+
+  pub fn num_vertices(&self) -> u32 {
+    self.num_vertices
+  }
+
+  pub fn num_indices(&self) -> Option<u32> {
+    self.num_indices
+  }
+
+  // allocated size in bytes, may be larger than the current vertices need
+  pub fn vertex_buffer_size(&self) -> wgpu::BufferAddress {
+    self.vertex_buffer.size()
+  }
+
+  pub fn device(&self) -> &wgpu::Device {
+    &self.device
+  }
+
+  pub fn queue(&self) -> &wgpu::Queue {
+    &self.queue
+  }
+
+  pub fn downlevel(&self) -> &wgpu::DownlevelCapabilities {
+    &self.downlevel
+  }
+
+  pub fn capability_report(&self) -> &CapabilityReport {
+    &self.capabilities
+  }
+
+  // decodes a Radiance .hdr equirectangular map, converts it to a cubemap and
+  // convolves the irradiance cubemap used for ambient lighting. The cubemap
+  // is drawn as a skybox behind the scene from the next render() on.
+  pub fn set_environment(&mut self, hdr_bytes: &[u8]) -> anyhow::Result<()> {
+    self.set_environment_with(hdr_bytes, &load::LoadContext::new())
+  }
+
+  // same as set_environment(), with progress reporting and cancellation;
+  // a cancelled load keeps the previous environment
+  pub fn set_environment_with(&mut self, hdr_bytes: &[u8], context: &load::LoadContext) -> anyhow::Result<()> {
+    if !self.capabilities.environment_maps {
+      anyhow::bail!("HDR environment maps need a renderable, filterable Rgba16Float format, which this adapter lacks");
+    }
+    let environment = Environment::from_hdr_with(&self.device, &self.queue, hdr_bytes, context)?;
+    self.environment_bind_group = environment.bind_group(&self.device, &self.environment_bind_group_layout);
+    self.skybox = Some(skybox::Skybox::new(
+      &self.device,
+      &self.uniform_bind_group_layout,
+      &self.environment_bind_group_layout,
+      &environment,
+      &self.camera,
+    ));
+    self.environment = Some(environment);
+    Ok(())
+  }
+
+  pub fn environment(&self) -> Option<&Environment> {
+    self.environment.as_ref()
+  }
+
+  // Only records the size; render() reconfigures once before the next frame,
+  // so a burst of resize events while dragging costs a single configure. A
+  // zero size (minimized window) pauses rendering until a real size arrives.
+  pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
+    if new_size != self.size {
+      self.size = new_size;
+      self.needs_reconfigure = true;
+    }
+  }
+
+  pub fn size(&self) -> winit::dpi::PhysicalSize<u32> {
+    self.size
+  }
+
+  // the negotiated format, see color_space_info() for how it's rendered to
+  pub fn surface_format(&self) -> wgpu::TextureFormat {
+    self.config.format
+  }
+
+  // the format of the frame views, the sRGB variant of surface_format() when
+  // rendering through an sRGB view
+  pub fn render_format(&self) -> wgpu::TextureFormat {
+    color_space::render_format(&self.config)
+  }
+
+  // what the surface is configured with, see StateOptions::surface_usages
+  pub fn surface_usages(&self) -> wgpu::TextureUsages {
+    self.config.usage
+  }
+
+  // how the colors get sRGB encoded on the current surface
+  pub fn color_space_info(&self) -> ColorSpaceInfo {
+    ColorSpaceInfo::new(&self.config)
+  }
+
+  // includes a mode set for the next frame that isn't applied yet
+  pub fn present_mode(&self) -> wgpu::PresentMode {
+    self.pending_present_mode.unwrap_or(self.config.present_mode)
+  }
+
+  pub fn supported_present_modes(&self) -> &[wgpu::PresentMode] {
+    &self.surface_caps.present_modes
+  }
+
+  pub fn supported_formats(&self) -> &[wgpu::TextureFormat] {
+    &self.surface_caps.formats
+  }
+
+  pub fn adapter_info(&self) -> wgpu::AdapterInfo {
+    self.adapter.get_info()
+  }
+
+  // The features granted for StateOptions::features: all the required ones
+  // and the optional ones the adapter has. Wireframe rendering, GPU frame
+  // timing and real push constants depend on the defaults being in here.
+  pub fn enabled_features(&self) -> wgpu::Features {
+    self.device.features()
+  }
+
+  // Writes the compiled pipelines to StateOptions::pipeline_cache_dir, also
+  // done when the State is dropped. Does nothing without a pipeline cache.
+  pub fn save_pipeline_cache(&self) -> std::io::Result<()> {
+    match &self.pipeline_cache {
+      Some(file) => file.save(),
+      None => Ok(()),
+    }
+  }
+
+  // The surface is reconfigured with the new mode before the next frame.
+  // The Auto modes are always accepted, wgpu resolves them itself.
+  pub fn set_present_mode(&mut self, mode: wgpu::PresentMode) -> Result<(), UnsupportedMode> {
+    let auto = matches!(mode, wgpu::PresentMode::AutoVsync | wgpu::PresentMode::AutoNoVsync);
+    if !auto && !self.surface_caps.present_modes.contains(&mode) {
+      return Err(UnsupportedMode {
+        mode,
+        supported: self.surface_caps.present_modes.clone(),
+      });
+    }
+    self.pending_present_mode = Some(mode);
+    Ok(())
+  }
+
+  // render() does nothing while minimized, callers can skip requesting redraws
+  pub fn is_minimized(&self) -> bool {
+    self.size.width == 0 || self.size.height == 0
+  }
+
+  // Drops the surface, e.g. on Android's Suspended event after which the
+  // native window is gone. The device, pipelines and buffers stay alive and
+  // render() returns RenderError::Suspended until resume(). Headless states
+  // ignore this.
+  pub fn suspend(&mut self) {
+    if let RenderTarget::Surface(_) = self.target {
+      self.target = RenderTarget::Suspended;
+    }
+  }
+
+  // Creates a new surface for `window` with the stored configuration, after
+  // suspend() or when the window was recreated. The format, present mode
+  // and alpha mode are renegotiated if the new surface lacks them. Fails
+  // like State::new() if the adapter can't present to the window, leaving
+  // the State suspended. Headless states ignore this.
+  pub fn resume(&mut self, window: Arc<Window>) -> Result<(), StateError> {
+    if let RenderTarget::Offscreen(_) = self.target {
+      return Ok(());
+    }
+    let size = window.inner_size();
+    let surface = self.instance.create_surface(window)?;
+    if surface.get_capabilities(&self.adapter).formats.is_empty() {
+      self.target = RenderTarget::Suspended;
+      return Err(StateError::NoSupportedFormat);
+    }
+    self.target = RenderTarget::Surface(surface);
+    self.renegotiate_surface();
+    self.size = size;
+    // a new surface always starts out unconfigured
+    self.needs_reconfigure = true;
+    Ok(())
+  }
+
+  pub fn is_suspended(&self) -> bool {
+    matches!(self.target, RenderTarget::Suspended)
+  }
+
+  fn reconfigure(&mut self) {
+    self.needs_reconfigure = false;
+    // the swapchain textures are bound by the 2D texture limit too
+    let max_size = self.device.limits().max_texture_dimension_2d;
+    if self.size.width > max_size || self.size.height > max_size {
+      log::warn!("Surface size {}x{} exceeds the device limit of {}, clamping", self.size.width, self.size.height, max_size);
+    }
+    let (width, height) = (self.size.width.min(max_size), self.size.height.min(max_size));
+    let resized = width != self.config.width || height != self.config.height;
+    self.config.width = width;
+    self.config.height = height;
+    match &mut self.target {
+      RenderTarget::Surface(surface) => surface.configure(&self.device, &self.config),
+      RenderTarget::Offscreen(texture) if resized => *texture = State::create_offscreen_target(&self.device, &self.config),
+      RenderTarget::Offscreen(_) | RenderTarget::Suspended => {}
+    }
+    if resized {
+      self.recreate_targets();
+      self.camera.aspect = self.config.width as f32 / self.config.height as f32;
+      self.write_uniforms();
+    }
+  }
+
+  // the next render() records what it submits, see debug_dump_frame()
+  pub fn capture_next_frame_dump(&mut self) {
+    self.capture_frame_dump = true;
+  }
+
+  // the most recently captured frame, if any
+  pub fn debug_dump_frame(&self) -> Option<&FrameDump> {
+    self.frame_dump.as_ref()
+  }
+
+  // Switching present mode only needs the surface reconfigured; textures and
+  // pipelines don't depend on it. The change is deferred to the start of the
+  // next render() so no acquired frame gets presented under a stale mode.
+  pub fn set_vsync(&mut self, enabled: bool) {
+    let mode = if enabled {
+      wgpu::PresentMode::AutoVsync
+    } else {
+      wgpu::PresentMode::AutoNoVsync
+    };
+    self.pending_present_mode = Some(mode);
+  }
+
+  pub fn vsync_enabled(&self) -> bool {
+    let mode = self.pending_present_mode.unwrap_or(self.config.present_mode);
+    matches!(mode, wgpu::PresentMode::AutoVsync | wgpu::PresentMode::Fifo | wgpu::PresentMode::FifoRelaxed)
+  }
+
+  // present-to-present timing; call frame_pacing_mut().pause() before an
+  // intentional wait so it isn't reported as a hitch
+  pub fn frame_pacing(&self) -> &FramePacing {
+    &self.frame_pacing
+  }
+
+  pub fn frame_pacing_mut(&mut self) -> &mut FramePacing {
+    &mut self.frame_pacing
+  }
+
+  // CPU, present and (with TIMESTAMP_QUERY) GPU time of the last rendered frame
+  pub fn last_frame_stats(&self) -> Option<FrameStats> {
+    self.frame_timings.last()
+  }
+
+  // the same averaged over the last STATS_WINDOW frames, plus frames per second
+  pub fn average_frame_stats(&self) -> AverageFrameStats {
+    self.frame_timings.average(&self.frame_pacing)
+  }
+
+  fn finish_gpu_timing(&mut self) {
+    if let Some(gpu_time) = self.gpu_timer.as_mut().and_then(|timer| timer.try_read()) {
+      self.frame_timings.record_gpu_time(gpu_time);
+    }
+  }
+
+  // Re-queries the surface capabilities, e.g. after the window moved to another
+  // monitor, and reconfigures before the next frame when the current format,
+  // present mode or alpha mode is no longer supported. Returns whether
+  // anything changed.
+  pub fn renegotiate_surface(&mut self) -> bool {
+    let RenderTarget::Surface(surface) = &self.target else { return false };
+    let caps = surface.get_capabilities(&self.adapter);
+    let mut changed = false;
+
+    if !caps.formats.contains(&self.config.format) {
+      let Some(format) = caps.formats.iter().find(|f| f.is_srgb()).or(caps.formats.first()).copied() else {
+        log::warn!("Surface reports no supported formats, keeping {:?}", self.config.format);
+        return false;
+      };
+      log::info!("Surface format changed from {:?} to {:?}", self.config.format, format);
+      self.config.format = format;
+      self.config.view_formats = color_space::srgb_view_formats(&self.adapter, format);
+      // the pipelines' color target and the MSAA target have to match the new format
+      self.rebuild_for_format();
+      changed = true;
+    }
+
+    // the Auto modes are resolved by wgpu itself on every configure
+    let present_mode = self.pending_present_mode.take().unwrap_or(self.config.present_mode);
+    let auto = matches!(present_mode, wgpu::PresentMode::AutoVsync | wgpu::PresentMode::AutoNoVsync);
+    if !auto && !caps.present_modes.contains(&present_mode) {
+      log::info!("Present mode {:?} is no longer supported, falling back to Fifo", present_mode);
+      self.config.present_mode = wgpu::PresentMode::Fifo;
+      changed = true;
+    } else if present_mode != self.config.present_mode {
+      self.config.present_mode = present_mode;
+      changed = true;
+    }
+
+    // the extra usages are kept as long as the surface still allows them
+    if !caps.usages.contains(self.config.usage) {
+      let usage = self.config.usage & caps.usages | wgpu::TextureUsages::RENDER_ATTACHMENT;
+      log::warn!("Surface usages {:?} are no longer supported, dropping them", self.config.usage - usage);
+      self.config.usage = usage;
+      changed = true;
+    }
+
+    if !caps.alpha_modes.contains(&self.config.alpha_mode) {
+      log::info!("Alpha mode changed from {:?} to {:?}", self.config.alpha_mode, caps.alpha_modes[0]);
+      self.config.alpha_mode = caps.alpha_modes[0];
+      changed = true;
+    }
+
+    self.surface_caps = caps;
+    if changed {
+      self.needs_reconfigure = true;
+    }
+    changed
+  }
+
+  // Handles an error from render(). Lost and outdated surfaces are
+  // reconfigured before the next frame, a timeout just skips the frame and
+  // so does a suspended State until resume(). Returns false for errors the
+  // app can't recover from (out of memory).
+  pub fn recover(&mut self, err: RenderError) -> bool {
+    match err {
+      RenderError::Surface(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+        self.needs_reconfigure = true;
+        true
+      }
+      RenderError::Surface(wgpu::SurfaceError::Timeout) => {
+        log::warn!("Surface timed out, skipping frame");
+        true
+      }
+      RenderError::Surface(wgpu::SurfaceError::OutOfMemory) => false,
+      RenderError::Suspended => true,
+    }
+  }
+
+  // render() followed by recover(), only fatal errors are returned
+  pub fn render_or_recover(&mut self) -> Result<(), RenderError> {
+    match self.render() {
+      Err(err) if !self.recover(err.clone()) => Err(err),
+      _ => Ok(()),
+    }
+  }
 
-// triangular shapes
-// const VERTICES: &[Vertex] = &[
-//   Vertex { position: [0.0, 0.5, 0.0], color: [1.0, 0.0, 0.0] },
-//   Vertex { position: [-0.5, -0.5, 0.0], color: [0.0, 1.0, 0.0] },
-//   Vertex { position: [0.5, -0.5, 0.0], color: [0.0, 0.0, 1.0] },
-// ];
+  // Saves the next rendered frame as a PNG. The frame is drawn a second time
+  // into a copyable texture, so the surface doesn't need COPY_SRC, and
+  // written once the readback completes; see take_capture_result().
+  pub fn capture_frame(&mut self, path: &Path) -> Result<(), CaptureError> {
+    capture::is_bgra(self.render_format())?;
+    self.capture_request = Some(path.to_path_buf());
+    Ok(())
+  }
 
-pub struct State<'window> {
-  surface: wgpu::Surface<'window>,
-  device: wgpu::Device,
-  queue: wgpu::Queue,
-  config: wgpu::SurfaceConfiguration,
-  size: winit::dpi::PhysicalSize<u32>,
-  render_pipeline: wgpu::RenderPipeline,
-  vertex_buffer: wgpu::Buffer,
-  index_buffer: wgpu::Buffer,
-  num_vertices: u32,
-}
+  // outcome of the last capture_frame(), once it has been written
+  pub fn take_capture_result(&mut self) -> Option<Result<std::path::PathBuf, CaptureError>> {
+    self.capture_result.take()
+  }
 
-impl<'window> State<'window> {
-  // Creating some of the wgpu types requires async code
-  pub fn new(window: Arc<Window>) -> State<'window> {
-    pollster::block_on(State::new_async(window))
+  fn finish_capture(&mut self) {
+    let Some(result) = self.frame_capture.as_mut().and_then(|capture| capture.try_finish()) else { return };
+    self.frame_capture = None;
+    match &result {
+      Ok(path) => log::info!("Saved frame to {}", path.display()),
+      Err(err) => log::error!("{}", err),
+    }
+    self.capture_result = Some(result);
   }
 
-  pub async fn new_async(window: Arc<Window>) -> State<'window> {
-    let size = window.inner_size();
-    // The instance is a handle to our GPU
-    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-      #[cfg(not(target_arch="wasm32"))]
-      backends: wgpu::Backends::PRIMARY,
-      #[cfg(target_arch="wasm32")]
-      backends: wgpu::Backends::GL,
-      ..Default::default()
+  // Copies the last headless frame back as tightly packed RGBA8 rows. Fails
+  // for window surfaces, their textures can't be read back.
+  pub fn read_pixels(&mut self) -> anyhow::Result<Vec<u8>> {
+    let RenderTarget::Offscreen(texture) = &self.target else {
+      anyhow::bail!("read_pixels() needs a headless State");
+    };
+    let row_bytes = self.config.width * 4;
+    let padded_row_bytes = readback::padded_bytes_per_row(row_bytes);
+    let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+      label: Some("Read Pixels Buffer"),
+      size: padded_row_bytes as u64 * self.config.height as u64,
+      usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+      mapped_at_creation: false,
     });
 
-    let surface = instance.create_surface(Arc::clone(&window)).unwrap();
-    let adapter = instance.request_adapter(&wgpu::RequestAdapterOptions {
-      power_preference: wgpu::PowerPreference::default(),
-      force_fallback_adapter: false,
-      // Request an adapter which can render to our surface
-      compatible_surface: Some(&surface),
-    })
-    .await
-    .expect("Failed to find an appropriate adapter");
-
-    let (device, queue) = adapter.request_device(
-      &wgpu::DeviceDescriptor {
-        label: Some("Device Setup"),
-        memory_hints: wgpu::MemoryHints::default(),
-        required_features: wgpu::Features::empty(),
-        // WebGL doesn't support all of wgpu's features, so if
-        // we're building for the web, we'll have to disable some.
-        // Make sure we use the texture resolution limits from the adapter, so we can support images the size of the swapchain.
-        required_limits: if cfg!(target_arch = "wasm32") {
-          wgpu::Limits::downlevel_webgl2_defaults()
-        } else {
-          wgpu::Limits::default() //:downlevel_webgl2_defaults()
+    let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+      label: Some("Read Pixels Encoder"),
+    });
+    encoder.copy_texture_to_buffer(
+      texture.as_image_copy(),
+      wgpu::ImageCopyBuffer {
+        buffer: &buffer,
+        layout: wgpu::ImageDataLayout {
+          offset: 0,
+          // copies need rows padded to 256 bytes
+          bytes_per_row: Some(padded_row_bytes),
+          rows_per_image: Some(self.config.height),
         },
       },
-      None,
-    ).await.unwrap();
+      texture.size(),
+    );
+    self.queue.submit(std::iter::once(encoder.finish()));
 
-    let surface_caps = surface.get_capabilities(&adapter);
-    // Shader code in this tutorial assumes an sRGB surface texture. Using a different
-    // one will result in all the colors coming out darker. If you want to support non
-    // sRGB surfaces, you'll need to account for that when drawing to the frame.
-    let surface_format = surface_caps.formats.iter()
-    .find(|f| f.is_srgb())
-    .copied()
-    .unwrap_or(surface_caps.formats[0]);
+    let slice = buffer.slice(..);
+    let (sender, receiver) = std::sync::mpsc::channel();
+    slice.map_async(wgpu::MapMode::Read, move |result| {
+      let _ = sender.send(result);
+    });
+    self.device.poll(wgpu::Maintain::Wait);
+    receiver.recv().context("Map callback was dropped")?.context("Failed to map the pixel buffer")?;
 
-    let config = wgpu::SurfaceConfiguration {
-      usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-      format: surface_format,
-      width: size.width,
-      height: size.height,
-      present_mode: wgpu::PresentMode::Fifo, // surface_caps.present_modes[0],
-      alpha_mode: surface_caps.alpha_modes[0],
-      view_formats: vec![],
-      desired_maximum_frame_latency: 2,
-    };
+    let data = slice.get_mapped_range();
+    let pixels = data
+      .chunks(padded_row_bytes as usize)
+      .flat_map(|row| &row[..row_bytes as usize])
+      .copied()
+      .collect();
+    drop(data);
+    buffer.unmap();
+    Ok(pixels)
+  }
 
-    let render_pipeline = State::render_pipeline(&device, &config);
-    let vertex_buffer = State::new_vertex_buffer(&device);
+  // draw
+  pub fn render(&mut self) -> Result<(), RenderError> {
+    self.render_with(|_, _, _, _| {})
+  }
 
-    let index_buffer = State::new_index_buffer(&device);
-    let num_vertices = POLYGON_INDICES.len() as u32;
-    // let num_vertices: u32 = VERTICES.len() as u32;
+  // Like render(), but `overlay` can record its own passes into the frame
+  // after the scene, before anything is submitted. Overlay passes should use
+  // LoadOp::Load to keep the scene, and target render_format() with a single
+  // sample. Frames saved by capture_frame() don't include the overlay.
+  pub fn render_with(
+    &mut self,
+    overlay: impl FnOnce(&wgpu::Device, &wgpu::Queue, &mut wgpu::CommandEncoder, &wgpu::TextureView),
+  ) -> Result<(), RenderError> {
+    #[cfg(feature = "hot-reload")]
+    self.reload_changed_shader();
 
-    Self {
-      surface,
-      device,
-      queue,
-      config,
-      size,
-      render_pipeline,
-      vertex_buffer,
-      index_buffer,
-      num_vertices,
+    // nothing is presented while suspended or minimized, so the gap before
+    // the next present isn't a hitch
+    if self.is_suspended() {
+      self.frame_pacing.pause();
+      return Err(RenderError::Suspended);
     }
-  }
 
-  pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
-    if new_size.width > 0 && new_size.height > 0 {
-      self.size = new_size;
-      self.config.width = new_size.width;
-      self.config.height = new_size.height;
-      self.surface.configure(&self.device, &self.config);
+    if self.is_minimized() {
+      self.frame_pacing.pause();
+      return Ok(());
     }
-  }
 
-  // draw
-  pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
-    let output = self.surface.get_current_texture().expect("Failed to acquire texture");
+    if let Some(mode) = self.pending_present_mode.take() {
+      if mode != self.config.present_mode {
+        self.config.present_mode = mode;
+        self.needs_reconfigure = true;
+      }
+    }
+    if self.needs_reconfigure {
+      self.reconfigure();
+    }
+
+    let mut dump = std::mem::take(&mut self.capture_frame_dump).then(FrameDump::default);
+
+    let view_proj: [[f32; 4]; 4] = self.camera.build_view_projection_matrix().into();
+    if view_proj != self.uniforms.view_proj {
+      self.write_uniforms();
+    }
+    self.upload_debug_lines();
+    self.upload_instances();
+    // includes what set_vertices() and friends wrote since the last frame
+    for (label, bytes) in self.pending_uploads.drain(..) {
+      if let Some(dump) = dump.as_mut() {
+        dump.record_upload(label, bytes);
+      }
+    }
 
-    // create texture_view with default settings
-    let texture_view = output.texture.create_view(&wgpu::TextureViewDescriptor::default());
+    // headless frames go straight into the offscreen texture
+    let (output, texture_view) = match &self.target {
+      RenderTarget::Surface(surface) => {
+        let output = surface.get_current_texture()?;
+        // create texture_view with default settings
+        let texture_view = output.texture.create_view(&wgpu::TextureViewDescriptor {
+          format: Some(self.render_format()),
+          ..Default::default()
+        });
+        (Some(output), texture_view)
+      }
+      RenderTarget::Offscreen(texture) => (None, texture.create_view(&wgpu::TextureViewDescriptor {
+        format: Some(self.render_format()),
+        ..Default::default()
+      })),
+      RenderTarget::Suspended => unreachable!("Suspended states return before rendering"),
+    };
+
+    self.select_lods();
+    let encode_start = Instant::now();
+    let kind = self.mesh_kind.pipeline();
+    let desc = self.render_mode.desc();
+    // objects always use the color pipeline, with their own depth bias
+    let object_pipelines = self.objects.iter().map(|object| (PipelineKind::Color, desc.with_depth_bias(object.depth_bias)));
+    let pipelines: Vec<_> = std::iter::once((kind, desc.with_depth_bias(self.depth_bias))).chain(object_pipelines).collect();
+    for (kind, desc) in pipelines {
+      if !self.pipelines.contains(kind, desc) {
+        let pipeline = self.create_pipeline(kind, desc);
+        self.pipelines.insert(kind, desc, pipeline);
+      }
+    }
+    // debug lines keep their line list whatever the render mode
+    if self.has_debug_lines() && !self.pipelines.contains(PipelineKind::Debug, debug_draw::LINE_PIPELINE) {
+      let pipeline = self.create_pipeline(PipelineKind::Debug, debug_draw::LINE_PIPELINE);
+      self.pipelines.insert(PipelineKind::Debug, debug_draw::LINE_PIPELINE, pipeline);
+    }
+    if self.skybox.is_some() && !self.pipelines.contains(PipelineKind::Skybox, skybox::SKYBOX_PIPELINE) {
+      let pipeline = self.create_pipeline(PipelineKind::Skybox, skybox::SKYBOX_PIPELINE);
+      self.pipelines.insert(PipelineKind::Skybox, skybox::SKYBOX_PIPELINE, pipeline);
+    }
 
     // create command encoder for commands sent to wgpu
     let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
       label: Some("Render Encoder"),
     });
 
-    {
-      let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-        label: Some("Render Pass"),
-        color_attachments: &[
-          // This is what @location(0) in the fragment shader targets
-          Some(wgpu::RenderPassColorAttachment {
-            view: &texture_view,
-            resolve_target: None,
-            ops: wgpu::Operations {
-              load: wgpu::LoadOp::Clear(wgpu::Color {
-                r: 0.1,
-                g: 0.2,
-                b: 0.3,
-                a: 1.0,
-              }),
-              store: wgpu::StoreOp::Store,
-            },
-          })
-        ],
-        depth_stencil_attachment: None,
-        occlusion_query_set: None,
-        timestamp_writes: None,
+    let timestamp_writes = self.gpu_timer.as_ref().and_then(|timer| timer.timestamp_writes());
+    let timed = timestamp_writes.is_some();
+    self.encode_render_pass(&mut encoder, &texture_view, kind, desc, "Render Pass", timestamp_writes);
+    if let (true, Some(timer)) = (timed, self.gpu_timer.as_mut()) {
+      timer.encode_resolve(&mut encoder);
+    }
+    overlay(&self.device, &self.queue, &mut encoder, &texture_view);
+    if let Some(dump) = dump.as_mut() {
+      // mirrors the filters, pipeline changes and order in encode_render_pass
+      let pass = dump.begin_pass("Render Pass");
+      let skybox = self.skybox.is_some().then(|| DrawDump {
+        mesh: "Skybox".to_string(),
+        pipeline: PipelineKind::Skybox.label().to_string(),
+        vertex_count: 3,
+        index_count: None,
+        instance_count: 1,
+        topology: skybox::SKYBOX_PIPELINE.topology,
       });
+      let (skybox_first, skybox_last) = if self.options.depth { (None, skybox) } else { (skybox, None) };
+      if let Some(draw) = skybox_first {
+        pass.pipeline_switches += 1;
+        pass.bind_group_switches += 2;
+        pass.draws.push(draw);
+      }
+      if self.num_vertices > 0 {
+        pass.pipeline_switches += 1;
+        pass.bind_group_switches += self.mesh_kind.bind_groups();
+        pass.draws.push(DrawDump {
+          mesh: "Vertex Buffer".to_string(),
+          pipeline: self.mesh_kind.pipeline().label().to_string(),
+          vertex_count: self.num_vertices,
+          index_count: self.num_indices,
+          instance_count: self.num_instances(),
+          topology: desc.topology,
+        });
+      }
+      let mut object_bias = None;
+      for object in self.objects.iter() {
+        let Some(mesh) = object.mesh().filter(|mesh| mesh.num_vertices > 0) else { continue };
+        if object_bias != Some(object.depth_bias) {
+          pass.pipeline_switches += 1;
+          object_bias = Some(object.depth_bias);
+        }
+        pass.bind_group_switches += 1;
+        pass.draws.push(DrawDump {
+          mesh: "Object Vertex Buffer".to_string(),
+          pipeline: PipelineKind::Color.label().to_string(),
+          vertex_count: mesh.num_vertices,
+          index_count: mesh.indices.as_ref().map(|(_, _, count)| *count),
+          instance_count: 1,
+          topology: desc.topology,
+        });
+      }
+      if let Some(draw) = skybox_last {
+        pass.pipeline_switches += 1;
+        pass.bind_group_switches += 2;
+        pass.draws.push(draw);
+      }
+      if let Some(lines) = self.debug_lines.as_ref().filter(|lines| lines.num_vertices > 0) {
+        pass.pipeline_switches += 1;
+        pass.bind_group_switches += 1;
+        pass.draws.push(DrawDump {
+          mesh: "Debug Vertex Buffer".to_string(),
+          pipeline: PipelineKind::Debug.label().to_string(),
+          vertex_count: lines.num_vertices,
+          index_count: None,
+          instance_count: 1,
+          topology: debug_draw::LINE_PIPELINE.topology,
+        });
+      }
+    }
 
-      render_pass.set_pipeline(&self.render_pipeline);
-      render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-      render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
-      // render_pass.draw(0..self.num_vertices, 0..1);
-      render_pass.draw_indexed(0..self.num_vertices, 0, 0..1);
+    // a capture still being read back is dropped in favour of the new one
+    let mut capture = self.capture_request.take().and_then(|path| {
+      capture::FrameCapture::new(&self.device, &self.config, self.render_format(), &path, &self.map_tracker)
+        .map_err(|err| self.capture_result = Some(Err(err)))
+        .ok()
+    });
+    if let Some(capture) = capture.as_mut() {
+      self.encode_render_pass(&mut encoder, &capture.view, kind, desc, "Capture Pass", None);
+      capture.encode_copy(&mut encoder);
     }
 
+    let command_buffer = encoder.finish();
+    let cpu_time = encode_start.elapsed();
+
     // submit will accept anything that implements IntoIter
-    self.queue.submit(std::iter::once(encoder.finish()));
-    output.present();
+    let submit_start = Instant::now();
+    self.queue.submit(std::iter::once(command_buffer));
+    if let Some(output) = output {
+      output.present();
+    }
+    self.frame_timings.record(cpu_time, submit_start.elapsed());
+    if let (true, Some(timer)) = (timed, self.gpu_timer.as_mut()) {
+      timer.map();
+    }
+    if let Some(mut capture) = capture {
+      capture.map();
+      self.frame_capture = Some(capture);
+    }
+    self.frame_pacing.record_present(dump.as_ref());
+    // lets map callbacks of readbacks submitted earlier run
+    self.device.poll(wgpu::Maintain::Poll);
+
+    self.finish_capture();
+    self.finish_gpu_timing();
+
+    if dump.is_some() {
+      self.frame_dump = dump;
+    }
 
     Ok(())
   }
 
-  fn render_pipeline(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> wgpu::RenderPipeline {
-    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-      label: Some("Shader"),
-      source: wgpu::ShaderSource::Wgsl(include_str!("shader.wgsl").into()),
+  fn encode_render_pass(
+    &self,
+    encoder: &mut wgpu::CommandEncoder,
+    view: &wgpu::TextureView,
+    kind: PipelineKind,
+    desc: PipelineDesc,
+    label: &str,
+    timestamp_writes: Option<wgpu::RenderPassTimestampWrites<'_>>,
+  ) {
+    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+      label: Some(label),
+      color_attachments: &[
+        // This is what @location(0) in the fragment shader targets
+        // with MSAA we draw into the multisampled target and resolve into the frame
+        Some(wgpu::RenderPassColorAttachment {
+          view: self.msaa_target.as_ref().unwrap_or(view),
+          resolve_target: self.msaa_target.as_ref().map(|_| view),
+          ops: wgpu::Operations {
+            load: wgpu::LoadOp::Clear(self.clear_color()),
+            store: wgpu::StoreOp::Store,
+          },
+        })
+      ],
+      depth_stencil_attachment: self.depth_texture.as_ref().map(|depth| wgpu::RenderPassDepthStencilAttachment {
+        view: &depth.view,
+        depth_ops: Some(wgpu::Operations {
+          load: wgpu::LoadOp::Clear(1.0),
+          store: wgpu::StoreOp::Store,
+        }),
+        stencil_ops: None,
+      }),
+      occlusion_query_set: None,
+      timestamp_writes,
     });
 
+    // without depth testing nothing would cover it afterwards
+    if !self.options.depth {
+      self.draw_skybox(&mut render_pass);
+    }
+
+    // without vertices the pass still clears the frame
+    if self.num_vertices > 0 {
+      let desc = desc.with_depth_bias(self.depth_bias);
+      render_pass.set_pipeline(self.pipelines.get(kind, desc).expect("Pipeline was created before encoding"));
+      match self.mesh_kind {
+        MeshKind::Color => {}
+        MeshKind::Textured(texture) => render_pass.set_bind_group(1, &self.textures[texture.0].1, &[]),
+        MeshKind::Lit => {
+          let material = self.material_bind_group.as_ref().expect("set_lit_mesh() creates the material");
+          render_pass.set_bind_group(1, material, &[]);
+          render_pass.set_bind_group(2, &self.environment_bind_group, &[]);
+        }
+      }
+      self.push_constants.apply(&mut render_pass, self.mesh_kind.bind_groups());
+      render_pass.set_bind_group(0, &self.uniform_bind_group, &[]);
+      render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+      render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
+      match self.num_indices {
+        Some(num_indices) => {
+          render_pass.set_index_buffer(self.index_buffer.slice(..), self.index_format);
+          render_pass.draw_indexed(0..num_indices, 0, 0..self.num_instances());
+        }
+        None => render_pass.draw(0..self.num_vertices, 0..self.num_instances()),
+      }
+    }
+
+    if !self.objects.is_empty() {
+      render_pass.set_vertex_buffer(1, self.identity_instance.slice(..));
+      // the pipeline only changes between objects with different biases
+      let mut object_bias = None;
+      for object in self.objects.iter() {
+        let Some(mesh) = object.mesh().filter(|mesh| mesh.num_vertices > 0) else { continue };
+        if object_bias != Some(object.depth_bias) {
+          let desc = desc.with_depth_bias(object.depth_bias);
+          render_pass.set_pipeline(self.pipelines.get(PipelineKind::Color, desc).expect("Pipeline was created before encoding"));
+          self.push_constants.apply(&mut render_pass, 1);
+          object_bias = Some(object.depth_bias);
+        }
+        render_pass.set_bind_group(0, &object.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+        match &mesh.indices {
+          Some((buffer, format, count)) => {
+            render_pass.set_index_buffer(buffer.slice(..), *format);
+            render_pass.draw_indexed(0..*count, 0, 0..1);
+          }
+          None => render_pass.draw(0..mesh.num_vertices, 0..1),
+        }
+      }
+    }
+
+    // after the scene, so only the pixels it left at the far plane are shaded
+    if self.options.depth {
+      self.draw_skybox(&mut render_pass);
+    }
+
+    // last, so lines are depth-tested against everything else
+    if let Some(lines) = self.debug_lines.as_ref().filter(|lines| lines.num_vertices > 0) {
+      let pipeline = self.pipelines.get(PipelineKind::Debug, debug_draw::LINE_PIPELINE);
+      render_pass.set_pipeline(pipeline.expect("Pipeline was created before encoding"));
+      self.push_constants.apply(&mut render_pass, 1);
+      render_pass.set_bind_group(0, &lines.bind_group, &[]);
+      render_pass.set_vertex_buffer(0, lines.vertex_buffer.slice(..));
+      render_pass.set_vertex_buffer(1, self.identity_instance.slice(..));
+      render_pass.draw(0..lines.num_vertices, 0..1);
+    }
+  }
+
+  fn draw_skybox(&self, render_pass: &mut wgpu::RenderPass<'_>) {
+    let Some(skybox) = &self.skybox else { return };
+    let pipeline = self.pipelines.get(PipelineKind::Skybox, skybox::SKYBOX_PIPELINE);
+    render_pass.set_pipeline(pipeline.expect("Pipeline was created before encoding"));
+    self.push_constants.apply(render_pass, 2);
+    render_pass.set_bind_group(0, &skybox.uniform_bind_group, &[]);
+    render_pass.set_bind_group(1, &skybox.environment_bind_group, &[]);
+    render_pass.draw(0..3, 0..1);
+  }
+
+  fn has_debug_lines(&self) -> bool {
+    self.debug_lines.as_ref().is_some_and(|lines| lines.num_vertices > 0)
+  }
+
+  // the clear color is linear like everything else, but isn't run through the shaders
+  fn clear_color(&self) -> wgpu::Color {
+    let color = self.options.clear_color;
+    if !needs_srgb_encode(self.render_format()) {
+      return color;
+    }
+    wgpu::Color {
+      r: linear_to_srgb(color.r),
+      g: linear_to_srgb(color.g),
+      b: linear_to_srgb(color.b),
+      a: color.a,
+    }
+  }
+
+  fn uniform_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+      label: Some("Uniform Bind Group Layout"),
+      entries: &[wgpu::BindGroupLayoutEntry {
+        binding: 0,
+        visibility: wgpu::ShaderStages::VERTEX,
+        ty: wgpu::BindingType::Buffer {
+          ty: wgpu::BufferBindingType::Uniform,
+          has_dynamic_offset: false,
+          min_binding_size: None,
+        },
+        count: None,
+      }],
+    })
+  }
+
+  fn create_pipeline(&self, kind: PipelineKind, desc: PipelineDesc) -> wgpu::RenderPipeline {
+    match kind {
+      PipelineKind::Color => self.render_pipeline(
+        desc,
+        kind.label(),
+        wgpu::ShaderModuleDescriptor {
+          label: Some("Shader"),
+          source: self.shader_source(&self.shader_source, 1),
+        },
+        Some(Vertex::desc()),
+        &[&self.uniform_bind_group_layout],
+        wgpu::CompareFunction::Less,
+      ),
+      PipelineKind::Textured => self.render_pipeline(
+        desc,
+        kind.label(),
+        wgpu::ShaderModuleDescriptor {
+          label: Some("Textured Shader"),
+          source: self.shader_source(include_str!("textured.wgsl"), 2),
+        },
+        Some(TexturedVertex::desc()),
+        &[&self.uniform_bind_group_layout, &self.texture_bind_group_layout],
+        wgpu::CompareFunction::Less,
+      ),
+      PipelineKind::Lit => self.render_pipeline(
+        desc,
+        kind.label(),
+        wgpu::ShaderModuleDescriptor {
+          label: Some("Lit Shader"),
+          source: self.shader_source(include_str!("lit.wgsl"), 3),
+        },
+        Some(LitVertex::desc()),
+        &[
+          &self.uniform_bind_group_layout,
+          &self.material_bind_group_layout,
+          &self.environment_bind_group_layout,
+        ],
+        wgpu::CompareFunction::Less,
+      ),
+      PipelineKind::Debug => self.render_pipeline(
+        desc,
+        kind.label(),
+        wgpu::ShaderModuleDescriptor {
+          label: Some("Debug Shader"),
+          source: self.shader_source(include_str!("debug.wgsl"), 1),
+        },
+        Some(Vertex::desc()),
+        &[&self.uniform_bind_group_layout],
+        // lines traced along the scene's own edges and faces have the same
+        // depth, so those pass too
+        wgpu::CompareFunction::LessEqual,
+      ),
+      PipelineKind::Skybox => self.render_pipeline(
+        desc,
+        kind.label(),
+        wgpu::ShaderModuleDescriptor {
+          label: Some("Skybox Shader"),
+          source: self.shader_source(include_str!("skybox.wgsl"), 2),
+        },
+        // the triangle comes from the vertex index
+        None,
+        &[&self.uniform_bind_group_layout, &self.environment_bind_group_layout],
+        // drawn at the far plane, which the cleared depth buffer holds
+        wgpu::CompareFunction::LessEqual,
+      ),
+    }
+  }
+
+  // prelude.wgsl first, then the push constant fallback applied to the whole
+  // source; `group` as in PushConstants::prepare_source()
+  fn shader_source(&self, source: &str, group: u32) -> wgpu::ShaderSource<'static> {
+    let source = with_prelude(source);
+    wgpu::ShaderSource::Wgsl(self.push_constants.prepare_source(&source, group).into_owned().into())
+  }
+
+  fn render_pipeline(
+    &self,
+    desc: PipelineDesc,
+    label: &str,
+    shader: wgpu::ShaderModuleDescriptor,
+    // with the instance layout after it, None for shaders without vertex buffers
+    vertex_layout: Option<wgpu::VertexBufferLayout>,
+    bind_group_layouts: &[&wgpu::BindGroupLayout],
+    depth_compare: wgpu::CompareFunction,
+  ) -> wgpu::RenderPipeline {
+    let device = &self.device;
+    let shader = device.create_shader_module(shader);
+    // shaders without the override just ignore it
+    let encode = if needs_srgb_encode(self.render_format()) { 1.0 } else { 0.0 };
+    let constants = HashMap::from([("OUTPUT_SRGB_ENCODE".to_string(), encode)]);
+
+    // the push constant fallback takes the group after the pipeline's own
+    let bind_group_layouts: Vec<_> = bind_group_layouts.iter().copied()
+      .chain(self.push_constants.bind_group_layout())
+      .collect();
     let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-      label: Some("Render Pipeline Layout"),
-      bind_group_layouts: &[],
-      push_constant_ranges: &[],
+      label: Some(&format!("{} Layout", label)),
+      bind_group_layouts: &bind_group_layouts,
+      push_constant_ranges: &self.push_constants.ranges(),
     });
 
-    let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-      label: Some("Render Pipeline"),
+    let buffers = match vertex_layout {
+      Some(vertex_layout) => vec![vertex_layout, InstanceRaw::desc()],
+      None => Vec::new(),
+    };
+    let start = Instant::now();
+    let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+      label: Some(label),
       layout: Some(&render_pipeline_layout),
       vertex: wgpu::VertexState {
         module: &shader,
         entry_point: "vs_main",
         compilation_options: wgpu::PipelineCompilationOptions::default(),
-        buffers: &[Vertex::desc(),]
+        buffers: &buffers,
       },
       fragment: Some(wgpu::FragmentState {
         module: &shader,
         entry_point: "fs_main",
-        compilation_options: wgpu::PipelineCompilationOptions::default(),
+        compilation_options: wgpu::PipelineCompilationOptions {
+          constants: &constants,
+          ..Default::default()
+        },
         targets: &[Some(wgpu::ColorTargetState {
-          format: config.format,
+          format: self.render_format(),
           blend: Some(wgpu::BlendState::REPLACE),
           write_mask: wgpu::ColorWrites::ALL,
         })],
@@ -224,53 +2265,190 @@ impl<'window> State<'window> {
       // field describes how to interpret our vertices when converting them into triangles.
       primitive: wgpu::PrimitiveState {
         // means that every three vertices will correspond to one triangle
-        topology: wgpu::PrimitiveTopology::TriangleList,
+        topology: desc.topology,
         strip_index_format: None,
         // fields tell wgpu how to determine whether a given triangle is facing forward or not
         front_face: wgpu::FrontFace::Ccw, // triangle facing forward
-        cull_mode: Some(wgpu::Face::Back), // Triangles that are not considered facing forward are culled (not included in the render)
+        cull_mode: desc.cull_mode, // Triangles that are not considered facing forward are culled (not included in the render)
         // Requires Features::DEPTH_CLIP_CONTROL
         unclipped_depth: false,
         // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
-        polygon_mode: wgpu::PolygonMode::Fill,
+        polygon_mode: desc.polygon_mode,
         // Requires Features::CONSERVATIVE_RASTERIZATION
         conservative: false
       },
-      depth_stencil: None,
+      depth_stencil: self.options.depth.then(|| wgpu::DepthStencilState {
+        format: texture::DEPTH_FORMAT,
+        depth_write_enabled: true,
+        // Less for the scene: a fragment is only drawn when nothing closer
+        // or at the same depth is already there
+        depth_compare,
+        stencil: wgpu::StencilState::default(),
+        bias: desc.depth_bias.state(),
+      }),
       multisample: wgpu::MultisampleState {
-        count: 1, // determines how many samples the pipeline will use (multisampling)
+        count: self.options.msaa_samples, // determines how many samples the pipeline will use (multisampling)
         mask: !0, // specifies which samples should be active. In this case, we are using all of them
         alpha_to_coverage_enabled: false // anti-aliasing
       },
       multiview: None, // indicates how many array layers the render attachments can have
-      cache: None, // allows wgpu to cache shader compilation data. Only really useful for Android build targets.
+      // compiled pipelines from earlier runs, see StateOptions::pipeline_cache_dir
+      cache: self.pipeline_cache.as_ref().map(|file| file.cache()),
     });
-
-    return render_pipeline;
+    let cache = match &self.pipeline_cache {
+      Some(file) if file.loaded => "loaded from disk",
+      Some(_) => "empty",
+      None => "off",
+    };
+    log::info!("Created {} {:?} in {:?} (pipeline cache {})", label, desc, start.elapsed(), cache);
+    pipeline
   }
 
   fn new_vertex_buffer(device: &wgpu::Device) -> wgpu::Buffer {
-    let vertex_buffer = device.create_buffer_init(
+    device.create_buffer_init(
       &wgpu::util::BufferInitDescriptor {
         label: Some("Vertex Buffer"),
         contents: bytemuck::cast_slice(POLYGON_VERTICES),
         // contents: bytemuck::cast_slice(VERTICES),
-        usage: wgpu::BufferUsages::VERTEX,
+        // COPY_DST so set_vertices() can overwrite it in place
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
       }
-    );
-    return vertex_buffer;
+    )
   }
 
   fn new_index_buffer(device: &wgpu::Device) -> wgpu::Buffer {
     device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
       label: Some("Index Buffer"),
       contents: bytemuck::cast_slice(POLYGON_INDICES),
-      usage: wgpu::BufferUsages::INDEX,
+      usage: wgpu::BufferUsages::INDEX | wgpu::BufferUsages::COPY_DST,
     })
   }
+
+  // Overwrites the buffer in place when the data fits, otherwise replaces it
+  // with one at least 1.5x the size. Buffers never shrink. Data over
+  // max_buffer_size fails before anything is touched.
+  fn write_or_grow(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    buffer: &mut wgpu::Buffer,
+    label: &'static str,
+    usage: wgpu::BufferUsages,
+    contents: &[u8],
+  ) -> Result<(), ExceedsLimit> {
+    // write_buffer() wants whole words, u16 indices can leave half of one
+    let aligned_len = contents.len().next_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT as usize);
+    let padded;
+    let contents = if aligned_len != contents.len() {
+      let mut bytes = contents.to_vec();
+      bytes.resize(aligned_len, 0);
+      padded = bytes;
+      &padded[..]
+    } else {
+      contents
+    };
+
+    let size = contents.len() as wgpu::BufferAddress;
+    let device_limits = device.limits();
+    limits::check_buffer_size(&device_limits, label, size)?;
+    if size > buffer.size() {
+      // headroom, so data that grows a little every frame doesn't reallocate every frame
+      let grown = (buffer.size() + buffer.size() / 2).min(device_limits.max_buffer_size);
+      *buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some(label),
+        size: size.max(grown).next_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT),
+        usage: usage | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+      });
+    }
+    queue.write_buffer(buffer, 0, contents);
+    Ok(())
+  }
+}
+
+impl Drop for State {
+  fn drop(&mut self) {
+    if let Err(err) = self.save_pipeline_cache() {
+      log::warn!("Failed to save the pipeline cache: {}", err);
+    }
+  }
+}
+
+// Index data for set_indexed_mesh(), 16 bit indices halve the buffer size
+// but can only address the first 65536 vertices.
+#[derive(Copy, Clone, Debug)]
+pub enum Indices<'a> {
+  U16(&'a [u16]),
+  U32(&'a [u32]),
+}
+
+impl Indices<'_> {
+  pub fn len(&self) -> usize {
+    match self {
+      Indices::U16(indices) => indices.len(),
+      Indices::U32(indices) => indices.len(),
+    }
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  pub fn format(&self) -> wgpu::IndexFormat {
+    match self {
+      Indices::U16(_) => wgpu::IndexFormat::Uint16,
+      Indices::U32(_) => wgpu::IndexFormat::Uint32,
+    }
+  }
+
+  fn as_bytes(&self) -> &[u8] {
+    match self {
+      Indices::U16(indices) => bytemuck::cast_slice(indices),
+      Indices::U32(indices) => bytemuck::cast_slice(indices),
+    }
+  }
+}
+
+impl<'a> From<&'a [u16]> for Indices<'a> {
+  fn from(indices: &'a [u16]) -> Self {
+    Indices::U16(indices)
+  }
+}
+
+impl<'a> From<&'a [u32]> for Indices<'a> {
+  fn from(indices: &'a [u32]) -> Self {
+    Indices::U32(indices)
+  }
+}
+
+impl<'a, const N: usize> From<&'a [u16; N]> for Indices<'a> {
+  fn from(indices: &'a [u16; N]) -> Self {
+    Indices::U16(indices)
+  }
+}
+
+impl<'a, const N: usize> From<&'a [u32; N]> for Indices<'a> {
+  fn from(indices: &'a [u32; N]) -> Self {
+    Indices::U32(indices)
+  }
+}
+
+impl<'a> From<&'a Vec<u16>> for Indices<'a> {
+  fn from(indices: &'a Vec<u16>) -> Self {
+    Indices::U16(indices)
+  }
+}
+
+impl<'a> From<&'a Vec<u32>> for Indices<'a> {
+  fn from(indices: &'a Vec<u32>) -> Self {
+    Indices::U32(indices)
+  }
 }
 
 impl Vertex {
+  pub fn new(position: [f32; 3], color: [f32; 3]) -> Self {
+    Self { position, color }
+  }
+
   fn desc() -> wgpu::VertexBufferLayout<'static> {
     wgpu::VertexBufferLayout {
       array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
@@ -300,4 +2478,64 @@ impl Vertex {
   //     attributes: &Self::ATTRIBS,
   //   }
   // }
-}
\ No newline at end of file
+}
+
+impl LitVertex {
+  pub fn new(position: [f32; 3], normal: [f32; 3], tex_coords: [f32; 2], tangent: [f32; 4]) -> Self {
+    Self { position, normal, tex_coords, tangent }
+  }
+
+  fn desc() -> wgpu::VertexBufferLayout<'static> {
+    wgpu::VertexBufferLayout {
+      array_stride: std::mem::size_of::<LitVertex>() as wgpu::BufferAddress,
+      step_mode: wgpu::VertexStepMode::Vertex,
+      attributes: &[
+        wgpu::VertexAttribute {
+          offset: 0,
+          shader_location: 0,
+          format: wgpu::VertexFormat::Float32x3,
+        },
+        wgpu::VertexAttribute {
+          offset: std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+          shader_location: 1,
+          format: wgpu::VertexFormat::Float32x3,
+        },
+        wgpu::VertexAttribute {
+          offset: std::mem::size_of::<[f32; 6]>() as wgpu::BufferAddress,
+          shader_location: 2,
+          format: wgpu::VertexFormat::Float32x2,
+        },
+        wgpu::VertexAttribute {
+          offset: std::mem::size_of::<[f32; 8]>() as wgpu::BufferAddress,
+          shader_location: 3,
+          format: wgpu::VertexFormat::Float32x4,
+        }
+      ]
+    }
+  }
+}
+
+impl TexturedVertex {
+  pub fn new(position: [f32; 3], tex_coords: [f32; 2]) -> Self {
+    Self { position, tex_coords }
+  }
+
+  fn desc() -> wgpu::VertexBufferLayout<'static> {
+    wgpu::VertexBufferLayout {
+      array_stride: std::mem::size_of::<TexturedVertex>() as wgpu::BufferAddress,
+      step_mode: wgpu::VertexStepMode::Vertex,
+      attributes: &[
+        wgpu::VertexAttribute {
+          offset: 0,
+          shader_location: 0,
+          format: wgpu::VertexFormat::Float32x3,
+        },
+        wgpu::VertexAttribute {
+          offset: std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+          shader_location: 1,
+          format: wgpu::VertexFormat::Float32x2,
+        }
+      ]
+    }
+  }
+}
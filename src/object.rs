@@ -0,0 +1,107 @@
+use crate::lod::LodGroup;
+use crate::DepthBias;
+
+// handle returned by State::create_object(). Ids of removed objects never
+// match a later object, even when it reuses the slot.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ObjectId {
+  index: u32,
+  generation: u32,
+}
+
+// the buffers of one of an object's meshes
+pub(crate) struct ObjectMesh {
+  pub(crate) vertex_buffer: wgpu::Buffer,
+  pub(crate) num_vertices: u32,
+  // None draws the vertices as a plain triangle list
+  pub(crate) indices: Option<(wgpu::Buffer, wgpu::IndexFormat, u32)>,
+}
+
+// A mesh with its own buffers and transform, drawn after the main mesh
+pub(crate) struct DrawObject {
+  // a single level without a switch distance unless created from a LodGroup
+  pub(crate) lod: LodGroup<ObjectMesh>,
+  // picked by the last render(), None beyond the last switch distance
+  pub(crate) level: Option<usize>,
+  pub(crate) transform: [[f32; 4]; 4],
+  pub(crate) depth_bias: DepthBias,
+  // Uniforms with the object's transform, bound at @group(0)
+  pub(crate) uniform_buffer: wgpu::Buffer,
+  pub(crate) bind_group: wgpu::BindGroup,
+}
+
+impl DrawObject {
+  pub(crate) fn mesh(&self) -> Option<&ObjectMesh> {
+    self.level.map(|level| self.lod.mesh(level))
+  }
+}
+
+struct Slot {
+  generation: u32,
+  object: Option<DrawObject>,
+}
+
+// Slot map of the objects. Freed slots are reused, so the draw order is kept
+// separately to stay in insertion order.
+#[derive(Default)]
+pub(crate) struct ObjectStore {
+  slots: Vec<Slot>,
+  free: Vec<u32>,
+  order: Vec<ObjectId>,
+}
+
+impl ObjectStore {
+  pub(crate) fn insert(&mut self, object: DrawObject) -> ObjectId {
+    let id = match self.free.pop() {
+      Some(index) => {
+        let slot = &mut self.slots[index as usize];
+        slot.object = Some(object);
+        ObjectId { index, generation: slot.generation }
+      }
+      None => {
+        self.slots.push(Slot { generation: 0, object: Some(object) });
+        ObjectId { index: self.slots.len() as u32 - 1, generation: 0 }
+      }
+    };
+    self.order.push(id);
+    id
+  }
+
+  pub(crate) fn get_mut(&mut self, id: ObjectId) -> Option<&mut DrawObject> {
+    let slot = self.slots.get_mut(id.index as usize)?;
+    if slot.generation != id.generation {
+      return None;
+    }
+    slot.object.as_mut()
+  }
+
+  pub(crate) fn remove(&mut self, id: ObjectId) -> Option<DrawObject> {
+    let slot = self.slots.get_mut(id.index as usize)?;
+    if slot.generation != id.generation {
+      return None;
+    }
+    let object = slot.object.take()?;
+    slot.generation = slot.generation.wrapping_add(1);
+    self.free.push(id.index);
+    self.order.retain(|&other| other != id);
+    Some(object)
+  }
+
+  pub(crate) fn len(&self) -> usize {
+    self.order.len()
+  }
+
+  pub(crate) fn is_empty(&self) -> bool {
+    self.order.is_empty()
+  }
+
+  // in slot order, for updates that don't depend on the draw order
+  pub(crate) fn iter_mut(&mut self) -> impl Iterator<Item = &mut DrawObject> + '_ {
+    self.slots.iter_mut().filter_map(|slot| slot.object.as_mut())
+  }
+
+  // in insertion order
+  pub(crate) fn iter(&self) -> impl Iterator<Item = &DrawObject> + '_ {
+    self.order.iter().filter_map(|id| self.slots[id.index as usize].object.as_ref())
+  }
+}
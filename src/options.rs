@@ -0,0 +1,207 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+use winit::window::Window;
+
+use crate::{State, StateError};
+
+// The features the crate uses when they're there: wireframe rendering,
+// GPU frame timing and push constants.
+pub const DEFAULT_OPTIONAL_FEATURES: wgpu::Features = wgpu::Features::POLYGON_MODE_LINE
+  .union(wgpu::Features::TIMESTAMP_QUERY)
+  .union(wgpu::Features::PUSH_CONSTANTS);
+
+// A wgpu feature to enable on the device, see StateOptions::feature(). What
+// was actually enabled is in State::enabled_features().
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FeatureRequest {
+  // State creation fails with StateError::MissingFeature without it
+  Required(wgpu::Features),
+  // enabled when the adapter has it, skipped otherwise
+  Optional(wgpu::Features),
+}
+
+impl FeatureRequest {
+  // (required, optional) of a list of requests
+  pub(crate) fn split(requests: &[FeatureRequest]) -> (wgpu::Features, wgpu::Features) {
+    requests.iter().fold((wgpu::Features::empty(), wgpu::Features::empty()), |(required, optional), request| match request {
+      FeatureRequest::Required(features) => (required | *features, optional),
+      FeatureRequest::Optional(features) => (required, optional | *features),
+    })
+  }
+}
+
+// Construction-time settings for State, see State::new_with_options().
+#[derive(Clone, Debug, PartialEq)]
+pub struct StateOptions {
+  // depth buffer and depth testing, 2D-only apps can turn it off
+  pub depth: bool,
+  // 1 disables MSAA, unsupported counts fall back to 1 with a warning
+  pub msaa_samples: u32,
+  // unsupported modes fall back to Fifo with a warning
+  pub present_mode: wgpu::PresentMode,
+  pub power_preference: wgpu::PowerPreference,
+  // restrict adapter selection to these backends, e.g. to debug a driver issue
+  pub backends: wgpu::Backends,
+  pub clear_color: wgpu::Color,
+  // starts out as DEFAULT_OPTIONAL_FEATURES; clear it to opt out of those
+  pub features: Vec<FeatureRequest>,
+  // where compiled pipelines are kept between runs, on devices that support
+  // it (Vulkan); see State::save_pipeline_cache()
+  pub pipeline_cache_dir: Option<PathBuf>,
+  // Usages the surface is configured with on top of RENDER_ATTACHMENT, e.g.
+  // COPY_SRC to copy frames out of the swapchain. State creation fails with
+  // StateError::UnsupportedSurfaceUsages if the surface can't have them.
+  // Extra usages can cost performance: some drivers turn off framebuffer
+  // compression or direct scanout for a COPY_SRC swapchain, so only ask for
+  // what gets used.
+  pub surface_usages: wgpu::TextureUsages,
+}
+
+impl Default for StateOptions {
+  fn default() -> Self {
+    Self {
+      depth: true,
+      msaa_samples: 1,
+      present_mode: wgpu::PresentMode::Fifo,
+      power_preference: wgpu::PowerPreference::default(),
+      #[cfg(not(target_arch="wasm32"))]
+      backends: wgpu::Backends::PRIMARY,
+      #[cfg(target_arch="wasm32")]
+      backends: wgpu::Backends::GL,
+      clear_color: wgpu::Color {
+        r: 0.1,
+        g: 0.2,
+        b: 0.3,
+        a: 1.0,
+      },
+      features: vec![FeatureRequest::Optional(DEFAULT_OPTIONAL_FEATURES)],
+      pipeline_cache_dir: None,
+      surface_usages: wgpu::TextureUsages::empty(),
+    }
+  }
+}
+
+impl StateOptions {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn depth(mut self, enabled: bool) -> Self {
+    self.depth = enabled;
+    self
+  }
+
+  pub fn msaa_samples(mut self, samples: u32) -> Self {
+    self.msaa_samples = samples;
+    self
+  }
+
+  pub fn present_mode(mut self, mode: wgpu::PresentMode) -> Self {
+    self.present_mode = mode;
+    self
+  }
+
+  pub fn power_preference(mut self, preference: wgpu::PowerPreference) -> Self {
+    self.power_preference = preference;
+    self
+  }
+
+  pub fn backends(mut self, backends: wgpu::Backends) -> Self {
+    self.backends = backends;
+    self
+  }
+
+  pub fn clear_color(mut self, color: wgpu::Color) -> Self {
+    self.clear_color = color;
+    self
+  }
+
+  // adds to the features requested so far
+  pub fn feature(mut self, request: FeatureRequest) -> Self {
+    self.features.push(request);
+    self
+  }
+
+  pub fn pipeline_cache_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+    self.pipeline_cache_dir = Some(dir.into());
+    self
+  }
+
+  pub fn surface_usages(mut self, usages: wgpu::TextureUsages) -> Self {
+    self.surface_usages = usages;
+    self
+  }
+}
+
+// StateBuilder::new(window).present_mode(..).power_preference(..).build()
+pub struct StateBuilder {
+  window: Arc<Window>,
+  options: StateOptions,
+}
+
+impl StateBuilder {
+  pub fn new(window: Arc<Window>) -> Self {
+    Self {
+      window,
+      options: StateOptions::default(),
+    }
+  }
+
+  pub fn options(mut self, options: StateOptions) -> Self {
+    self.options = options;
+    self
+  }
+
+  pub fn depth(mut self, enabled: bool) -> Self {
+    self.options = self.options.depth(enabled);
+    self
+  }
+
+  pub fn msaa_samples(mut self, samples: u32) -> Self {
+    self.options = self.options.msaa_samples(samples);
+    self
+  }
+
+  pub fn present_mode(mut self, mode: wgpu::PresentMode) -> Self {
+    self.options = self.options.present_mode(mode);
+    self
+  }
+
+  pub fn power_preference(mut self, preference: wgpu::PowerPreference) -> Self {
+    self.options = self.options.power_preference(preference);
+    self
+  }
+
+  pub fn backends(mut self, backends: wgpu::Backends) -> Self {
+    self.options = self.options.backends(backends);
+    self
+  }
+
+  pub fn clear_color(mut self, color: wgpu::Color) -> Self {
+    self.options = self.options.clear_color(color);
+    self
+  }
+
+  pub fn feature(mut self, request: FeatureRequest) -> Self {
+    self.options = self.options.feature(request);
+    self
+  }
+
+  pub fn pipeline_cache_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+    self.options = self.options.pipeline_cache_dir(dir);
+    self
+  }
+
+  pub fn surface_usages(mut self, usages: wgpu::TextureUsages) -> Self {
+    self.options = self.options.surface_usages(usages);
+    self
+  }
+
+  pub fn build(self) -> Result<State, StateError> {
+    State::new_with_options(self.window, self.options)
+  }
+
+  pub async fn build_async(self) -> Result<State, StateError> {
+    State::new_async_with_options(self.window, self.options).await
+  }
+}
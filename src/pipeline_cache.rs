@@ -0,0 +1,263 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+// The parts of a render pipeline that vary between render modes; everything
+// else comes from the State (surface format, depth, MSAA, shaders).
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PipelineDesc {
+  pub polygon_mode: wgpu::PolygonMode,
+  pub topology: wgpu::PrimitiveTopology,
+  pub cull_mode: Option<wgpu::Face>,
+  pub depth_bias: DepthBias,
+}
+
+impl Default for PipelineDesc {
+  fn default() -> Self {
+    Self {
+      polygon_mode: wgpu::PolygonMode::Fill,
+      topology: wgpu::PrimitiveTopology::TriangleList,
+      cull_mode: Some(wgpu::Face::Back),
+      depth_bias: DepthBias::NONE,
+    }
+  }
+}
+
+impl PipelineDesc {
+  // WebGPU only allows a bias on triangles, so points and lines keep
+  // DepthBias::NONE
+  pub fn with_depth_bias(self, depth_bias: DepthBias) -> Self {
+    let triangles = matches!(self.topology, wgpu::PrimitiveTopology::TriangleList | wgpu::PrimitiveTopology::TriangleStrip);
+    let depth_bias = if triangles { depth_bias } else { DepthBias::NONE };
+    Self { depth_bias, ..self }
+  }
+}
+
+// Offsets the depth of a pipeline's fragments, see wgpu::DepthBiasState.
+// The depth compare is Less, so a negative bias moves a draw towards the
+// camera. A nonzero clamp needs DownlevelFlags::DEPTH_BIAS_CLAMP, which GLES
+// lacks, so the presets leave it at zero.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct DepthBias {
+  pub constant: i32,
+  pub slope_scale: f32,
+  pub clamp: f32,
+}
+
+impl DepthBias {
+  pub const NONE: Self = Self { constant: 0, slope_scale: 0.0, clamp: 0.0 };
+  // a decal drawn over the surface it lies on
+  pub const DECAL: Self = Self { constant: -2, slope_scale: -1.0, clamp: 0.0 };
+  // pushes shadow map depths away from the light against shadow acne
+  pub const SHADOW_CASTER: Self = Self { constant: 2, slope_scale: 2.0, clamp: 0.0 };
+
+  pub(crate) fn state(self) -> wgpu::DepthBiasState {
+    wgpu::DepthBiasState {
+      constant: self.constant,
+      slope_scale: self.slope_scale,
+      clamp: self.clamp,
+    }
+  }
+
+  // the floats compared and hashed bit for bit, so it can key the cache
+  fn bits(self) -> (i32, u32, u32) {
+    (self.constant, self.slope_scale.to_bits(), self.clamp.to_bits())
+  }
+}
+
+impl PartialEq for DepthBias {
+  fn eq(&self, other: &Self) -> bool {
+    self.bits() == other.bits()
+  }
+}
+
+impl Eq for DepthBias {}
+
+impl Hash for DepthBias {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    self.bits().hash(state);
+  }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum RenderMode {
+  #[default]
+  Fill,
+  // triangle edges, back faces included
+  Wireframe,
+  // one point per vertex
+  Points,
+}
+
+impl RenderMode {
+  pub fn desc(self) -> PipelineDesc {
+    match self {
+      RenderMode::Fill => PipelineDesc::default(),
+      RenderMode::Wireframe => PipelineDesc {
+        polygon_mode: wgpu::PolygonMode::Line,
+        cull_mode: None,
+        ..Default::default()
+      },
+      // a point list needs no extra feature, unlike PolygonMode::Point
+      RenderMode::Points => PipelineDesc {
+        topology: wgpu::PrimitiveTopology::PointList,
+        cull_mode: None,
+        ..Default::default()
+      },
+    }
+  }
+
+  pub fn required_features(self) -> wgpu::Features {
+    match self {
+      RenderMode::Wireframe => wgpu::Features::POLYGON_MODE_LINE,
+      RenderMode::Fill | RenderMode::Points => wgpu::Features::empty(),
+    }
+  }
+}
+
+// Returned by State::set_render_mode() when the device lacks a feature the
+// mode needs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnsupportedRenderMode {
+  pub mode: RenderMode,
+  pub missing: wgpu::Features,
+}
+
+impl fmt::Display for UnsupportedRenderMode {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "Render mode {:?} requires {:?}, which isn't enabled on the device", self.mode, self.missing)
+  }
+}
+
+impl std::error::Error for UnsupportedRenderMode {}
+
+// which vertex layout and shader a pipeline is for
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum PipelineKind {
+  Color,
+  Textured,
+  // LitVertex with the irradiance cubemap
+  Lit,
+  // DebugDraw lines, always drawn with debug_draw::LINE_PIPELINE
+  Debug,
+  // the environment behind the scene, always drawn with skybox::SKYBOX_PIPELINE
+  Skybox,
+}
+
+impl PipelineKind {
+  // also what frame dumps call it
+  pub(crate) fn label(self) -> &'static str {
+    match self {
+      PipelineKind::Color => "Render Pipeline",
+      PipelineKind::Textured => "Textured Render Pipeline",
+      PipelineKind::Lit => "Lit Render Pipeline",
+      PipelineKind::Debug => "Debug Line Pipeline",
+      PipelineKind::Skybox => "Skybox Pipeline",
+    }
+  }
+}
+
+// Pipelines are created on first use and dropped wholesale when something
+// they all depend on changes (surface format, MSAA).
+#[derive(Default)]
+pub(crate) struct PipelineCache {
+  pipelines: HashMap<(PipelineKind, PipelineDesc), wgpu::RenderPipeline>,
+}
+
+impl PipelineCache {
+  pub(crate) fn get(&self, kind: PipelineKind, desc: PipelineDesc) -> Option<&wgpu::RenderPipeline> {
+    self.pipelines.get(&(kind, desc))
+  }
+
+  pub(crate) fn contains(&self, kind: PipelineKind, desc: PipelineDesc) -> bool {
+    self.pipelines.contains_key(&(kind, desc))
+  }
+
+  pub(crate) fn insert(&mut self, kind: PipelineKind, desc: PipelineDesc, pipeline: wgpu::RenderPipeline) {
+    self.pipelines.insert((kind, desc), pipeline);
+  }
+
+  // drops the pipelines of one kind, e.g. after its shader changed
+  pub(crate) fn remove_kind(&mut self, kind: PipelineKind) {
+    self.pipelines.retain(|(k, _), _| *k != kind);
+  }
+
+  pub(crate) fn clear(&mut self) {
+    self.pipelines.clear();
+  }
+}
+
+// The driver's compiled pipelines, kept in a file in
+// StateOptions::pipeline_cache_dir between runs. Only devices with
+// Features::PIPELINE_CACHE (Vulkan) have one. The file name comes from the
+// adapter and driver, so an update starts a new file. wgpu only checks the
+// header of the data, so the file also carries a checksum of the rest and
+// a damaged file is ignored and overwritten by the next save().
+pub(crate) struct PipelineCacheFile {
+  cache: wgpu::PipelineCache,
+  path: PathBuf,
+  // whether the cache started out with data from the file
+  pub(crate) loaded: bool,
+}
+
+impl PipelineCacheFile {
+  // None without the feature or when wgpu can't key the adapter
+  pub(crate) fn open(device: &wgpu::Device, adapter_info: &wgpu::AdapterInfo, dir: &Path) -> Option<Self> {
+    if !device.features().contains(wgpu::Features::PIPELINE_CACHE) {
+      return None;
+    }
+    let path = dir.join(wgpu::util::pipeline_cache_key(adapter_info)?);
+    // a missing file is just the first run
+    let data = std::fs::read(&path).ok().and_then(|file| {
+      let data = checked_data(&file);
+      if data.is_none() {
+        log::warn!("Ignoring the damaged pipeline cache {}", path.display());
+      }
+      data.map(<[u8]>::to_vec)
+    });
+
+    // Safety: the data was returned by get_data() on an earlier run, and the
+    // checksum shows it's unchanged since. wgpu checks it's for this adapter
+    // and wgpu version and falls back to an empty cache otherwise.
+    let cache = unsafe {
+      device.create_pipeline_cache(&wgpu::PipelineCacheDescriptor {
+        label: Some("Pipeline Cache"),
+        data: data.as_deref(),
+        fallback: true,
+      })
+    };
+    Some(Self {
+      cache,
+      path,
+      loaded: data.is_some(),
+    })
+  }
+
+  pub(crate) fn cache(&self) -> &wgpu::PipelineCache {
+    &self.cache
+  }
+
+  // written to a temporary file first, so a crash can't leave half a cache
+  pub(crate) fn save(&self) -> std::io::Result<()> {
+    let Some(data) = self.cache.get_data() else { return Ok(()) };
+    if let Some(dir) = self.path.parent() {
+      std::fs::create_dir_all(dir)?;
+    }
+    let mut file = fnv1a(&data).to_le_bytes().to_vec();
+    file.extend_from_slice(&data);
+    let temp = self.path.with_extension("tmp");
+    std::fs::write(&temp, file)?;
+    std::fs::rename(&temp, &self.path)
+  }
+}
+
+// the cache data of a file written by save(), None if it was changed since
+fn checked_data(file: &[u8]) -> Option<&[u8]> {
+  let (checksum, data) = file.split_first_chunk::<8>()?;
+  (u64::from_le_bytes(*checksum) == fnv1a(data)).then_some(data)
+}
+
+fn fnv1a(data: &[u8]) -> u64 {
+  data.iter().fold(0xcbf29ce484222325, |hash, &byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3))
+}
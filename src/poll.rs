@@ -0,0 +1,66 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+// Counts buffer mappings that were requested but haven't completed yet.
+// Mapped-buffer utilities register with it so State knows when the device
+// needs polling even if nothing is being rendered.
+#[derive(Clone, Debug, Default)]
+pub struct MapTracker(Arc<AtomicUsize>);
+
+impl MapTracker {
+  pub fn outstanding(&self) -> usize {
+    self.0.load(Ordering::Acquire)
+  }
+
+  pub(crate) fn begin(&self) {
+    self.0.fetch_add(1, Ordering::AcqRel);
+  }
+
+  pub(crate) fn end(&self) {
+    self.0.fetch_sub(1, Ordering::AcqRel);
+  }
+}
+
+// Background thread that keeps polling the device while mappings are
+// outstanding, for apps that stop rendering while they wait for a readback.
+#[cfg(feature = "poll-thread")]
+pub(crate) struct PollThread {
+  stop: Arc<std::sync::atomic::AtomicBool>,
+  handle: Option<std::thread::JoinHandle<()>>,
+}
+
+#[cfg(feature = "poll-thread")]
+impl PollThread {
+  pub(crate) fn spawn(device: Arc<wgpu::Device>, tracker: MapTracker) -> Self {
+    let stop = Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let thread_stop = Arc::clone(&stop);
+    let handle = std::thread::Builder::new()
+      .name("wgpu poll".to_string())
+      .spawn(move || {
+        while !thread_stop.load(Ordering::Acquire) {
+          if tracker.outstanding() > 0 {
+            // blocking is fine here, this isn't the render thread
+            device.poll(wgpu::Maintain::Wait);
+          } else {
+            std::thread::sleep(std::time::Duration::from_millis(1));
+          }
+        }
+      })
+      .expect("Failed to spawn the device poll thread");
+
+    Self {
+      stop,
+      handle: Some(handle),
+    }
+  }
+}
+
+#[cfg(feature = "poll-thread")]
+impl Drop for PollThread {
+  fn drop(&mut self) {
+    self.stop.store(true, Ordering::Release);
+    if let Some(handle) = self.handle.take() {
+      let _ = handle.join();
+    }
+  }
+}
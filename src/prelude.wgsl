@@ -0,0 +1,30 @@
+// prepended to every render shader source, including load_shader()'s
+
+// the camera and the update_uniforms() or object transform
+struct Uniforms {
+  view_proj: mat4x4<f32>,
+  transform: mat4x4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> uniforms: Uniforms;
+
+// per-instance model matrix, see InstanceRaw::desc()
+struct InstanceInput {
+  @location(5) model_matrix_0: vec4<f32>,
+  @location(6) model_matrix_1: vec4<f32>,
+  @location(7) model_matrix_2: vec4<f32>,
+  @location(8) model_matrix_3: vec4<f32>,
+};
+
+// set by the pipeline when the target has no sRGB format (WebGL)
+override OUTPUT_SRGB_ENCODE: bool = false;
+
+fn encode_output(color: vec4<f32>) -> vec4<f32> {
+  if !OUTPUT_SRGB_ENCODE {
+    return color;
+  }
+  let c = color.rgb;
+  let srgb = select(1.055 * pow(c, vec3<f32>(1.0 / 2.4)) - 0.055, c * 12.92, c <= vec3<f32>(0.0031308));
+  return vec4<f32>(srgb, color.a);
+}
@@ -0,0 +1,155 @@
+use std::borrow::Cow;
+
+use wgpu::util::DeviceExt;
+
+use crate::limits::{self, ExceedsLimit};
+
+// Bytes set_push_constants() accepts on every adapter, the smallest
+// max_push_constant_size Vulkan guarantees. Devices with a lower limit
+// report it through State::push_constant_size().
+pub const MAX_PUSH_CONSTANT_SIZE: u32 = 128;
+
+const STAGES: wgpu::ShaderStages = wgpu::ShaderStages::VERTEX_FRAGMENT;
+
+// Data for shaders that declare `var<push_constant> pc: T;`. Real push
+// constants when the device has Features::PUSH_CONSTANTS. Otherwise (WebGL)
+// a uniform buffer bound after the pipeline's other bind groups, with the
+// declaration rewritten to a uniform binding so shaders work either way.
+// That only holds for types that are valid in the uniform address space,
+// see check_uniform_layout().
+pub(crate) struct PushConstants {
+  // always the full size, unset bytes are zero
+  data: Vec<u8>,
+  fallback: Option<UniformFallback>,
+}
+
+struct UniformFallback {
+  buffer: wgpu::Buffer,
+  layout: wgpu::BindGroupLayout,
+  bind_group: wgpu::BindGroup,
+}
+
+impl PushConstants {
+  pub(crate) fn new(device: &wgpu::Device) -> Self {
+    if device.features().contains(wgpu::Features::PUSH_CONSTANTS) {
+      let size = device.limits().max_push_constant_size.min(MAX_PUSH_CONSTANT_SIZE);
+      return Self {
+        data: vec![0; size as usize],
+        fallback: None,
+      };
+    }
+
+    let data = vec![0; MAX_PUSH_CONSTANT_SIZE as usize];
+    let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+      label: Some("Push Constant Buffer"),
+      contents: &data,
+      usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+    });
+    let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+      label: Some("Push Constant Bind Group Layout"),
+      entries: &[wgpu::BindGroupLayoutEntry {
+        binding: 0,
+        visibility: STAGES,
+        ty: wgpu::BindingType::Buffer {
+          ty: wgpu::BufferBindingType::Uniform,
+          has_dynamic_offset: false,
+          min_binding_size: None,
+        },
+        count: None,
+      }],
+    });
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+      label: Some("Push Constant Bind Group"),
+      layout: &layout,
+      entries: &[wgpu::BindGroupEntry {
+        binding: 0,
+        resource: buffer.as_entire_binding(),
+      }],
+    });
+
+    Self {
+      data,
+      fallback: Some(UniformFallback { buffer, layout, bind_group }),
+    }
+  }
+
+  pub(crate) fn size(&self) -> u32 {
+    self.data.len() as u32
+  }
+
+  pub(crate) fn set(&mut self, queue: &wgpu::Queue, bytes: &[u8]) -> Result<(), ExceedsLimit> {
+    limits::check(
+      "Push constant data size",
+      bytes.len() as u64,
+      self.data.len() as u64,
+      "move larger per-draw data into a uniform or storage buffer",
+    )?;
+    self.data.fill(0);
+    self.data[..bytes.len()].copy_from_slice(bytes);
+    if let Some(fallback) = &self.fallback {
+      queue.write_buffer(&fallback.buffer, 0, &self.data);
+    }
+    Ok(())
+  }
+
+  // for the pipeline layout, empty with the fallback
+  pub(crate) fn ranges(&self) -> Vec<wgpu::PushConstantRange> {
+    if self.fallback.is_some() {
+      return Vec::new();
+    }
+    vec![wgpu::PushConstantRange {
+      stages: STAGES,
+      range: 0..self.size(),
+    }]
+  }
+
+  // appended to the pipeline's bind group layouts with the fallback
+  pub(crate) fn bind_group_layout(&self) -> Option<&wgpu::BindGroupLayout> {
+    self.fallback.as_ref().map(|fallback| &fallback.layout)
+  }
+
+  // `group` is the number of bind groups the pipeline has without this one
+  pub(crate) fn prepare_source<'a>(&self, source: &'a str, group: u32) -> Cow<'a, str> {
+    if self.fallback.is_none() || !source.contains("var<push_constant>") {
+      return Cow::Borrowed(source);
+    }
+    Cow::Owned(as_uniform(source, group))
+  }
+
+  // call after every set_pipeline()
+  pub(crate) fn apply(&self, render_pass: &mut wgpu::RenderPass, group: u32) {
+    match &self.fallback {
+      Some(fallback) => render_pass.set_bind_group(group, &fallback.bind_group, &[]),
+      None => render_pass.set_push_constants(STAGES, 0, &self.data),
+    }
+  }
+}
+
+fn as_uniform(source: &str, group: u32) -> String {
+  let binding = format!("@group({}) @binding(0) var<uniform>", group);
+  source.replace("var<push_constant>", &binding)
+}
+
+// WGSL lays a type out the same way in every address space, but uniforms
+// have extra rules push constants don't: array strides and the offset of a
+// member after a struct must be multiples of 16, so e.g. `array<f32, 4>`
+// isn't allowed. The shader is checked against them on every device, so a
+// push constant type that would only break on the WebGL fallback fails
+// natively too. Err has naga's message; shaders that fail to validate as
+// they are pass here and are left to wgpu to report.
+pub(crate) fn check_uniform_layout(source: &str, group: u32) -> Result<(), String> {
+  if !source.contains("var<push_constant>") {
+    return Ok(());
+  }
+  let validate = |source: &str| -> Result<(), String> {
+    let module = naga::front::wgsl::parse_str(source).map_err(|err| err.emit_to_string(source))?;
+    naga::valid::Validator::new(naga::valid::ValidationFlags::all(), naga::valid::Capabilities::all())
+      .validate(&module)
+      .map(|_| ())
+      .map_err(|err| err.emit_to_string(source))
+  };
+  if validate(source).is_err() {
+    return Ok(());
+  }
+  validate(&as_uniform(source, group))
+}
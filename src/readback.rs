@@ -0,0 +1,235 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+
+use crate::limits::{self, ExceedsLimit};
+use crate::MapTracker;
+
+const MAP_PENDING: u8 = 0;
+const MAP_DONE: u8 = 1;
+const MAP_FAILED: u8 = 2;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ReadbackId(u64);
+
+pub struct Readback<T> {
+  pub id: ReadbackId,
+  pub data: Vec<T>,
+}
+
+enum SlotState {
+  Free,
+  // copy recorded into an encoder that hasn't been submitted yet
+  Recorded,
+  Mapping(Arc<AtomicU8>),
+}
+
+struct Slot {
+  buffer: wgpu::Buffer,
+  state: SlotState,
+  id: ReadbackId,
+  len: u64,
+  // set for texture copies, whose rows are padded to COPY_BYTES_PER_ROW_ALIGNMENT
+  rows: Option<(u32, u32)>,
+}
+
+// Asynchronous GPU -> CPU readbacks without stalling the frame. Copies go into
+// one of N small staging buffers, which are mapped after submission and
+// handed back by poll() a few frames later. The render thread never waits on
+// the GPU: when every slot is in flight new requests are refused instead.
+pub struct ReadbackRing<T> {
+  slots: Vec<Slot>,
+  next_slot: usize,
+  next_id: u64,
+  ready: VecDeque<Readback<T>>,
+  // mappings the last poll() found failed
+  failed: Vec<ReadbackId>,
+  tracker: Option<MapTracker>,
+}
+
+impl<T: bytemuck::Pod> ReadbackRing<T> {
+  pub fn new(device: &wgpu::Device, slot_count: usize, slot_size: wgpu::BufferAddress) -> Result<Self, ExceedsLimit> {
+    limits::check_buffer_size(&device.limits(), "readback slot size", slot_size)?;
+
+    let slots = (0..slot_count)
+      .map(|i| Slot {
+        buffer: device.create_buffer(&wgpu::BufferDescriptor {
+          label: Some(&format!("Readback Slot {}", i)),
+          size: slot_size,
+          usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+          mapped_at_creation: false,
+        }),
+        state: SlotState::Free,
+        id: ReadbackId(0),
+        len: 0,
+        rows: None,
+      })
+      .collect();
+
+    Ok(Self {
+      slots,
+      next_slot: 0,
+      next_id: 0,
+      ready: VecDeque::new(),
+      failed: Vec::new(),
+      tracker: None,
+    })
+  }
+
+  // registers pending mappings with a tracker, e.g. State::map_tracker(), so
+  // the device gets polled while they are outstanding
+  pub fn tracked_by(mut self, tracker: MapTracker) -> Self {
+    self.tracker = Some(tracker);
+    self
+  }
+
+  pub fn slot_size(&self) -> wgpu::BufferAddress {
+    self.slots.first().map_or(0, |slot| slot.buffer.size())
+  }
+
+  pub fn in_flight(&self) -> usize {
+    self.slots.iter().filter(|slot| !matches!(slot.state, SlotState::Free)).count()
+  }
+
+  // Records a copy of `size` bytes of `source` into a free slot. Returns None
+  // when all slots are busy or the copy doesn't fit a slot.
+  pub fn request_buffer(
+    &mut self,
+    encoder: &mut wgpu::CommandEncoder,
+    source: &wgpu::Buffer,
+    offset: wgpu::BufferAddress,
+    size: wgpu::BufferAddress,
+  ) -> Option<ReadbackId> {
+    if size == 0 || !size.is_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT) || size > self.slot_size() {
+      return None;
+    }
+    let slot = self.acquire_slot()?;
+    encoder.copy_buffer_to_buffer(source, offset, &self.slots[slot].buffer, 0, size);
+    Some(self.record(slot, size, None))
+  }
+
+  // Records a copy of a texture region. The row padding required by
+  // copy_texture_to_buffer is stripped again before the data is returned.
+  pub fn request_texture(
+    &mut self,
+    encoder: &mut wgpu::CommandEncoder,
+    source: wgpu::ImageCopyTexture,
+    extent: wgpu::Extent3d,
+  ) -> Option<ReadbackId> {
+    let texel_size = source.texture.format().block_copy_size(Some(source.aspect))?;
+    let row_bytes = extent.width * texel_size;
+    let padded_row_bytes = padded_bytes_per_row(row_bytes);
+    let size = padded_row_bytes as u64 * extent.height as u64;
+    if extent.depth_or_array_layers != 1 || size > self.slot_size() {
+      return None;
+    }
+
+    let slot = self.acquire_slot()?;
+    encoder.copy_texture_to_buffer(
+      source,
+      wgpu::ImageCopyBuffer {
+        buffer: &self.slots[slot].buffer,
+        layout: wgpu::ImageDataLayout {
+          offset: 0,
+          bytes_per_row: Some(padded_row_bytes),
+          rows_per_image: Some(extent.height),
+        },
+      },
+      extent,
+    );
+    Some(self.record(slot, size, Some((row_bytes, padded_row_bytes))))
+  }
+
+  // Starts mapping every slot recorded since the last call. Must be called
+  // after the encoder holding the copies was submitted.
+  pub fn after_submit(&mut self) {
+    for slot in &mut self.slots {
+      if let SlotState::Recorded = slot.state {
+        let status = Arc::new(AtomicU8::new(MAP_PENDING));
+        let callback_status = Arc::clone(&status);
+        let tracker = self.tracker.clone();
+        if let Some(tracker) = &tracker {
+          tracker.begin();
+        }
+        slot.buffer.slice(..slot.len).map_async(wgpu::MapMode::Read, move |result| {
+          let value = if result.is_ok() { MAP_DONE } else { MAP_FAILED };
+          callback_status.store(value, Ordering::Release);
+          if let Some(tracker) = tracker {
+            tracker.end();
+          }
+        });
+        slot.state = SlotState::Mapping(status);
+      }
+    }
+  }
+
+  // Collects every finished mapping without blocking. The device still has
+  // to be polled for the map callbacks to run on native, State::poll_readbacks()
+  // does both.
+  pub fn poll(&mut self) -> impl Iterator<Item = Readback<T>> + '_ {
+    self.failed.clear();
+    for slot in &mut self.slots {
+      let status = match &slot.state {
+        SlotState::Mapping(status) => status.load(Ordering::Acquire),
+        _ => continue,
+      };
+      match status {
+        MAP_DONE => {
+          let data = {
+            let bytes = slot.buffer.slice(..slot.len).get_mapped_range();
+            match slot.rows {
+              Some((row_bytes, padded_row_bytes)) => {
+                let packed: Vec<u8> = bytes.chunks(padded_row_bytes as usize)
+                  .flat_map(|row| &row[..row_bytes as usize])
+                  .copied()
+                  .collect();
+                bytemuck::pod_collect_to_vec(&packed)
+              }
+              None => bytemuck::pod_collect_to_vec(&bytes),
+            }
+          };
+          slot.buffer.unmap();
+          slot.state = SlotState::Free;
+          self.ready.push_back(Readback { id: slot.id, data });
+        }
+        MAP_FAILED => {
+          log::warn!("Readback {:?} failed to map", slot.id);
+          slot.state = SlotState::Free;
+          self.failed.push(slot.id);
+        }
+        _ => (),
+      }
+    }
+    self.ready.drain(..)
+  }
+
+  // the readbacks the last poll() found failed, their data is lost
+  pub fn failed(&self) -> &[ReadbackId] {
+    &self.failed
+  }
+
+  fn acquire_slot(&mut self) -> Option<usize> {
+    let count = self.slots.len();
+    let slot = (0..count)
+      .map(|i| (self.next_slot + i) % count)
+      .find(|&i| matches!(self.slots[i].state, SlotState::Free))?;
+    self.next_slot = (slot + 1) % count;
+    Some(slot)
+  }
+
+  fn record(&mut self, slot: usize, len: u64, rows: Option<(u32, u32)>) -> ReadbackId {
+    let id = ReadbackId(self.next_id);
+    self.next_id += 1;
+    let slot = &mut self.slots[slot];
+    slot.state = SlotState::Recorded;
+    slot.id = id;
+    slot.len = len;
+    slot.rows = rows;
+    id
+  }
+}
+
+pub(crate) fn padded_bytes_per_row(row_bytes: u32) -> u32 {
+  let alignment = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+  row_bytes.div_ceil(alignment) * alignment
+}
@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+
+// All randomness inside the crate goes through here so a fixed seed makes
+// runs reproducible. Every subsystem gets its own stream derived from the
+// seed and the subsystem's name, so adding a consumer doesn't shift the
+// numbers another one sees.
+//
+// Determinism covers CPU-side outputs only (generated data, simulation
+// state); the same inputs can still rasterize slightly differently on
+// different GPUs and drivers.
+pub struct RngStreams {
+  seed: u64,
+  streams: HashMap<&'static str, Pcg32>,
+}
+
+impl RngStreams {
+  pub fn new(seed: u64) -> Self {
+    Self {
+      seed,
+      streams: HashMap::new(),
+    }
+  }
+
+  pub fn seed(&self) -> u64 {
+    self.seed
+  }
+
+  // restarts every stream from the new seed
+  pub fn reseed(&mut self, seed: u64) {
+    self.seed = seed;
+    self.streams.clear();
+  }
+
+  pub fn stream(&mut self, subsystem: &'static str) -> &mut Pcg32 {
+    let seed = self.seed;
+    self.streams
+      .entry(subsystem)
+      .or_insert_with(|| Pcg32::from_stream(seed, fnv1a(subsystem.as_bytes())))
+  }
+}
+
+// PCG-XSH-RR 32 bit output, 64 bit state
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Pcg32 {
+  state: u64,
+  increment: u64,
+}
+
+const PCG_MULTIPLIER: u64 = 6364136223846793005;
+
+impl Pcg32 {
+  pub fn new(seed: u64) -> Self {
+    Self::from_stream(seed, 0)
+  }
+
+  // SplitMix64 spreads nearby seeds and stream ids across the state space
+  pub fn from_stream(seed: u64, stream: u64) -> Self {
+    let mut mix = seed ^ stream.rotate_left(32);
+    let state = splitmix64(&mut mix);
+    let increment = (splitmix64(&mut mix) << 1) | 1;
+    let mut rng = Self { state: 0, increment };
+    rng.next_u32();
+    rng.state = rng.state.wrapping_add(state);
+    rng.next_u32();
+    rng
+  }
+
+  pub fn next_u32(&mut self) -> u32 {
+    let old = self.state;
+    self.state = old.wrapping_mul(PCG_MULTIPLIER).wrapping_add(self.increment);
+    let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+    let rotation = (old >> 59) as u32;
+    xorshifted.rotate_right(rotation)
+  }
+
+  pub fn next_u64(&mut self) -> u64 {
+    ((self.next_u32() as u64) << 32) | self.next_u32() as u64
+  }
+
+  // uniform in [0, 1)
+  pub fn next_f32(&mut self) -> f32 {
+    (self.next_u32() >> 8) as f32 / (1u32 << 24) as f32
+  }
+
+  pub fn range_f32(&mut self, min: f32, max: f32) -> f32 {
+    min + (max - min) * self.next_f32()
+  }
+}
+
+// lets the rand crate's distributions draw from a crate stream
+impl rand::RngCore for Pcg32 {
+  fn next_u32(&mut self) -> u32 {
+    Pcg32::next_u32(self)
+  }
+
+  fn next_u64(&mut self) -> u64 {
+    Pcg32::next_u64(self)
+  }
+
+  fn fill_bytes(&mut self, dest: &mut [u8]) {
+    for chunk in dest.chunks_mut(4) {
+      let bytes = Pcg32::next_u32(self).to_le_bytes();
+      chunk.copy_from_slice(&bytes[..chunk.len()]);
+    }
+  }
+
+  fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+    self.fill_bytes(dest);
+    Ok(())
+  }
+}
+
+fn splitmix64(state: &mut u64) -> u64 {
+  *state = state.wrapping_add(0x9E3779B97F4A7C15);
+  let mut z = *state;
+  z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+  z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+  z ^ (z >> 31)
+}
+
+// stable across platforms and Rust versions, unlike std's DefaultHasher
+fn fnv1a(bytes: &[u8]) -> u64 {
+  bytes.iter().fold(0xcbf29ce484222325, |hash, &byte| {
+    (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+  })
+}
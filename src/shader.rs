@@ -0,0 +1,86 @@
+use std::fmt;
+use std::path::PathBuf;
+
+#[derive(Debug)]
+pub enum ShaderError {
+  Io { path: PathBuf, source: std::io::Error },
+  // wgpu's validation message, including naga's source location
+  Validation(String),
+  // the push constant type breaks the uniform layout rules, so it wouldn't
+  // work where push constants fall back to a uniform buffer (WebGL)
+  PushConstantLayout(String),
+  #[cfg(feature = "hot-reload")]
+  Watch(notify::Error),
+}
+
+impl fmt::Display for ShaderError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      ShaderError::Io { path, source } => write!(f, "Failed to read shader {}: {}", path.display(), source),
+      ShaderError::Validation(message) => write!(f, "Shader failed to compile: {}", message),
+      ShaderError::PushConstantLayout(message) => {
+        write!(f, "The push constant type isn't valid in a uniform buffer, which replaces push constants on WebGL: {}", message)
+      }
+      #[cfg(feature = "hot-reload")]
+      ShaderError::Watch(err) => write!(f, "Failed to watch shader: {}", err),
+    }
+  }
+}
+
+impl std::error::Error for ShaderError {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      ShaderError::Io { source, .. } => Some(source),
+      ShaderError::Validation(_) | ShaderError::PushConstantLayout(_) => None,
+      #[cfg(feature = "hot-reload")]
+      ShaderError::Watch(err) => Some(err),
+    }
+  }
+}
+
+// Watches a shader file for changes. The parent directory is watched
+// rather than the file itself, since many editors save by replacing the
+// file, which would silently end a watch on the old inode.
+#[cfg(feature = "hot-reload")]
+pub(crate) struct ShaderWatcher {
+  path: PathBuf,
+  events: std::sync::mpsc::Receiver<notify::Result<notify::Event>>,
+  _watcher: notify::RecommendedWatcher,
+}
+
+#[cfg(feature = "hot-reload")]
+impl ShaderWatcher {
+  pub(crate) fn new(path: &std::path::Path) -> Result<Self, ShaderError> {
+    use notify::Watcher;
+
+    let path = path.canonicalize().map_err(|source| ShaderError::Io {
+      path: path.to_path_buf(),
+      source,
+    })?;
+    let directory = path.parent().unwrap_or(&path).to_path_buf();
+    let (sender, events) = std::sync::mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender).map_err(ShaderError::Watch)?;
+    watcher.watch(&directory, notify::RecursiveMode::NonRecursive).map_err(ShaderError::Watch)?;
+
+    Ok(Self {
+      path,
+      events,
+      _watcher: watcher,
+    })
+  }
+
+  // Drains pending events and returns the path if the shader was modified.
+  // A save often arrives as several events, they count as one change.
+  pub(crate) fn changed(&self) -> Option<PathBuf> {
+    let mut changed = false;
+    for event in self.events.try_iter() {
+      match event {
+        Ok(event) => {
+          changed |= (event.kind.is_create() || event.kind.is_modify()) && event.paths.contains(&self.path);
+        }
+        Err(err) => log::warn!("Shader watch error: {}", err),
+      }
+    }
+    changed.then(|| self.path.clone())
+  }
+}
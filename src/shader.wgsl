@@ -10,10 +10,16 @@ struct VertexOutput {
 }
 
 @vertex
-fn vs_main(model: VertexInput) -> VertexOutput {
+fn vs_main(model: VertexInput, instance: InstanceInput) -> VertexOutput {
+  let model_matrix = mat4x4<f32>(
+    instance.model_matrix_0,
+    instance.model_matrix_1,
+    instance.model_matrix_2,
+    instance.model_matrix_3,
+  );
   var out: VertexOutput;
   out.color = model.color;
-  out.clip_position = vec4<f32>(model.position, 1.0);
+  out.clip_position = uniforms.view_proj * uniforms.transform * model_matrix * vec4<f32>(model.position, 1.0);
 
   return out;
 }
@@ -22,5 +28,5 @@ fn vs_main(model: VertexInput) -> VertexOutput {
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-  return vec4<f32>(in.color, 1.0);
+  return encode_output(vec4<f32>(in.color, 1.0));
 }
@@ -0,0 +1,71 @@
+use cgmath::{Matrix4, SquareMatrix, Vector4};
+use wgpu::util::DeviceExt;
+
+use crate::{Camera, DepthBias, Environment, PipelineDesc, Uniforms, IDENTITY};
+
+// what the skybox pipeline is cached under: one fullscreen triangle, never
+// culled, whatever the render mode
+pub(crate) const SKYBOX_PIPELINE: PipelineDesc = PipelineDesc {
+  polygon_mode: wgpu::PolygonMode::Fill,
+  topology: wgpu::PrimitiveTopology::TriangleList,
+  cull_mode: None,
+  depth_bias: DepthBias::NONE,
+};
+
+// The environment cubemap drawn behind the scene, created by
+// State::set_environment(). skybox.wgsl draws it at the far plane, so with
+// depth testing on only the pixels nothing else covered remain.
+pub(crate) struct Skybox {
+  // Uniforms whose view_proj is the inverse of the camera's without the
+  // translation, bound at @group(0)
+  uniform_buffer: wgpu::Buffer,
+  pub(crate) uniform_bind_group: wgpu::BindGroup,
+  // the cubemap, bound at @group(1)
+  pub(crate) environment_bind_group: wgpu::BindGroup,
+}
+
+impl Skybox {
+  pub(crate) fn new(
+    device: &wgpu::Device,
+    uniform_layout: &wgpu::BindGroupLayout,
+    environment_layout: &wgpu::BindGroupLayout,
+    environment: &Environment,
+    camera: &Camera,
+  ) -> Self {
+    let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+      label: Some("Skybox Uniform Buffer"),
+      contents: bytemuck::cast_slice(&[uniforms(camera)]),
+      usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+    });
+    let uniform_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+      label: Some("Skybox Bind Group"),
+      layout: uniform_layout,
+      entries: &[wgpu::BindGroupEntry {
+        binding: 0,
+        resource: uniform_buffer.as_entire_binding(),
+      }],
+    });
+
+    Self {
+      uniform_buffer,
+      uniform_bind_group,
+      environment_bind_group: environment.skybox_bind_group(device, environment_layout),
+    }
+  }
+
+  pub(crate) fn write_uniforms(&self, queue: &wgpu::Queue, camera: &Camera) {
+    queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniforms(camera)]));
+  }
+}
+
+// Only the camera's rotation, so the sky stays put when the eye moves. The
+// inverse takes a clip space position back to a direction from the eye.
+fn uniforms(camera: &Camera) -> Uniforms {
+  let mut view = Matrix4::look_at_rh(camera.eye, camera.target, camera.up);
+  view.w = Vector4::unit_w();
+  let view_proj = camera.projection_matrix() * view;
+  Uniforms {
+    view_proj: view_proj.invert().unwrap_or(Matrix4::identity()).into(),
+    transform: IDENTITY,
+  }
+}
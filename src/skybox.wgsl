@@ -0,0 +1,28 @@
+// the environment cubemap behind the scene, see skybox.rs. uniforms.view_proj
+// is the inverse of the camera's rotation-only view projection.
+@group(1) @binding(0)
+var t_environment: texture_cube<f32>;
+@group(1) @binding(1)
+var s_environment: sampler;
+
+struct VertexOutput {
+  @builtin(position) clip_position: vec4<f32>,
+  // linear in clip space, so it's only divided per fragment
+  @location(0) far: vec4<f32>,
+}
+
+// one triangle covering the screen, at the far plane
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+  let ndc = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u)) * 2.0 - 1.0;
+  var out: VertexOutput;
+  out.clip_position = vec4<f32>(ndc, 1.0, 1.0);
+  out.far = uniforms.view_proj * out.clip_position;
+  return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+  let direction = normalize(in.far.xyz / in.far.w);
+  return encode_output(vec4<f32>(textureSample(t_environment, s_environment, direction).rgb, 1.0));
+}
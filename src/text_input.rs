@@ -0,0 +1,303 @@
+use std::ops::Range;
+use std::time::Duration;
+use web_time::Instant;
+
+use unicode_segmentation::UnicodeSegmentation;
+use winit::event::{ElementState, Ime, KeyEvent, WindowEvent};
+use winit::keyboard::{Key, ModifiersState, NamedKey};
+
+const CARET_BLINK: Duration = Duration::from_millis(530);
+
+// in-progress IME composition, shown separately from the committed text
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Composition {
+  pub text: String,
+  // byte range of the IME's own cursor/selection inside `text`
+  pub cursor: Option<(usize, usize)>,
+}
+
+// Editable single-line text state fed from winit events. Cursor and
+// selection are byte indices into the UTF-8 text and always sit on grapheme
+// boundaries, so arrows and backspace treat "é" or a flag emoji as one unit.
+pub struct TextInput {
+  text: String,
+  cursor: usize,
+  // other end of the selection, if any
+  anchor: Option<usize>,
+  composition: Option<Composition>,
+  modifiers: ModifiersState,
+  last_activity: Instant,
+  #[cfg(feature = "clipboard")]
+  clipboard: Option<arboard::Clipboard>,
+}
+
+impl Default for TextInput {
+  fn default() -> Self {
+    Self {
+      text: String::new(),
+      cursor: 0,
+      anchor: None,
+      composition: None,
+      modifiers: ModifiersState::empty(),
+      last_activity: Instant::now(),
+      #[cfg(feature = "clipboard")]
+      clipboard: arboard::Clipboard::new().ok(),
+    }
+  }
+}
+
+impl TextInput {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn with_text(text: &str) -> Self {
+    Self {
+      text: text.to_string(),
+      cursor: text.len(),
+      ..Default::default()
+    }
+  }
+
+  pub fn text(&self) -> &str {
+    &self.text
+  }
+
+  pub fn cursor(&self) -> usize {
+    self.cursor
+  }
+
+  pub fn selection(&self) -> Option<Range<usize>> {
+    let anchor = self.anchor?;
+    (anchor != self.cursor).then(|| anchor.min(self.cursor)..anchor.max(self.cursor))
+  }
+
+  pub fn selected_text(&self) -> Option<&str> {
+    self.selection().map(|range| &self.text[range])
+  }
+
+  pub fn composition(&self) -> Option<&Composition> {
+    self.composition.as_ref()
+  }
+
+  // blinks at a fixed rate and stays solid for a moment after each edit
+  pub fn caret_visible(&self) -> bool {
+    let phase = self.last_activity.elapsed().as_millis() / CARET_BLINK.as_millis();
+    phase.is_multiple_of(2)
+  }
+
+  // Returns whether the event was consumed by the text field.
+  pub fn handle_event(&mut self, event: &WindowEvent) -> bool {
+    match event {
+      WindowEvent::ModifiersChanged(modifiers) => {
+        self.modifiers = modifiers.state();
+        false
+      }
+      WindowEvent::Ime(ime) => {
+        self.handle_ime(ime);
+        true
+      }
+      WindowEvent::KeyboardInput { event, .. } if event.state == ElementState::Pressed => {
+        // while composing, keys belong to the IME
+        if self.composition.is_some() {
+          return true;
+        }
+        self.handle_key(event)
+      }
+      _ => false,
+    }
+  }
+
+  fn handle_ime(&mut self, ime: &Ime) {
+    match ime {
+      Ime::Preedit(text, cursor) => {
+        self.composition = (!text.is_empty()).then(|| Composition {
+          text: text.clone(),
+          cursor: *cursor,
+        });
+      }
+      Ime::Commit(text) => {
+        self.composition = None;
+        self.insert_str(text);
+      }
+      Ime::Enabled | Ime::Disabled => self.composition = None,
+    }
+    self.touch();
+  }
+
+  fn handle_key(&mut self, event: &KeyEvent) -> bool {
+    let extend = self.modifiers.shift_key();
+    let handled = match &event.logical_key {
+      Key::Named(NamedKey::Backspace) => {
+        self.delete_backward();
+        true
+      }
+      Key::Named(NamedKey::Delete) => {
+        self.delete_forward();
+        true
+      }
+      // without shift, an arrow key collapses the selection to its edge
+      Key::Named(NamedKey::ArrowLeft) => {
+        let position = match self.selection() {
+          Some(selection) if !extend => selection.start,
+          _ => self.previous_boundary(self.cursor),
+        };
+        self.move_to(position, extend);
+        true
+      }
+      Key::Named(NamedKey::ArrowRight) => {
+        let position = match self.selection() {
+          Some(selection) if !extend => selection.end,
+          _ => self.next_boundary(self.cursor),
+        };
+        self.move_to(position, extend);
+        true
+      }
+      Key::Named(NamedKey::Home) => {
+        self.move_to(0, extend);
+        true
+      }
+      Key::Named(NamedKey::End) => {
+        self.move_to(self.text.len(), extend);
+        true
+      }
+      Key::Character(c) if self.command_modifier() => self.handle_shortcut(c.as_str()),
+      _ => match event.text.as_deref() {
+        Some(text) if !text.chars().any(char::is_control) => {
+          self.insert_str(text);
+          true
+        }
+        _ => false,
+      },
+    };
+    if handled {
+      self.touch();
+    }
+    handled
+  }
+
+  // Ctrl on most platforms, Cmd on macOS
+  fn command_modifier(&self) -> bool {
+    if cfg!(target_os = "macos") {
+      self.modifiers.super_key()
+    } else {
+      self.modifiers.control_key()
+    }
+  }
+
+  fn handle_shortcut(&mut self, key: &str) -> bool {
+    match key.to_lowercase().as_str() {
+      "a" => {
+        self.select_all();
+        true
+      }
+      #[cfg(feature = "clipboard")]
+      "c" => {
+        self.copy();
+        true
+      }
+      #[cfg(feature = "clipboard")]
+      "x" => {
+        self.cut();
+        true
+      }
+      #[cfg(feature = "clipboard")]
+      "v" => {
+        self.paste();
+        true
+      }
+      _ => false,
+    }
+  }
+
+  // replaces the selection, if any
+  pub fn insert_str(&mut self, text: &str) {
+    self.delete_selection();
+    self.text.insert_str(self.cursor, text);
+    self.cursor += text.len();
+  }
+
+  pub fn delete_backward(&mut self) {
+    if !self.delete_selection() && self.cursor > 0 {
+      let start = self.previous_boundary(self.cursor);
+      self.text.replace_range(start..self.cursor, "");
+      self.cursor = start;
+    }
+  }
+
+  pub fn delete_forward(&mut self) {
+    if !self.delete_selection() && self.cursor < self.text.len() {
+      let end = self.next_boundary(self.cursor);
+      self.text.replace_range(self.cursor..end, "");
+    }
+  }
+
+  pub fn select_all(&mut self) {
+    self.anchor = Some(0);
+    self.cursor = self.text.len();
+  }
+
+  pub fn clear(&mut self) {
+    self.text.clear();
+    self.cursor = 0;
+    self.anchor = None;
+    self.composition = None;
+  }
+
+  #[cfg(feature = "clipboard")]
+  pub fn copy(&mut self) {
+    let Some(selected) = self.selected_text().map(str::to_string) else { return };
+    if let Some(clipboard) = self.clipboard.as_mut() {
+      if let Err(err) = clipboard.set_text(selected) {
+        log::warn!("Failed to copy to the clipboard: {}", err);
+      }
+    }
+  }
+
+  #[cfg(feature = "clipboard")]
+  pub fn cut(&mut self) {
+    self.copy();
+    self.delete_selection();
+  }
+
+  #[cfg(feature = "clipboard")]
+  pub fn paste(&mut self) {
+    let text = self.clipboard.as_mut().and_then(|clipboard| clipboard.get_text().ok());
+    if let Some(text) = text {
+      // single-line field
+      self.insert_str(&text.replace(['\r', '\n'], " "));
+    }
+  }
+
+  fn move_to(&mut self, position: usize, extend: bool) {
+    if extend {
+      self.anchor.get_or_insert(self.cursor);
+    } else {
+      self.anchor = None;
+    }
+    self.cursor = position;
+  }
+
+  fn delete_selection(&mut self) -> bool {
+    let Some(selection) = self.selection() else {
+      self.anchor = None;
+      return false;
+    };
+    self.text.replace_range(selection.clone(), "");
+    self.cursor = selection.start;
+    self.anchor = None;
+    true
+  }
+
+  fn previous_boundary(&self, position: usize) -> usize {
+    self.text[..position].grapheme_indices(true).next_back().map_or(0, |(index, _)| index)
+  }
+
+  fn next_boundary(&self, position: usize) -> usize {
+    self.text[position..].graphemes(true).next().map_or(position, |grapheme| position + grapheme.len())
+  }
+
+  fn touch(&mut self) {
+    self.last_activity = Instant::now();
+  }
+}
@@ -0,0 +1,191 @@
+use anyhow::Context;
+
+use crate::limits;
+
+// Whether the texels hold colors (stored in sRGB, decoded to linear when
+// sampled) or plain data like normal maps that must not be converted.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ColorSpace {
+  #[default]
+  Srgb,
+  Linear,
+}
+
+impl ColorSpace {
+  fn format(self) -> wgpu::TextureFormat {
+    match self {
+      ColorSpace::Srgb => wgpu::TextureFormat::Rgba8UnormSrgb,
+      ColorSpace::Linear => wgpu::TextureFormat::Rgba8Unorm,
+    }
+  }
+}
+
+pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+// 2D RGBA8 texture with a view and sampler ready to bind
+pub struct Texture {
+  pub texture: wgpu::Texture,
+  pub view: wgpu::TextureView,
+  pub sampler: wgpu::Sampler,
+}
+
+impl Texture {
+  // decodes PNG, JPEG and anything else the image crate recognizes, as sRGB
+  pub fn from_bytes(device: &wgpu::Device, queue: &wgpu::Queue, bytes: &[u8], label: &str) -> anyhow::Result<Self> {
+    Self::from_bytes_with(device, queue, bytes, label, ColorSpace::Srgb)
+  }
+
+  pub fn from_bytes_with(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    bytes: &[u8],
+    label: &str,
+    color_space: ColorSpace,
+  ) -> anyhow::Result<Self> {
+    let image = image::load_from_memory(bytes).with_context(|| format!("Failed to decode texture {}", label))?;
+    Self::from_image(device, queue, &image, label, color_space)
+  }
+
+  pub fn from_image(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    image: &image::DynamicImage,
+    label: &str,
+    color_space: ColorSpace,
+  ) -> anyhow::Result<Self> {
+    let rgba = image.to_rgba8();
+    let (width, height) = rgba.dimensions();
+    limits::check_texture_2d(&device.limits(), "Texture size", width, height)?;
+
+    let size = wgpu::Extent3d {
+      width,
+      height,
+      depth_or_array_layers: 1,
+    };
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+      label: Some(label),
+      size,
+      mip_level_count: 1,
+      sample_count: 1,
+      dimension: wgpu::TextureDimension::D2,
+      format: color_space.format(),
+      usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+      view_formats: &[],
+    });
+    // unlike buffer-to-texture copies, write_texture doesn't need rows padded
+    // to 256 bytes, so odd widths can be uploaded as they are
+    queue.write_texture(
+      wgpu::ImageCopyTexture {
+        texture: &texture,
+        mip_level: 0,
+        origin: wgpu::Origin3d::ZERO,
+        aspect: wgpu::TextureAspect::All,
+      },
+      &rgba,
+      wgpu::ImageDataLayout {
+        offset: 0,
+        bytes_per_row: Some(width * 4),
+        rows_per_image: Some(height),
+      },
+      size,
+    );
+
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    // clamping and no mipmaps keep non-power-of-two sizes valid on WebGL2
+    let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+      label: Some(label),
+      address_mode_u: wgpu::AddressMode::ClampToEdge,
+      address_mode_v: wgpu::AddressMode::ClampToEdge,
+      address_mode_w: wgpu::AddressMode::ClampToEdge,
+      mag_filter: wgpu::FilterMode::Linear,
+      min_filter: wgpu::FilterMode::Linear,
+      mipmap_filter: wgpu::FilterMode::Nearest,
+      ..Default::default()
+    });
+
+    Ok(Self { texture, view, sampler })
+  }
+
+  // depth attachment matching a surface of the given size, the comparison
+  // sampler is there for shadow-map style lookups
+  pub fn create_depth_texture(device: &wgpu::Device, width: u32, height: u32, sample_count: u32, label: &str) -> Self {
+    // multisampled depth can't be sampled through the comparison sampler
+    // anyway, and GL drivers may fail to attach it when it's bindable
+    let usage = if sample_count > 1 {
+      wgpu::TextureUsages::RENDER_ATTACHMENT
+    } else {
+      wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING
+    };
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+      label: Some(label),
+      size: wgpu::Extent3d {
+        width: width.max(1),
+        height: height.max(1),
+        depth_or_array_layers: 1,
+      },
+      mip_level_count: 1,
+      sample_count,
+      dimension: wgpu::TextureDimension::D2,
+      format: DEPTH_FORMAT,
+      usage,
+      view_formats: &[],
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+      label: Some(label),
+      address_mode_u: wgpu::AddressMode::ClampToEdge,
+      address_mode_v: wgpu::AddressMode::ClampToEdge,
+      address_mode_w: wgpu::AddressMode::ClampToEdge,
+      mag_filter: wgpu::FilterMode::Linear,
+      min_filter: wgpu::FilterMode::Linear,
+      mipmap_filter: wgpu::FilterMode::Nearest,
+      compare: Some(wgpu::CompareFunction::LessEqual),
+      lod_min_clamp: 0.0,
+      lod_max_clamp: 100.0,
+      ..Default::default()
+    });
+
+    Self { texture, view, sampler }
+  }
+
+  pub fn bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+      label: Some("Texture Bind Group Layout"),
+      entries: &[
+        wgpu::BindGroupLayoutEntry {
+          binding: 0,
+          visibility: wgpu::ShaderStages::FRAGMENT,
+          ty: wgpu::BindingType::Texture {
+            multisampled: false,
+            view_dimension: wgpu::TextureViewDimension::D2,
+            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+          },
+          count: None,
+        },
+        wgpu::BindGroupLayoutEntry {
+          binding: 1,
+          visibility: wgpu::ShaderStages::FRAGMENT,
+          ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+          count: None,
+        },
+      ],
+    })
+  }
+
+  pub fn bind_group(&self, device: &wgpu::Device, layout: &wgpu::BindGroupLayout) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+      label: Some("Texture Bind Group"),
+      layout,
+      entries: &[
+        wgpu::BindGroupEntry {
+          binding: 0,
+          resource: wgpu::BindingResource::TextureView(&self.view),
+        },
+        wgpu::BindGroupEntry {
+          binding: 1,
+          resource: wgpu::BindingResource::Sampler(&self.sampler),
+        },
+      ],
+    })
+  }
+}
@@ -0,0 +1,37 @@
+// vertex shader
+struct VertexInput {
+  @location(0) position: vec3<f32>,
+  @location(1) tex_coords: vec2<f32>,
+};
+
+struct VertexOutput {
+  @builtin(position) clip_position: vec4<f32>,
+  @location(0) tex_coords: vec2<f32>,
+}
+
+@vertex
+fn vs_main(model: VertexInput, instance: InstanceInput) -> VertexOutput {
+  let model_matrix = mat4x4<f32>(
+    instance.model_matrix_0,
+    instance.model_matrix_1,
+    instance.model_matrix_2,
+    instance.model_matrix_3,
+  );
+  var out: VertexOutput;
+  out.tex_coords = model.tex_coords;
+  out.clip_position = uniforms.view_proj * uniforms.transform * model_matrix * vec4<f32>(model.position, 1.0);
+
+  return out;
+}
+
+// fragment shader
+
+@group(1) @binding(0)
+var t_diffuse: texture_2d<f32>;
+@group(1) @binding(1)
+var s_diffuse: sampler;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+  return encode_output(textureSample(t_diffuse, s_diffuse, in.tex_coords));
+}
@@ -0,0 +1,139 @@
+use std::fmt;
+use std::sync::Arc;
+
+use wasm_bindgen::prelude::*;
+use winit::{
+  application::ApplicationHandler,
+  dpi::PhysicalSize,
+  event::WindowEvent,
+  event_loop::{ActiveEventLoop, EventLoop, EventLoopProxy},
+  platform::web::{EventLoopExtWebSys, WindowExtWebSys},
+  window::{Window, WindowId},
+};
+
+use crate::{State, StateError};
+
+#[derive(Debug)]
+pub enum CanvasError {
+  NoDocument,
+  NoElement(String),
+  // the window has no canvas, e.g. it was already dropped
+  NoCanvas,
+  Append,
+}
+
+impl fmt::Display for CanvasError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      CanvasError::NoDocument => write!(f, "No document to attach the canvas to"),
+      CanvasError::NoElement(id) => write!(f, "No element with id {:?} to attach the canvas to", id),
+      CanvasError::NoCanvas => write!(f, "The window has no canvas"),
+      CanvasError::Append => write!(f, "Couldn't append the canvas to the document"),
+    }
+  }
+}
+
+impl std::error::Error for CanvasError {}
+
+// Puts the window's canvas inside the DOM element with the given id.
+pub fn attach_canvas(window: &Window, element_id: &str) -> Result<(), CanvasError> {
+  let document = web_sys::window()
+    .and_then(|win| win.document())
+    .ok_or(CanvasError::NoDocument)?;
+  let dst = document.get_element_by_id(element_id)
+    .ok_or_else(|| CanvasError::NoElement(element_id.to_string()))?;
+  let canvas = window.canvas().ok_or(CanvasError::NoCanvas)?;
+  dst.append_child(&canvas).map_err(|_| CanvasError::Append)?;
+  Ok(())
+}
+
+// Entry point of `wasm-pack build --target web`, draws the demo triangle
+// into the element with id "wasm-example".
+#[wasm_bindgen(start)]
+pub fn start() {
+  std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+  console_log::init_with_level(log::Level::Warn).expect("Couldn't initialize logger");
+
+  let event_loop = EventLoop::with_user_event().build().expect("Can't create the event loop");
+  let app = WebApp {
+    proxy: Some(event_loop.create_proxy()),
+    window: None,
+    state: None,
+  };
+  event_loop.spawn_app(app);
+}
+
+type StateResult = Result<State, StateError>;
+
+// The browser can't block on futures, so State is created with
+// spawn_local and handed back to the event loop as a user event.
+struct WebApp {
+  proxy: Option<EventLoopProxy<StateResult>>,
+  window: Option<Arc<Window>>,
+  state: Option<State>,
+}
+
+impl ApplicationHandler<StateResult> for WebApp {
+  fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+    let Some(proxy) = self.proxy.take() else { return };
+    let win_attr = Window::default_attributes().with_title("App Initialization");
+    let window = Arc::new(
+      event_loop.create_window(win_attr).expect("create window err."),
+    );
+    // Winit prevents sizing with CSS, so we have to set
+    // the size manually when on the web.
+    let _ = window.request_inner_size(PhysicalSize::new(450, 400));
+    if let Err(err) = attach_canvas(&window, "wasm-example") {
+      log::error!("{}", err);
+    }
+    self.window = Some(window.clone());
+
+    wasm_bindgen_futures::spawn_local(async move {
+      // only fails once the event loop is gone
+      let _ = proxy.send_event(State::new_async(window).await);
+    });
+  }
+
+  fn user_event(&mut self, event_loop: &ActiveEventLoop, state: StateResult) {
+    match state {
+      Ok(state) => {
+        self.state = Some(state);
+        if let Some(window) = self.window.as_ref() {
+          window.request_redraw();
+        }
+      }
+      Err(err) => {
+        log::error!("Failed to set up rendering: {}", err);
+        event_loop.exit();
+      }
+    }
+  }
+
+  fn window_event(
+    &mut self,
+    event_loop: &ActiveEventLoop,
+    _window_id: WindowId,
+    event: WindowEvent,
+  ) {
+    match event {
+      WindowEvent::CloseRequested => {
+        event_loop.exit();
+      }
+      WindowEvent::Resized(new_size) => {
+        if let (Some(state), Some(window)) = (self.state.as_mut(), self.window.as_ref()) {
+          state.resize(new_size);
+          window.request_redraw();
+        }
+      }
+      WindowEvent::RedrawRequested => {
+        if let Some(state) = self.state.as_mut() {
+          if let Err(err) = state.render_or_recover() {
+            log::error!("Rendering failed: {}", err);
+            event_loop.exit();
+          }
+        }
+      }
+      _ => (),
+    }
+  }
+}
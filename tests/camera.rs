@@ -0,0 +1,134 @@
+use std::time::Duration;
+
+use cgmath::{EuclideanSpace, Matrix4, MetricSpace, Point3, Vector4};
+use sotrh::{Camera, Projection};
+
+#[test]
+fn aspect_changes_the_x_scale() {
+  let square = Camera::new(1.0).build_view_projection_matrix();
+  let wide = Camera::new(2.0).build_view_projection_matrix();
+  // a wider frame squeezes x, y is left alone
+  assert!((wide.x.x - square.x.x / 2.0).abs() < 1e-6, "{} vs {}", wide.x.x, square.x.x);
+  assert_eq!(wide.y.y, square.y.y);
+}
+
+fn in_clip_space(clip: Vector4<f32>) -> bool {
+  clip.x.abs() <= clip.w && clip.y.abs() <= clip.w && 0.0 <= clip.z && clip.z <= clip.w
+}
+
+#[test]
+fn points_in_front_of_the_eye_are_in_clip_space() {
+  let camera = Camera::new(1.0);
+  let view_proj = camera.build_view_projection_matrix();
+  let project = |p: Point3<f32>| view_proj * p.to_homogeneous();
+
+  // the eye is at z = 2 looking down -z
+  assert!(in_clip_space(project(Point3::new(0.0, 0.0, 0.0))));
+  assert!(in_clip_space(project(Point3::new(0.3, -0.3, 1.0))));
+  assert!(!in_clip_space(project(Point3::new(0.0, 0.0, 3.0))));
+}
+
+fn assert_near(a: f32, b: f32) {
+  assert!((a - b).abs() < 1e-4, "{} vs {}", a, b);
+}
+
+fn assert_near_point(a: Point3<f32>, b: [f32; 3]) {
+  assert!(a.distance(Point3::from(b)) < 1e-4, "{:?} vs {:?}", a, b);
+}
+
+// the default camera at (0, 0, 2), 2 units tall and wide
+fn orthographic() -> Camera {
+  let mut camera = Camera::new(1.0);
+  camera.projection = Projection::Orthographic { height: 2.0, znear: 0.1, zfar: 100.0 };
+  camera
+}
+
+#[test]
+fn orthographic_rays_are_parallel() {
+  let camera = orthographic();
+  for (x, y) in [(0.0, 0.0), (0.5, -0.5), (1.0, 1.0)] {
+    let ray = camera.screen_ray(x, y);
+    // straight down -z from the near plane at z = 2 - 0.1
+    assert_near_point(ray.origin, [x, y, 1.9]);
+    assert_near_point(Point3::from_vec(ray.direction), [0.0, 0.0, -1.0]);
+  }
+}
+
+#[test]
+fn perspective_rays_spread_out() {
+  let camera = Camera::new(1.0);
+  let centre = camera.screen_ray(0.0, 0.0);
+  assert_near_point(centre.origin, [0.0, 0.0, 1.9]);
+  assert_near_point(Point3::from_vec(centre.direction), [0.0, 0.0, -1.0]);
+  // the top edge is half the 45 degree field of view up
+  let top = camera.screen_ray(0.0, 1.0);
+  assert_near(top.direction.y / -top.direction.z, 22.5f32.to_radians().tan());
+  assert_near(top.direction.x, 0.0);
+}
+
+#[test]
+fn orthographic_culling_has_straight_sides() {
+  let camera = orthographic();
+  let frustum = camera.frustum();
+  // the sides are at x and y = ±1 whatever the distance
+  assert!(frustum.intersects_sphere(Point3::new(0.9, 0.0, 0.0), 0.05));
+  assert!(frustum.intersects_sphere(Point3::new(1.05, 0.0, 0.0), 0.1));
+  assert!(!frustum.intersects_sphere(Point3::new(1.2, 0.0, 0.0), 0.1));
+  assert!(!frustum.intersects_sphere(Point3::new(0.0, -1.2, -50.0), 0.1));
+  // past the far plane at z = 2 - 100
+  assert!(!frustum.intersects_sphere(Point3::new(0.0, 0.0, -99.0), 0.5));
+  // behind the near plane at z = 1.9
+  assert!(!frustum.intersects_sphere(Point3::new(0.0, 0.0, 2.5), 0.5));
+
+  // 22 units away the perspective frustum is 2 * 22 * tan(22.5°) ≈ 18 wide
+  let point = Point3::new(5.0, 0.0, -20.0);
+  assert!(Camera::new(1.0).frustum().intersects_sphere(point, 0.1));
+  assert!(!frustum.intersects_sphere(point, 0.1));
+}
+
+#[test]
+fn zoom_scales_the_orthographic_height() {
+  let mut camera = orthographic();
+  camera.zoom(0.5);
+  assert_eq!(camera.projection, Projection::Orthographic { height: 1.0, znear: 0.1, zfar: 100.0 });
+  assert_eq!(camera.eye, Point3::new(0.0, 0.0, 2.0));
+
+  let mut camera = Camera::new(1.0);
+  camera.zoom(0.5);
+  assert_eq!(camera.eye, Point3::new(0.0, 0.0, 1.0));
+}
+
+#[test]
+fn toggling_keeps_the_target_the_same_size() {
+  let mut camera = Camera::new(1.0);
+  let perspective = camera.build_view_projection_matrix();
+  camera.toggle_projection(Duration::from_millis(300));
+  match camera.projection {
+    Projection::Orthographic { height, .. } => assert_near(height, 4.0 * 22.5f32.to_radians().tan()),
+    projection => panic!("{:?}", projection),
+  }
+  let orthographic = camera.projection.matrix(1.0) * Matrix4::look_at_rh(camera.eye, camera.target, camera.up);
+  // a point at the target's distance lands on the same spot either way
+  let ndc_x = |m: Matrix4<f32>| {
+    let clip = m * Vector4::new(0.3, 0.0, 0.0, 1.0);
+    clip.x / clip.w
+  };
+  assert_near(ndc_x(perspective), ndc_x(orthographic));
+
+  // halfway through it's neither
+  assert!(camera.is_animating());
+  camera.update(Duration::from_millis(150));
+  let halfway = camera.build_view_projection_matrix();
+  assert_ne!(halfway, perspective);
+  assert_ne!(halfway, orthographic);
+  camera.update(Duration::from_millis(150));
+  assert!(!camera.is_animating());
+  assert_eq!(camera.build_view_projection_matrix(), orthographic);
+
+  // and back
+  camera.toggle_projection(Duration::ZERO);
+  match camera.projection {
+    Projection::Perspective { fovy, .. } => assert_near(fovy, 45.0),
+    projection => panic!("{:?}", projection),
+  }
+}
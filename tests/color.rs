@@ -0,0 +1,33 @@
+mod common;
+
+use common::{assert_close, pixel, quad, render, QUAD_INDICES, HEIGHT, WIDTH};
+use sotrh::{SrgbEncoding, State};
+
+fn draw_gradient(state: &mut State) -> Vec<u8> {
+  // one corner per channel so the quad covers a range of values
+  let mut vertices = quad(0.7, 0.0, [0.0; 3]);
+  let colors = [[0.02, 0.3, 0.9], [0.9, 0.02, 0.3], [0.3, 0.9, 0.02], [0.5, 0.5, 0.5]];
+  for (vertex, color) in vertices.iter_mut().zip(colors) {
+    vertex.color = color;
+  }
+  state.set_indexed_mesh(&vertices, &QUAD_INDICES).unwrap();
+  render(state)
+}
+
+// shader-side encoding on a plain target matches the hardware encoding of
+// an sRGB target within one step
+#[test]
+fn shader_encoding_matches_srgb_target() {
+  let Some(mut srgb) = common::headless() else { return };
+  let Some(mut fallback) = common::headless_without_srgb() else { return };
+  assert_eq!(srgb.color_space_info().encoding, SrgbEncoding::SurfaceFormat);
+  assert_eq!(fallback.color_space_info().encoding, SrgbEncoding::Shader);
+
+  let expected = draw_gradient(&mut srgb);
+  let actual = draw_gradient(&mut fallback);
+  for y in (0..HEIGHT).step_by(3) {
+    for x in (0..WIDTH).step_by(3) {
+      assert_close(pixel(&actual, x, y), pixel(&expected, x, y), 1);
+    }
+  }
+}
@@ -0,0 +1,84 @@
+// Shared setup for the headless pixel tests. Not every test binary uses
+// every helper.
+#![allow(dead_code)]
+
+use sotrh::{State, StateError, StateOptions, Vertex};
+
+pub const WIDTH: u32 = 64;
+pub const HEIGHT: u32 = 64;
+
+// A headless State on any backend, or None when the machine has no adapter
+// at all, so GPU-less CI skips the pixel tests instead of failing them.
+// Any other setup error fails the test.
+pub fn headless() -> Option<State> {
+  headless_with(StateOptions::default())
+}
+
+pub fn headless_with(options: StateOptions) -> Option<State> {
+  let options = options.backends(wgpu::Backends::all());
+  skip_without_adapter(State::new_headless_with_options(WIDTH, HEIGHT, options))
+}
+
+// the WebGL color path, see State::new_headless_without_srgb()
+pub fn headless_without_srgb() -> Option<State> {
+  let options = StateOptions::default().backends(wgpu::Backends::all());
+  skip_without_adapter(State::new_headless_without_srgb(WIDTH, HEIGHT, options))
+}
+
+fn skip_without_adapter(result: Result<State, StateError>) -> Option<State> {
+  match result {
+    Ok(state) => Some(state),
+    Err(err @ StateError::NoAdapter { .. }) => {
+      eprintln!("skipping: {}", err);
+      None
+    }
+    Err(err) => panic!("{}", err),
+  }
+}
+
+// renders one frame and reads it back as RGBA8 rows
+pub fn render(state: &mut State) -> Vec<u8> {
+  state.render().expect("headless render failed");
+  state.read_pixels().expect("read_pixels failed")
+}
+
+pub fn pixel(pixels: &[u8], x: u32, y: u32) -> [u8; 4] {
+  let i = ((y * WIDTH + x) * 4) as usize;
+  pixels[i..i + 4].try_into().unwrap()
+}
+
+// how many pixels differ from the clear color, i.e. were drawn over
+pub fn covered(pixels: &[u8]) -> usize {
+  let clear = pixel(pixels, 0, 0);
+  pixels.chunks(4).filter(|p| *p != clear).count()
+}
+
+// every channel within `tolerance` of the expected sRGB value
+pub fn assert_close(actual: [u8; 4], expected: [u8; 4], tolerance: u8) {
+  let close = actual.iter().zip(expected).all(|(a, e)| a.abs_diff(e) <= tolerance);
+  assert!(close, "pixel {:?} isn't within {} of {:?}", actual, tolerance, expected);
+}
+
+// linear 0..1 to the 8 bit sRGB value the hardware encoding produces
+pub fn srgb(c: f64) -> u8 {
+  let encoded = if c <= 0.0031308 { c * 12.92 } else { 1.055 * c.powf(1.0 / 2.4) - 0.055 };
+  (encoded * 255.0).round() as u8
+}
+
+// square of side 2 * half facing the camera at depth z, counter-clockwise
+pub fn quad(half: f32, z: f32, color: [f32; 3]) -> [Vertex; 4] {
+  [
+    Vertex::new([-half, half, z], color),
+    Vertex::new([-half, -half, z], color),
+    Vertex::new([half, -half, z], color),
+    Vertex::new([half, half, z], color),
+  ]
+}
+
+pub const QUAD_INDICES: [u16; 6] = [0, 1, 2, 0, 2, 3];
+
+// the same square as a plain triangle list
+pub fn quad_triangles(half: f32, z: f32, color: [f32; 3]) -> Vec<Vertex> {
+  let corners = quad(half, z, color);
+  QUAD_INDICES.iter().map(|&i| corners[i as usize]).collect()
+}
@@ -0,0 +1,106 @@
+use sotrh::{GpuContext, StateError};
+
+// None without an adapter or compute support, so GPU-less CI skips these
+fn compute() -> Option<GpuContext> {
+  match GpuContext::new_compute() {
+    Ok(context) => Some(context),
+    Err(err @ (StateError::NoAdapter { .. } | StateError::NoComputeShaders { .. })) => {
+      eprintln!("skipping: {}", err);
+      None
+    }
+    Err(err) => panic!("{}", err),
+  }
+}
+
+const DOUBLE: &str = "
+@group(0) @binding(0)
+var<storage, read_write> values: array<u32>;
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+  if id.x < arrayLength(&values) {
+    values[id.x] = values[id.x] * 2u;
+  }
+}
+";
+
+#[test]
+fn doubling_a_buffer() {
+  let Some(context) = compute() else { return };
+  let values: Vec<u32> = (0..100).collect();
+  let buffer = context.create_storage_buffer("Values", &values).unwrap();
+  let pipeline = context.create_compute_pipeline("Double", DOUBLE, "main");
+  let bind_group = context.bind_buffers(&pipeline, &[&buffer]).unwrap();
+
+  context.dispatch(&pipeline, &bind_group, [2, 1, 1]);
+  let doubled: Vec<u32> = context.read_buffer(&buffer).unwrap();
+  assert_eq!(doubled, values.iter().map(|v| v * 2).collect::<Vec<_>>());
+}
+
+#[test]
+fn doubling_through_a_readback_ring() {
+  let Some(context) = compute() else { return };
+  let values: Vec<u32> = (0..64).collect();
+  let buffer = context.create_storage_buffer("Values", &values).unwrap();
+  let pipeline = context.create_compute_pipeline("Double", DOUBLE, "main");
+  let bind_group = context.bind_buffers(&pipeline, &[&buffer]).unwrap();
+  let mut ring = context.create_readback_ring::<u32>(2, 256).unwrap();
+
+  let id = context.dispatch_with_readback(&pipeline, &bind_group, [1, 1, 1], &mut ring, &buffer, 256).unwrap();
+  let mut received = None;
+  for _ in 0..1000 {
+    if let Some(readback) = context.poll_readbacks(&mut ring).next() {
+      received = Some(readback);
+      break;
+    }
+    std::thread::sleep(std::time::Duration::from_millis(1));
+  }
+  let readback = received.expect("the readback never arrived");
+  assert_eq!(readback.id, id);
+  assert_eq!(readback.data, values.iter().map(|v| v * 2).collect::<Vec<_>>());
+}
+
+#[test]
+fn doubling_a_texture() {
+  let Some(context) = compute() else { return };
+  let shader = "
+@group(0) @binding(0)
+var input: texture_2d<u32>;
+@group(0) @binding(1)
+var output: texture_storage_2d<r32uint, write>;
+
+@compute @workgroup_size(8, 8)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+  let size = textureDimensions(input);
+  if id.x < size.x && id.y < size.y {
+    textureStore(output, id.xy, textureLoad(input, id.xy, 0) * 2u);
+  }
+}
+";
+  // an odd width, so the readback has to strip the row padding
+  let (width, height) = (5, 3);
+  let texels: Vec<u32> = (0..width * height).collect();
+  let input = context.create_storage_texture("Input", width, height, wgpu::TextureFormat::R32Uint).unwrap();
+  let output = context.create_storage_texture("Output", width, height, wgpu::TextureFormat::R32Uint).unwrap();
+  context.write_texture(&input, &texels);
+
+  let pipeline = context.create_compute_pipeline("Double Texture", shader, "main");
+  let input_view = input.create_view(&Default::default());
+  let output_view = output.create_view(&Default::default());
+  let bind_group = context.bind_resources(&pipeline, &[
+    wgpu::BindingResource::TextureView(&input_view),
+    wgpu::BindingResource::TextureView(&output_view),
+  ]);
+  context.dispatch(&pipeline, &bind_group, [1, 1, 1]);
+
+  let doubled: Vec<u32> = context.read_texture(&output).unwrap();
+  assert_eq!(doubled, texels.iter().map(|v| v * 2).collect::<Vec<_>>());
+}
+
+#[test]
+fn oversized_storage_textures_are_an_error() {
+  let Some(context) = compute() else { return };
+  let max = context.device().limits().max_texture_dimension_2d;
+  let err = context.create_storage_texture("Huge", max + 1, 1, wgpu::TextureFormat::R32Uint).unwrap_err();
+  assert_eq!(err.what, "storage texture size");
+}
@@ -0,0 +1,25 @@
+mod common;
+
+use common::{covered, render};
+
+#[test]
+fn lines_are_drawn_until_cleared() {
+  let Some(mut state) = common::headless() else { return };
+  state.set_vertices(&[]).unwrap();
+  // frames before the first line have nothing to upload
+  assert_eq!(covered(&render(&mut state)), 0);
+
+  state.debug().line([-1.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 0.0, 0.0]);
+  state.debug().line([0.0, -1.0, 0.0], [0.0, 1.0, 0.0], [1.0, 0.0, 0.0]);
+  assert!(covered(&render(&mut state)) > common::WIDTH as usize);
+
+  state.debug().clear();
+  assert_eq!(covered(&render(&mut state)), 0);
+
+  // the buffer grows for more lines than the first upload had
+  for i in 0..10 {
+    let y = i as f32 * 0.1 - 0.5;
+    state.debug().line([-1.0, y, 0.0], [1.0, y, 0.0], [0.0, 1.0, 0.0]);
+  }
+  assert!(covered(&render(&mut state)) > 5 * common::WIDTH as usize);
+}
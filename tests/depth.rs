@@ -0,0 +1,69 @@
+mod common;
+
+use common::{assert_close, pixel, quad, render, HEIGHT, WIDTH};
+use sotrh::{DepthBias, RenderMode, StateOptions, Vertex};
+
+const GREEN: [u8; 4] = [0, 255, 0, 255];
+const RED: [u8; 4] = [255, 0, 0, 255];
+
+// a green quad half a unit in front of the origin and a red one behind it,
+// in the given draw order
+fn near_and_far(near_first: bool) -> (Vec<Vertex>, Vec<u16>) {
+  let near = quad(0.3, 0.5, [0.0, 1.0, 0.0]);
+  let far = quad(0.3, -0.5, [1.0, 0.0, 0.0]);
+  let vertices = if near_first { [near, far] } else { [far, near] }.concat();
+  let indices = vec![0, 1, 2, 0, 2, 3, 4, 5, 6, 4, 6, 7];
+  (vertices, indices)
+}
+
+#[test]
+fn near_triangles_occlude_far_ones() {
+  let Some(mut state) = common::headless() else { return };
+  for near_first in [true, false] {
+    let (vertices, indices) = near_and_far(near_first);
+    state.set_indexed_mesh(&vertices, &indices).unwrap();
+    assert_close(pixel(&render(&mut state), WIDTH / 2, HEIGHT / 2), GREEN, 0);
+  }
+}
+
+#[test]
+fn without_depth_the_last_draw_wins() {
+  let Some(mut state) = common::headless_with(StateOptions::default().depth(false)) else { return };
+  let (vertices, indices) = near_and_far(true);
+  state.set_indexed_mesh(&vertices, &indices).unwrap();
+  assert_close(pixel(&render(&mut state), WIDTH / 2, HEIGHT / 2), RED, 0);
+}
+
+// the depth compare is Less, so at equal depth the first draw stays
+#[test]
+fn equal_depth_keeps_the_first_draw() {
+  let Some(mut state) = common::headless() else { return };
+  state.set_vertices(&[]).unwrap();
+  state.create_object(&quad(0.3, 0.0, [0.0, 1.0, 0.0]), &common::QUAD_INDICES).unwrap();
+  state.create_object(&quad(0.3, 0.0, [1.0, 0.0, 0.0]), &common::QUAD_INDICES).unwrap();
+  assert_close(pixel(&render(&mut state), WIDTH / 2, HEIGHT / 2), GREEN, 0);
+}
+
+// the same coplanar quads, but the later one is pulled towards the camera
+#[test]
+fn depth_bias_wins_at_equal_depth() {
+  let Some(mut state) = common::headless() else { return };
+  state.set_vertices(&[]).unwrap();
+  state.create_object(&quad(0.3, 0.0, [0.0, 1.0, 0.0]), &common::QUAD_INDICES).unwrap();
+  let decal = state.create_object(&quad(0.3, 0.0, [1.0, 0.0, 0.0]), &common::QUAD_INDICES).unwrap();
+  assert!(state.set_object_depth_bias(decal, DepthBias::DECAL));
+  let frame = render(&mut state);
+  for (x, y) in [(WIDTH / 2, HEIGHT / 2), (WIDTH / 2 - 5, HEIGHT / 2 + 5), (WIDTH / 2 + 5, HEIGHT / 2 - 5)] {
+    assert_close(pixel(&frame, x, y), RED, 0);
+  }
+}
+
+#[test]
+fn depth_bias_keys_the_pipeline() {
+  let plain = RenderMode::Fill.desc();
+  let decal = plain.with_depth_bias(DepthBias::DECAL);
+  assert_ne!(plain, decal);
+  assert_eq!(decal, RenderMode::Fill.desc().with_depth_bias(DepthBias::DECAL));
+  // points can't be biased
+  assert_eq!(RenderMode::Points.desc().with_depth_bias(DepthBias::DECAL), RenderMode::Points.desc());
+}
@@ -0,0 +1,166 @@
+mod common;
+
+use cgmath::Point3;
+use common::{assert_close, headless, headless_with, pixel, quad, render, srgb, QUAD_INDICES, WIDTH, HEIGHT};
+use half::f16;
+use sotrh::{ColorSpace, HdrImage, LitVertex, Material, State, StateOptions, TextureId};
+
+#[test]
+fn texels_above_the_f16_range_are_clamped() {
+  let hdr = HdrImage {
+    width: 1,
+    height: 1,
+    pixels: vec![[1.0e6, -1.0e6, 0.5, 1.0]],
+  };
+  assert_eq!(hdr.to_rgba16f(), vec![[f16::MAX, f16::MIN, f16::from_f32(0.5), f16::ONE]]);
+}
+
+// 1x1 PNG of the given texel
+fn solid_texture(state: &mut State, color: [u8; 3], color_space: ColorSpace) -> TextureId {
+  let image = image::RgbaImage::from_pixel(1, 1, image::Rgba([color[0], color[1], color[2], 255]));
+  let mut png = std::io::Cursor::new(Vec::new());
+  image.write_to(&mut png, image::ImageFormat::Png).unwrap();
+  state.load_texture_with(png.get_ref(), color_space).unwrap()
+}
+
+// a quad facing the camera, its normals pointing at it and u along +X
+fn lit_quad(state: &mut State, material: Material) {
+  let normal = [0.0, 0.0, 1.0];
+  let tangent = [1.0, 0.0, 0.0, 1.0];
+  let vertices = [
+    LitVertex::new([-0.5, 0.5, 0.0], normal, [0.0, 0.0], tangent),
+    LitVertex::new([-0.5, -0.5, 0.0], normal, [0.0, 1.0], tangent),
+    LitVertex::new([0.5, -0.5, 0.0], normal, [1.0, 1.0], tangent),
+    LitVertex::new([0.5, 0.5, 0.0], normal, [1.0, 0.0], tangent),
+  ];
+  state.set_lit_mesh(&vertices, &[0u16, 1, 2, 0, 2, 3][..], material).unwrap();
+}
+
+// equirectangular, .hdr encoded
+fn hdr_bytes(width: usize, height: usize, radiance: impl Fn(usize) -> [f32; 3]) -> Vec<u8> {
+  let texels: Vec<_> = (0..width * height).map(|i| image::Rgb(radiance(i % width))).collect();
+  let mut hdr = Vec::new();
+  image::codecs::hdr::HdrEncoder::new(&mut hdr).encode(&texels, width, height).unwrap();
+  hdr
+}
+
+fn with_environment_maps(state: &State) -> bool {
+  let supported = state.capability_report().environment_maps;
+  if !supported {
+    eprintln!("skipping: no HDR environment maps");
+  }
+  supported
+}
+
+#[test]
+fn lit_mesh_without_environment_shows_its_texture() {
+  let Some(mut state) = headless() else { return };
+  let texture = solid_texture(&mut state, [200, 100, 50], ColorSpace::Srgb);
+  lit_quad(&mut state, Material::new(texture));
+
+  let pixels = render(&mut state);
+  assert_close(pixel(&pixels, WIDTH / 2, HEIGHT / 2), [200, 100, 50, 255], 2);
+}
+
+#[test]
+fn lit_mesh_is_lit_by_the_irradiance() {
+  let Some(mut state) = headless() else { return };
+  if !with_environment_maps(&state) {
+    return;
+  }
+  // a constant environment convolves to the same irradiance
+  let radiance = [0.25, 0.5, 1.0];
+  state.set_environment(&hdr_bytes(8, 4, |_| radiance)).unwrap();
+  let texture = solid_texture(&mut state, [255, 255, 255], ColorSpace::Srgb);
+  lit_quad(&mut state, Material::new(texture));
+
+  let pixels = render(&mut state);
+  let expected = radiance.map(|c| srgb(c as f64));
+  assert_close(pixel(&pixels, WIDTH / 2, HEIGHT / 2), [expected[0], expected[1], expected[2], 255], 6);
+}
+
+#[test]
+fn normal_map_turns_the_normal() {
+  let Some(mut state) = headless() else { return };
+  if !with_environment_maps(&state) {
+    return;
+  }
+  // red towards +X, blue towards -X; the middle half of an equirect map is +X
+  let red_towards_x = |column: usize| if (4..12).contains(&column) { [1.0, 0.0, 0.0] } else { [0.0, 0.0, 1.0] };
+  state.set_environment(&hdr_bytes(16, 8, red_towards_x)).unwrap();
+  let white = solid_texture(&mut state, [255, 255, 255], ColorSpace::Srgb);
+
+  // facing +Z the quad sees as much red as blue
+  lit_quad(&mut state, Material::new(white));
+  let [r, _, b, _] = pixel(&render(&mut state), WIDTH / 2, HEIGHT / 2);
+  assert!(r.abs_diff(b) <= 8, "{} red, {} blue", r, b);
+
+  // a tangent space normal along the tangent, i.e. +X
+  let normal_map = solid_texture(&mut state, [255, 128, 128], ColorSpace::Linear);
+  lit_quad(&mut state, Material::new(white).with_normal_map(normal_map));
+  let [r, _, b, _] = pixel(&render(&mut state), WIDTH / 2, HEIGHT / 2);
+  assert!(r > 200 && b < 60, "{} red, {} blue", r, b);
+}
+
+#[test]
+fn skybox_fills_an_empty_scene() {
+  let Some(mut state) = headless() else { return };
+  if !with_environment_maps(&state) {
+    return;
+  }
+  state.set_vertices(&[]).unwrap();
+  let clear = pixel(&render(&mut state), WIDTH / 2, HEIGHT / 2);
+  let radiance = [0.25, 0.5, 1.0];
+  state.set_environment(&hdr_bytes(8, 4, |_| radiance)).unwrap();
+
+  let pixels = render(&mut state);
+  let expected = radiance.map(|c| srgb(c as f64));
+  for (x, y) in [(0, 0), (WIDTH / 2, HEIGHT / 2), (WIDTH - 1, HEIGHT - 1)] {
+    let sky = pixel(&pixels, x, y);
+    assert_ne!(sky, clear);
+    assert_close(sky, [expected[0], expected[1], expected[2], 255], 2);
+  }
+}
+
+// red towards +X, blue everywhere else
+fn red_towards_x(column: usize) -> [f32; 3] {
+  if (6..10).contains(&column) { [1.0, 0.0, 0.0] } else { [0.0, 0.0, 1.0] }
+}
+
+#[test]
+fn skybox_turns_with_the_camera() {
+  let Some(mut state) = headless() else { return };
+  if !with_environment_maps(&state) {
+    return;
+  }
+  state.set_vertices(&[]).unwrap();
+  state.set_environment(&hdr_bytes(16, 8, red_towards_x)).unwrap();
+  // the default camera looks down -Z
+  let [r, _, b, _] = pixel(&render(&mut state), WIDTH / 2, HEIGHT / 2);
+  assert!(r < 60 && b > 200, "{} red, {} blue", r, b);
+
+  // moving the eye doesn't move the sky, turning towards +X does
+  let camera = state.camera_mut();
+  camera.eye = Point3::new(5.0, 0.0, 5.0);
+  camera.target = Point3::new(5.0, 0.0, 0.0);
+  let [r, _, b, _] = pixel(&render(&mut state), WIDTH / 2, HEIGHT / 2);
+  assert!(r < 60 && b > 200, "{} red, {} blue", r, b);
+  state.camera_mut().target = Point3::new(10.0, 0.0, 5.0);
+  let [r, _, b, _] = pixel(&render(&mut state), WIDTH / 2, HEIGHT / 2);
+  assert!(r > 200 && b < 60, "{} red, {} blue", r, b);
+}
+
+#[test]
+fn meshes_cover_the_skybox() {
+  for depth in [true, false] {
+    let Some(mut state) = headless_with(StateOptions::default().depth(depth)) else { return };
+    if !with_environment_maps(&state) {
+      return;
+    }
+    state.set_environment(&hdr_bytes(8, 4, |_| [0.0, 0.0, 1.0])).unwrap();
+    state.set_indexed_mesh(&quad(0.3, 0.0, [1.0, 0.0, 0.0]), &QUAD_INDICES).unwrap();
+    let pixels = render(&mut state);
+    assert_close(pixel(&pixels, WIDTH / 2, HEIGHT / 2), [255, 0, 0, 255], 0);
+    assert_close(pixel(&pixels, 0, 0), [0, 0, 255, 255], 2);
+  }
+}
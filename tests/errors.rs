@@ -0,0 +1,105 @@
+use sotrh::{ExceedsLimit, RenderError, StateError, UnsupportedMode};
+
+#[test]
+fn no_adapter_lists_the_backends() {
+  let err = StateError::NoAdapter { backends: wgpu::Backends::VULKAN | wgpu::Backends::GL };
+  assert_eq!(err.to_string(), "No compatible GPU adapter found (tried backends: Backends(VULKAN | GL))");
+}
+
+#[test]
+fn missing_feature_names_the_features() {
+  let err = StateError::MissingFeature(wgpu::Features::POLYGON_MODE_LINE);
+  assert_eq!(err.to_string(), "The adapter doesn't support the required features Features(POLYGON_MODE_LINE)");
+}
+
+#[test]
+fn no_compute_shaders_names_the_adapter() {
+  let err = StateError::NoComputeShaders { adapter: "llvmpipe".to_string(), backend: wgpu::Backend::Gl };
+  assert_eq!(err.to_string(), "llvmpipe (Gl) does not support compute shaders");
+}
+
+#[test]
+fn no_supported_format() {
+  assert_eq!(StateError::NoSupportedFormat.to_string(), "The surface supports no texture formats on this adapter");
+}
+
+#[test]
+fn target_too_large_passes_the_limit_through() {
+  let limit = ExceedsLimit {
+    what: "Headless target size",
+    requested: 20000,
+    limit: 8192,
+    suggestion: "downscale the image or split it into tiles",
+  };
+  let err = StateError::from(limit.clone());
+  assert_eq!(
+    err.to_string(),
+    "Headless target size of 20000 exceeds the device limit of 8192: downscale the image or split it into tiles",
+  );
+  let source = std::error::Error::source(&err).expect("the limit is the source");
+  assert_eq!(source.to_string(), limit.to_string());
+}
+
+#[test]
+fn unsupported_surface_usages_list_what_is_missing_and_supported() {
+  let err = StateError::UnsupportedSurfaceUsages {
+    requested: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+    supported: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+  };
+  assert_eq!(
+    err.to_string(),
+    "The surface doesn't support the usages TextureUsages(COPY_SRC) (supported: TextureUsages(TEXTURE_BINDING | RENDER_ATTACHMENT))",
+  );
+}
+
+// needs an adapter to get a real RequestDeviceError, so it's skipped without one
+#[test]
+fn device_request_names_the_limits_preset() {
+  let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
+  let Some(adapter) = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default())) else {
+    eprintln!("skipping: no adapter");
+    return;
+  };
+  // no adapter supports this, so the request always fails
+  let limits = wgpu::Limits {
+    max_bind_groups: u32::MAX,
+    ..wgpu::Limits::downlevel_webgl2_defaults()
+  };
+  let source = pollster::block_on(adapter.request_device(
+    &wgpu::DeviceDescriptor {
+      required_limits: limits.clone(),
+      ..Default::default()
+    },
+    None,
+  )).expect_err("u32::MAX bind groups can't be supported");
+
+  let message = StateError::DeviceRequest { source, limits: Box::new(limits) }.to_string();
+  assert!(message.starts_with("Failed to create a device with custom limits (max_texture_dimension_2d 2048, max_bind_groups 4294967295, "), "{}", message);
+
+  // the preset itself fits, so fail through a feature no adapter has all of
+  let source = pollster::block_on(adapter.request_device(
+    &wgpu::DeviceDescriptor {
+      required_features: wgpu::Features::all(),
+      required_limits: wgpu::Limits::downlevel_webgl2_defaults(),
+      ..Default::default()
+    },
+    None,
+  )).expect_err("no adapter has every feature");
+  let err = StateError::DeviceRequest { source, limits: Box::new(wgpu::Limits::downlevel_webgl2_defaults()) };
+  assert!(err.to_string().starts_with("Failed to create a device with the WebGL2 downlevel limits (max_texture_dimension_2d 2048, max_bind_groups 4, "), "{}", err);
+}
+
+#[test]
+fn render_errors() {
+  assert_eq!(RenderError::Suspended.to_string(), "Rendering is suspended until the surface is resumed");
+  assert_eq!(RenderError::from(wgpu::SurfaceError::Lost).to_string(), wgpu::SurfaceError::Lost.to_string());
+}
+
+#[test]
+fn unsupported_present_mode_lists_the_supported_ones() {
+  let err = UnsupportedMode {
+    mode: wgpu::PresentMode::Mailbox,
+    supported: vec![wgpu::PresentMode::Fifo, wgpu::PresentMode::Immediate],
+  };
+  assert_eq!(err.to_string(), "Present mode Mailbox isn't supported by the surface (supported: [Fifo, Immediate])");
+}
@@ -0,0 +1,67 @@
+mod common;
+
+use common::quad_triangles;
+use sotrh::{FrameDump, Instance, RenderMode, State};
+
+fn dump_next_frame(state: &mut State) -> FrameDump {
+  state.capture_next_frame_dump();
+  state.render().unwrap();
+  state.debug_dump_frame().unwrap().clone()
+}
+
+fn uploaded(dump: &FrameDump, label: &str) -> u64 {
+  dump.uploads.iter().filter(|upload| upload.label == label).map(|upload| upload.bytes).sum()
+}
+
+#[test]
+fn primitives_follow_the_topology() {
+  let Some(mut state) = common::headless() else { return };
+  state.set_vertices(&quad_triangles(0.5, 0.0, [1.0, 1.0, 1.0])).unwrap();
+  state.debug().line([0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 0.0, 0.0]);
+  let totals = dump_next_frame(&mut state).totals();
+  assert_eq!((totals.triangles, totals.lines, totals.points), (2, 1, 0));
+
+  state.set_render_mode(RenderMode::Points).unwrap();
+  let totals = dump_next_frame(&mut state).totals();
+  assert_eq!((totals.triangles, totals.lines, totals.points), (0, 1, 6));
+}
+
+#[test]
+fn uploads_between_frames_are_counted() {
+  let Some(mut state) = common::headless() else { return };
+  // whatever the first frame uploads on its own
+  dump_next_frame(&mut state);
+
+  let vertices = quad_triangles(0.5, 0.0, [1.0, 1.0, 1.0]);
+  state.set_vertices(&vertices).unwrap();
+  state.update_vertices(&vertices).unwrap();
+  state.set_instances(&[Instance::default(); 3]).unwrap();
+  state.create_object(&vertices, &[] as &[u16]).unwrap();
+  let dump = dump_next_frame(&mut state);
+
+  let vertex_bytes = std::mem::size_of_val(vertices.as_slice()) as u64;
+  assert_eq!(uploaded(&dump, "Vertex Buffer"), 2 * vertex_bytes);
+  assert!(uploaded(&dump, "Instance Buffer") > 0);
+  assert_eq!(uploaded(&dump, "Object Vertex Buffer"), vertex_bytes);
+  assert!(dump.totals().bytes_uploaded >= 3 * vertex_bytes);
+
+  // nothing new was written
+  let dump = dump_next_frame(&mut state);
+  assert_eq!(dump.totals().bytes_uploaded, 0);
+}
+
+#[test]
+fn empty_meshes_are_not_dumped() {
+  let Some(mut state) = common::headless() else { return };
+  state.set_vertices(&[]).unwrap();
+  state.create_object(&[], &[] as &[u16]).unwrap();
+  state.create_object(&quad_triangles(0.5, 0.0, [1.0, 1.0, 1.0]), &[] as &[u16]).unwrap();
+  let dump = dump_next_frame(&mut state);
+
+  let pass = &dump.passes[0];
+  let meshes: Vec<_> = pass.draws.iter().map(|draw| draw.mesh.as_str()).collect();
+  assert_eq!(meshes, ["Object Vertex Buffer"]);
+  assert_eq!(pass.draws[0].vertex_count, 6);
+  // only the object pipeline and the one non-empty object's bind group
+  assert_eq!((pass.pipeline_switches, pass.bind_group_switches), (1, 1));
+}
@@ -0,0 +1,126 @@
+mod common;
+
+use common::{assert_close, pixel, render, srgb, HEIGHT, WIDTH};
+use sotrh::RenderError;
+
+// the demo polygon is drawn in the middle over the clear color
+#[test]
+fn renders_the_demo_polygon() {
+  let Some(mut state) = common::headless() else { return };
+  let pixels = render(&mut state);
+  assert_eq!(pixels.len(), (WIDTH * HEIGHT * 4) as usize);
+
+  // clear color 0.1, 0.2, 0.3 and the polygon's 0.5, 0.0, 0.5
+  assert_close(pixel(&pixels, 0, 0), [srgb(0.1), srgb(0.2), srgb(0.3), 255], 1);
+  assert_close(pixel(&pixels, WIDTH / 2, HEIGHT / 2), [srgb(0.5), 0, srgb(0.5), 255], 1);
+}
+
+#[test]
+fn read_pixels_follows_resize() {
+  let Some(mut state) = common::headless() else { return };
+  state.resize(winit::dpi::PhysicalSize::new(100, 30));
+  state.render().unwrap();
+  // 100 * 4 bytes per row needs padding to 512 for the copy
+  assert_eq!(state.read_pixels().unwrap().len(), 100 * 30 * 4);
+}
+
+// the time spent minimized isn't reported as a hitch once frames resume
+#[test]
+fn minimizing_pauses_frame_pacing() {
+  let Some(mut state) = common::headless() else { return };
+  let hitches = std::rc::Rc::new(std::cell::Cell::new(0));
+  let counter = hitches.clone();
+  state
+    .frame_pacing_mut()
+    .set_hitch_callback(std::time::Duration::from_millis(100), move |_| counter.set(counter.get() + 1));
+
+  state.render().unwrap();
+  state.resize(winit::dpi::PhysicalSize::new(0, 0));
+  state.render().unwrap();
+  std::thread::sleep(std::time::Duration::from_millis(300));
+  state.resize(winit::dpi::PhysicalSize::new(WIDTH, HEIGHT));
+  state.render().unwrap();
+  assert_eq!(hitches.get(), 0);
+}
+
+#[test]
+fn surface_usages_are_kept_across_resizes() {
+  let options = sotrh::StateOptions::default().surface_usages(wgpu::TextureUsages::COPY_DST);
+  let Some(mut state) = common::headless_with(options) else { return };
+  assert!(state.surface_usages().contains(wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_DST));
+  state.resize(winit::dpi::PhysicalSize::new(32, 16));
+  state.render().unwrap();
+  assert!(state.surface_usages().contains(wgpu::TextureUsages::COPY_DST));
+}
+
+#[test]
+fn unsupported_surface_usages_are_an_error() {
+  let options = sotrh::StateOptions::default()
+    .backends(wgpu::Backends::all())
+    .surface_usages(wgpu::TextureUsages::STORAGE_BINDING);
+  match sotrh::State::new_headless_with_options(WIDTH, HEIGHT, options) {
+    Err(sotrh::StateError::UnsupportedSurfaceUsages { requested, .. }) => {
+      assert!(requested.contains(wgpu::TextureUsages::STORAGE_BINDING));
+    }
+    Err(sotrh::StateError::NoAdapter { .. }) => eprintln!("skipping: no adapter"),
+    Err(err) => panic!("unexpected error: {}", err),
+    Ok(_) => panic!("STORAGE_BINDING isn't allowed on the target"),
+  }
+}
+
+// recover() reconfigures from the stored size, not the one the target had
+#[test]
+fn outdated_target_is_reconfigured_with_the_stored_size() {
+  let Some(mut state) = common::headless() else { return };
+  state.resize(winit::dpi::PhysicalSize::new(40, 24));
+  assert!(state.recover(RenderError::Surface(wgpu::SurfaceError::Outdated)));
+  let pixels = render(&mut state);
+  assert_eq!(pixels.len(), 40 * 24 * 4);
+  assert_eq!(state.size(), winit::dpi::PhysicalSize::new(40, 24));
+}
+
+#[test]
+fn lost_target_is_recreated() {
+  let Some(mut state) = common::headless() else { return };
+  render(&mut state);
+  assert!(state.recover(RenderError::Surface(wgpu::SurfaceError::Lost)));
+  let pixels = render(&mut state);
+  assert_eq!(pixels.len(), (WIDTH * HEIGHT * 4) as usize);
+  assert_ne!(common::covered(&pixels), 0);
+}
+
+// the timestamps come back through a ReadbackRing a frame or two later
+#[test]
+fn gpu_time_is_read_back_when_supported() {
+  let Some(mut state) = common::headless() else { return };
+  if !state.enabled_features().contains(wgpu::Features::TIMESTAMP_QUERY) {
+    assert_eq!(state.last_frame_stats().and_then(|stats| stats.gpu_time), None);
+    return;
+  }
+  let timed = (0..10).any(|_| {
+    state.render().unwrap();
+    state.device().poll(wgpu::Maintain::Wait);
+    state.poll_outstanding();
+    state.last_frame_stats().and_then(|stats| stats.gpu_time).is_some()
+  });
+  assert!(timed, "no frame was timed");
+}
+
+#[test]
+fn captured_frame_matches_the_rendered_one() {
+  let Some(mut state) = common::headless() else { return };
+  let path = std::env::temp_dir().join(format!("sotrh-capture-{}.png", std::process::id()));
+  state.capture_frame(&path).unwrap();
+  let frame = render(&mut state);
+  let result = (0..10).find_map(|_| {
+    state.device().poll(wgpu::Maintain::Wait);
+    state.poll_outstanding();
+    state.take_capture_result()
+  });
+  assert_eq!(result.expect("the capture never finished").unwrap(), path);
+
+  let png = image::open(&path).unwrap().to_rgba8();
+  std::fs::remove_file(&path).unwrap();
+  assert_eq!(png.dimensions(), (WIDTH, HEIGHT));
+  assert_eq!(png.into_raw(), frame);
+}
@@ -0,0 +1,52 @@
+mod common;
+
+use common::{assert_close, pixel, quad, render, QUAD_INDICES, HEIGHT, WIDTH};
+
+const GREEN: [u8; 4] = [0, 255, 0, 255];
+
+// checks the middle and a point inside each of the two triangles
+fn assert_quad_drawn(pixels: &[u8]) {
+  for (x, y) in [(WIDTH / 2, HEIGHT / 2), (WIDTH / 2 - 10, HEIGHT / 2 + 10), (WIDTH / 2 + 10, HEIGHT / 2 - 10)] {
+    assert_close(pixel(pixels, x, y), GREEN, 0);
+  }
+  assert_ne!(pixel(pixels, 2, 2), GREEN);
+}
+
+#[test]
+fn quad_from_16_bit_indices() {
+  let Some(mut state) = common::headless() else { return };
+  state.set_indexed_mesh(&quad(0.5, 0.0, [0.0, 1.0, 0.0]), &QUAD_INDICES).unwrap();
+  assert_eq!(state.num_vertices(), 4);
+  assert_eq!(state.num_indices(), Some(6));
+  assert_quad_drawn(&render(&mut state));
+}
+
+#[test]
+fn quad_from_32_bit_indices() {
+  let Some(mut state) = common::headless() else { return };
+  let indices = QUAD_INDICES.map(u32::from);
+  state.set_indexed_mesh(&quad(0.5, 0.0, [0.0, 1.0, 0.0]), &indices).unwrap();
+  assert_eq!(state.num_indices(), Some(6));
+  assert_quad_drawn(&render(&mut state));
+}
+
+// an odd number of u16 indices leaves half a word, which write_buffer() can't take
+#[test]
+fn odd_16_bit_index_count() {
+  let Some(mut state) = common::headless() else { return };
+  state.set_indexed_mesh(&quad(0.5, 0.0, [0.0, 1.0, 0.0]), &QUAD_INDICES[..3]).unwrap();
+  assert_eq!(state.num_indices(), Some(3));
+  let pixels = render(&mut state);
+  // only the first triangle, below the diagonal
+  assert_close(pixel(&pixels, WIDTH / 2 - 10, HEIGHT / 2 + 10), GREEN, 0);
+  assert_ne!(pixel(&pixels, WIDTH / 2 + 10, HEIGHT / 2 - 10), GREEN);
+}
+
+#[test]
+fn empty_indices_draw_the_vertices_directly() {
+  let Some(mut state) = common::headless() else { return };
+  let triangles = common::quad_triangles(0.5, 0.0, [0.0, 1.0, 0.0]);
+  state.set_indexed_mesh(&triangles, &[] as &[u16]).unwrap();
+  assert_eq!(state.num_indices(), None);
+  assert_quad_drawn(&render(&mut state));
+}
@@ -0,0 +1,143 @@
+mod common;
+
+use cgmath::{Quaternion, Vector3};
+use common::{pixel, render, HEIGHT, WIDTH};
+use sotrh::{Instance, InstanceRaw, State, Vertex};
+
+// a small triangle around the origin, offset by each instance
+fn small_triangle() -> [Vertex; 3] {
+  let color = [1.0, 1.0, 1.0];
+  [
+    Vertex::new([0.0, 0.02, 0.0], color),
+    Vertex::new([-0.02, -0.02, 0.0], color),
+    Vertex::new([0.02, -0.02, 0.0], color),
+  ]
+}
+
+// 10x10 instances spread over -0.6..0.6, inside the default camera's view
+fn grid() -> Vec<Instance> {
+  (0..100).map(|i| {
+    let position = Vector3::new((i % 10) as f32 * 0.13 - 0.585, (i / 10) as f32 * 0.13 - 0.585, 0.0);
+    Instance::new(position, Quaternion::new(1.0, 0.0, 0.0, 0.0))
+  }).collect()
+}
+
+// connected groups of drawn pixels
+fn count_blobs(pixels: &[u8]) -> usize {
+  let clear = pixel(pixels, 0, 0);
+  let mut seen = vec![false; (WIDTH * HEIGHT) as usize];
+  let mut blobs = 0;
+  for start in 0..WIDTH * HEIGHT {
+    if seen[start as usize] || pixel(pixels, start % WIDTH, start / WIDTH) == clear {
+      continue;
+    }
+    blobs += 1;
+    let mut stack = vec![start];
+    while let Some(i) = stack.pop() {
+      if std::mem::replace(&mut seen[i as usize], true) {
+        continue;
+      }
+      let (x, y) = (i % WIDTH, i / WIDTH);
+      for (nx, ny) in [(x.wrapping_sub(1), y), (x + 1, y), (x, y.wrapping_sub(1)), (x, y + 1)] {
+        if nx < WIDTH && ny < HEIGHT && pixel(pixels, nx, ny) != clear {
+          stack.push(ny * WIDTH + nx);
+        }
+      }
+    }
+  }
+  blobs
+}
+
+#[test]
+fn a_grid_of_100_instances() {
+  let Some(mut state) = common::headless() else { return };
+  state.set_vertices(&small_triangle()).unwrap();
+  let single = common::covered(&render(&mut state));
+  assert!(single > 0);
+
+  state.set_instances(&grid()).unwrap();
+  assert_eq!(state.num_instances(), 100);
+  let pixels = render(&mut state);
+  // at 64x64 neighbours can touch, but far more than one triangle landed
+  assert!(count_blobs(&pixels) > 1, "only one triangle was drawn");
+  assert!(common::covered(&pixels) > single * 20);
+}
+
+#[test]
+fn no_instances_draw_nothing() {
+  let Some(mut state) = common::headless() else { return };
+  state.set_instances(&[]).unwrap();
+  assert_eq!(state.num_instances(), 0);
+  assert_eq!(common::covered(&render(&mut state)), 0);
+}
+
+// bytes the next frame uploads into the instance buffer
+fn instance_upload(state: &mut State) -> u64 {
+  state.capture_next_frame_dump();
+  state.render().unwrap();
+  let dump = state.debug_dump_frame().unwrap();
+  dump.uploads.iter().filter(|upload| upload.label == "Instance Buffer").map(|upload| upload.bytes).sum()
+}
+
+#[test]
+fn only_changed_instances_are_uploaded() {
+  let Some(mut state) = common::headless() else { return };
+  state.set_vertices(&small_triangle()).unwrap();
+  let instances = grid();
+  state.set_instances(&instances).unwrap();
+  let stride = std::mem::size_of::<InstanceRaw>() as u64;
+  assert_eq!(instance_upload(&mut state), 100 * stride);
+  // nothing changed, nothing is uploaded
+  assert_eq!(instance_upload(&mut state), 0);
+
+  // 1% of the instances
+  state.update_instance(42, instances[0]).unwrap();
+  assert_eq!(instance_upload(&mut state), stride);
+
+  // overlapping and adjacent changes are written once
+  state.update_instances_range(10, &instances[..3]).unwrap();
+  state.update_instance(11, instances[5]).unwrap();
+  state.update_instance(13, instances[6]).unwrap();
+  state.update_instance(50, instances[7]).unwrap();
+  assert_eq!(instance_upload(&mut state), 5 * stride);
+}
+
+#[test]
+fn appending_within_capacity_keeps_the_buffer() {
+  let Some(mut state) = common::headless() else { return };
+  state.set_vertices(&small_triangle()).unwrap();
+  state.reserve_instances(200).unwrap();
+  assert!(state.instance_capacity() >= 200);
+  let capacity = state.instance_capacity();
+  let instances = grid();
+  state.update_instances_range(0, &instances[..50]).unwrap();
+  state.update_instances_range(50, &instances[50..]).unwrap();
+  assert_eq!(state.num_instances(), 100);
+  assert_eq!(state.instance_capacity(), capacity);
+  let all = common::covered(&render(&mut state));
+
+  state.truncate_instances(10);
+  assert_eq!(state.num_instances(), 10);
+  assert_eq!(state.instance_capacity(), capacity);
+  let ten = common::covered(&render(&mut state));
+  assert!(ten > 0 && ten < all, "{} of {} pixels", ten, all);
+
+  // past the capacity everything moves to a bigger buffer
+  state.update_instances_range(10, &vec![Instance::default(); capacity as usize]).unwrap();
+  assert!(state.instance_capacity() > capacity);
+  assert_eq!(state.num_instances(), capacity + 10);
+}
+
+#[test]
+fn updated_instances_move_on_screen() {
+  let Some(mut state) = common::headless() else { return };
+  state.set_vertices(&small_triangle()).unwrap();
+  state.set_instances(&grid()).unwrap();
+  render(&mut state);
+  // the bottom left instance, moved to the centre of the frame
+  let centre = Instance::new(Vector3::new(0.0, 0.0, 0.0), Quaternion::new(1.0, 0.0, 0.0, 0.0));
+  let before = pixel(&render(&mut state), WIDTH / 2, HEIGHT / 2);
+  state.update_instance(0, centre).unwrap();
+  let after = pixel(&render(&mut state), WIDTH / 2, HEIGHT / 2);
+  assert_ne!(before, after);
+}
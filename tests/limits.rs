@@ -0,0 +1,54 @@
+mod common;
+
+use sotrh::{Instance, Vertex};
+
+// zeroed with calloc, so the pages are never touched: the checks fail
+// before anything reads the data
+fn oversized_vertices(state: &sotrh::State) -> Vec<u32> {
+  let max = state.device().limits().max_buffer_size as usize;
+  let vertices = max / std::mem::size_of::<Vertex>() + 1;
+  vec![0u32; vertices * std::mem::size_of::<Vertex>() / 4]
+}
+
+#[test]
+fn oversized_vertex_data_is_an_error() {
+  let Some(mut state) = common::headless() else { return };
+  let data = oversized_vertices(&state);
+  let vertices: &[Vertex] = bytemuck::cast_slice(&data);
+  let before = (state.num_vertices(), state.num_indices(), state.vertex_buffer_size());
+
+  let err = state.set_vertices(vertices).expect_err("over max_buffer_size");
+  assert_eq!(err.what, "Vertex Buffer");
+  assert_eq!(err.limit, state.device().limits().max_buffer_size);
+  assert!(err.requested > err.limit);
+  assert!(state.update_vertices(vertices).is_err());
+  assert!(state.set_indexed_mesh(vertices, &[0u16, 1, 2]).is_err());
+  // nothing changed, the last frame still draws the old mesh
+  assert_eq!((state.num_vertices(), state.num_indices(), state.vertex_buffer_size()), before);
+  common::render(&mut state);
+}
+
+#[test]
+fn oversized_index_data_leaves_the_vertices_alone() {
+  let Some(mut state) = common::headless() else { return };
+  let max = state.device().limits().max_buffer_size as usize;
+  let indices = vec![0u32; max / 4 + 1];
+  let vertices = [Vertex::new([0.0; 3], [1.0; 3]); 3];
+  let before = (state.num_vertices(), state.num_indices());
+
+  let err = state.set_indexed_mesh(&vertices, &indices).expect_err("over max_buffer_size");
+  assert_eq!(err.what, "Index Buffer");
+  assert_eq!((state.num_vertices(), state.num_indices()), before);
+}
+
+#[test]
+fn oversized_instance_data_is_an_error() {
+  let Some(mut state) = common::headless() else { return };
+  let max = state.device().limits().max_buffer_size as usize;
+  // InstanceRaw is a 4x4 f32 matrix
+  let instances = vec![Instance::default(); max / 64 + 1];
+
+  let err = state.set_instances(&instances).expect_err("over max_buffer_size");
+  assert_eq!(err.what, "Instance Buffer");
+  assert_eq!(state.num_instances(), 1);
+}
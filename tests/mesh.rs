@@ -0,0 +1,177 @@
+mod common;
+
+use cgmath::{InnerSpace, Vector3};
+use sotrh::mesh::{self, NormalMode, WindingDetection, WindingPolicy};
+use sotrh::Vertex;
+
+// unit cube around the origin, counter-clockwise seen from outside
+const CUBE_POSITIONS: [[f32; 3]; 8] = [
+  [-1.0, -1.0, -1.0],
+  [1.0, -1.0, -1.0],
+  [1.0, 1.0, -1.0],
+  [-1.0, 1.0, -1.0],
+  [-1.0, -1.0, 1.0],
+  [1.0, -1.0, 1.0],
+  [1.0, 1.0, 1.0],
+  [-1.0, 1.0, 1.0],
+];
+
+const CUBE_INDICES: [u32; 36] = [
+  4, 5, 6, 4, 6, 7, // +z
+  1, 0, 3, 1, 3, 2, // -z
+  5, 1, 2, 5, 2, 6, // +x
+  0, 4, 7, 0, 7, 3, // -x
+  7, 6, 2, 7, 2, 3, // +y
+  0, 1, 5, 0, 5, 4, // -y
+];
+
+// latitude/longitude sphere of radius 2 with a single vertex at each pole,
+// counter-clockwise seen from outside
+fn sphere(rings: u32, segments: u32) -> (Vec<[f32; 3]>, Vec<u32>) {
+  let mut positions = vec![[0.0, 2.0, 0.0]];
+  for ring in 1..rings {
+    let theta = std::f32::consts::PI * ring as f32 / rings as f32;
+    for segment in 0..segments {
+      let phi = std::f32::consts::TAU * segment as f32 / segments as f32;
+      positions.push([2.0 * theta.sin() * phi.cos(), 2.0 * theta.cos(), 2.0 * theta.sin() * phi.sin()]);
+    }
+  }
+  positions.push([0.0, -2.0, 0.0]);
+
+  let bottom = positions.len() as u32 - 1;
+  let ring_start = |ring: u32| 1 + (ring - 1) * segments;
+  let mut indices = Vec::new();
+  for segment in 0..segments {
+    let next = (segment + 1) % segments;
+    indices.extend([0, ring_start(1) + next, ring_start(1) + segment]);
+    let last = ring_start(rings - 1);
+    indices.extend([bottom, last + segment, last + next]);
+    for ring in 1..rings - 1 {
+      let (a, b) = (ring_start(ring), ring_start(ring + 1));
+      indices.extend([a + segment, a + next, b + segment, a + next, b + next, b + segment]);
+    }
+  }
+  (positions, indices)
+}
+
+#[test]
+fn flat_cube_normals_are_axis_aligned() {
+  let mesh = mesh::compute_normals(&CUBE_POSITIONS, &CUBE_INDICES, NormalMode::Flat);
+
+  // four corners per face, the two triangles of a face share theirs
+  assert_eq!(mesh.positions.len(), 24);
+  assert_eq!(mesh.normals.len(), 24);
+  assert_eq!(mesh.indices.len(), 36);
+  for (i, (position, normal)) in mesh.positions.iter().zip(&mesh.normals).enumerate() {
+    assert_eq!(*position, CUBE_POSITIONS[mesh.source_vertices[i] as usize]);
+    // exactly one non-zero component, pointing away from the center
+    let axes = normal.iter().filter(|n| **n != 0.0).count();
+    assert_eq!(axes, 1, "normal {:?} isn't axis-aligned", normal);
+    assert!(Vector3::from(*normal).dot(Vector3::from(*position)) > 0.0, "normal {:?} points inwards", normal);
+  }
+  for triangle in mesh.indices.chunks(3) {
+    let [a, b, c] = [triangle[0], triangle[1], triangle[2]].map(|i| mesh.normals[i as usize]);
+    assert!(a == b && b == c, "triangle {:?} mixes normals", triangle);
+  }
+}
+
+#[test]
+fn smooth_sphere_normals_point_outwards() {
+  let (positions, indices) = sphere(24, 48);
+  let mesh = mesh::compute_normals(&positions, &indices, NormalMode::Smooth);
+
+  assert_eq!(mesh.positions.len(), positions.len());
+  assert_eq!(mesh.indices, indices);
+  for (position, normal) in positions.iter().zip(&mesh.normals) {
+    let expected = Vector3::from(*position).normalize();
+    let normal = Vector3::from(*normal);
+    assert!((normal.magnitude() - 1.0).abs() < 1e-5);
+    assert!(normal.dot(expected) > 0.999, "normal {:?} at {:?}", normal, position);
+  }
+}
+
+#[test]
+fn small_triangles_are_kept() {
+  let positions = [[0.0, 0.0, 0.0], [1e-3, 0.0, 0.0], [0.0, 1e-3, 0.0]];
+  let mesh = mesh::compute_normals(&positions, &[0, 1, 2], NormalMode::Flat);
+  assert_eq!(mesh.indices.len(), 3);
+  assert_eq!(mesh.normals[0], [0.0, 0.0, 1.0]);
+}
+
+#[test]
+fn degenerate_triangles_are_skipped_without_nans() {
+  // collapsed to a point, and collinear
+  let positions = [[0.0, 0.0, 0.0], [0.0, 0.0, 0.0], [0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [2.0, 0.0, 0.0]];
+  let indices = [0, 1, 2, 0, 3, 4];
+
+  assert!(mesh::compute_normals(&positions, &indices, NormalMode::Flat).indices.is_empty());
+  let smooth = mesh::compute_normals(&positions, &indices, NormalMode::Smooth);
+  assert!(smooth.normals.iter().flatten().all(|n| n.is_finite()));
+}
+
+fn clockwise_cube() -> Vec<u32> {
+  CUBE_INDICES.chunks_exact(3).flat_map(|triangle| [triangle[0], triangle[2], triangle[1]]).collect()
+}
+
+#[test]
+fn clockwise_cube_is_flipped_by_volume() {
+  let mut indices = clockwise_cube();
+  let report = mesh::fix_winding(&CUBE_POSITIONS, None, &mut indices, WindingPolicy::AutoDetect);
+  assert!(report.flipped);
+  assert!(matches!(report.detection, WindingDetection::SignedVolume(volume) if volume < 0.0));
+  assert_eq!(indices, CUBE_INDICES);
+}
+
+#[test]
+fn clockwise_cube_is_flipped_by_normals() {
+  // outward corner normals disagree with every clockwise face
+  let normals = CUBE_POSITIONS.map(|p| Vector3::from(p).normalize().into());
+  let mut indices = clockwise_cube();
+  let report = mesh::fix_winding(&CUBE_POSITIONS, Some(&normals), &mut indices, WindingPolicy::AutoDetect);
+  assert!(report.flipped);
+  assert_eq!(report.detection, WindingDetection::Normals { disagreeing: 12, total: 12 });
+  assert_eq!(indices, CUBE_INDICES);
+}
+
+// Under the default back-face culling a clockwise cube only shows its
+// inside. The far (-z) face is left out, so nothing covers the centre of the
+// frame until the near face is wound the right way.
+#[test]
+fn fixed_clockwise_cube_becomes_visible() {
+  let Some(mut state) = common::headless() else { return };
+  let vertices = CUBE_POSITIONS.map(|[x, y, z]| Vertex::new([x * 0.5, y * 0.5, z * 0.5], [1.0, 0.0, 0.0]));
+  let mut indices: Vec<u32> = clockwise_cube();
+  indices.drain(6..12);
+  let center = |state: &mut sotrh::State, indices: &[u32]| {
+    state.set_indexed_mesh(&vertices, indices).unwrap();
+    let pixels = common::render(state);
+    (common::pixel(&pixels, common::WIDTH / 2, common::HEIGHT / 2), common::pixel(&pixels, 0, 0))
+  };
+
+  let (before, clear) = center(&mut state, &indices);
+  assert_eq!(before, clear);
+
+  let report = mesh::fix_winding(&CUBE_POSITIONS, None, &mut indices, WindingPolicy::AutoDetect);
+  assert!(report.flipped);
+  let (after, _) = center(&mut state, &indices);
+  common::assert_close(after, [255, 0, 0, 255], 0);
+}
+
+#[test]
+fn counter_clockwise_cube_is_kept() {
+  let mut indices = CUBE_INDICES.to_vec();
+  let report = mesh::fix_winding(&CUBE_POSITIONS, None, &mut indices, WindingPolicy::AutoDetect);
+  assert!(!report.flipped);
+  assert_eq!(indices, CUBE_INDICES);
+
+  let report = mesh::fix_winding(&CUBE_POSITIONS, None, &mut indices, WindingPolicy::Keep);
+  assert_eq!(report.detection, WindingDetection::NotChecked);
+  assert_eq!(indices, CUBE_INDICES);
+}
+
+#[test]
+#[should_panic(expected = "texture coordinates per position")]
+fn tangents_need_tex_coords_per_position() {
+  let normals = [[0.0, 0.0, 1.0]; 8];
+  mesh::compute_tangents(&CUBE_POSITIONS, &normals, &[[0.0, 0.0]; 3], &CUBE_INDICES);
+}
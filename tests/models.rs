@@ -0,0 +1,132 @@
+#![cfg(feature = "models")]
+
+use sotrh::load::{CancellationToken, LoadContext};
+use sotrh::mesh::WindingPolicy;
+use sotrh::{Mesh, ModelError};
+
+// the unit cube from tests/mesh.rs, wound clockwise seen from outside
+const CLOCKWISE_CUBE: &str = "
+v -1 -1 -1
+v 1 -1 -1
+v 1 1 -1
+v -1 1 -1
+v -1 -1 1
+v 1 -1 1
+v 1 1 1
+v -1 1 1
+f 5 7 6
+f 5 8 7
+f 2 4 1
+f 2 3 4
+f 6 3 2
+f 6 7 3
+f 1 8 5
+f 1 4 8
+f 8 3 7
+f 8 4 3
+f 1 6 2
+f 1 5 6
+";
+
+fn outward_normals(mesh: &Mesh) -> bool {
+  mesh.vertices.iter().all(|vertex| {
+    let [x, y, z] = vertex.position;
+    let [nx, ny, nz] = vertex.normal;
+    x * nx + y * ny + z * nz > 0.0
+  })
+}
+
+#[test]
+fn auto_detect_fixes_a_clockwise_obj() {
+  let mesh = Mesh::from_obj_with(CLOCKWISE_CUBE.as_bytes(), WindingPolicy::AutoDetect, &LoadContext::new()).unwrap();
+  assert!(mesh.winding.unwrap().flipped);
+  // computed after the flip, so they point outwards
+  assert!(outward_normals(&mesh));
+}
+
+#[test]
+fn keep_leaves_a_clockwise_obj_alone() {
+  let mesh = Mesh::from_obj(CLOCKWISE_CUBE.as_bytes()).unwrap();
+  assert!(!mesh.winding.unwrap().flipped);
+  assert!(!outward_normals(&mesh));
+}
+
+#[test]
+fn obj_quad_with_relative_indices() {
+  let mesh = Mesh::from_obj(&include_bytes!("assets/quad.obj")[..]).unwrap();
+  assert_eq!(mesh.name.as_deref(), Some("quad"));
+  assert_eq!(mesh.vertices.len(), 4);
+  // the quad face is split in two
+  assert_eq!(mesh.indices.len(), 6);
+  for vertex in &mesh.vertices {
+    assert_eq!(vertex.tex_coords, [0.0, 0.0]);
+    assert!((vertex.normal[2] - 1.0).abs() < 1e-6, "{:?}", vertex.normal);
+  }
+}
+
+#[test]
+fn glb_with_two_primitives() {
+  let meshes = Mesh::from_gltf(include_bytes!("assets/two_primitives.glb")).unwrap();
+  let counts: Vec<_> = meshes.iter().map(|mesh| (mesh.vertices.len(), mesh.indices.len())).collect();
+  // a triangle without indices, then an indexed quad
+  assert_eq!(counts, [(3, 3), (4, 6)]);
+  assert!(meshes.iter().all(|mesh| mesh.name.as_deref() == Some("pair")));
+}
+
+#[test]
+fn out_of_range_indices_are_an_error() {
+  let mut glb = include_bytes!("assets/two_primitives.glb").to_vec();
+  // the quad's u16 indices follow the 7 positions in the binary chunk
+  let json_len = u32::from_le_bytes(glb[12..16].try_into().unwrap()) as usize;
+  let indices = 12 + 8 + json_len + 8 + 7 * 12;
+  glb[indices + 2..indices + 4].copy_from_slice(&9u16.to_le_bytes());
+  match Mesh::from_gltf(&glb) {
+    Err(ModelError::IndexOutOfRange { index: 9, vertices: 4 }) => {}
+    other => panic!("expected IndexOutOfRange, got {:?}", other),
+  }
+}
+
+#[test]
+fn progress_and_cancellation() {
+  let stages = std::cell::RefCell::new(Vec::new());
+  let context = LoadContext::new().with_progress(|stage, _| stages.borrow_mut().push(stage.to_string()));
+  Mesh::from_gltf_with(include_bytes!("assets/two_primitives.glb"), WindingPolicy::Keep, &context).unwrap();
+  assert!(stages.borrow().contains(&"normals".to_string()));
+
+  let token = CancellationToken::new();
+  token.cancel();
+  let context = LoadContext::new().with_cancellation(token);
+  let result = Mesh::from_obj_with(&include_bytes!("assets/quad.obj")[..], WindingPolicy::Keep, &context);
+  assert!(matches!(result, Err(ModelError::Cancelled(_))));
+}
+
+#[test]
+fn gltf_tangents_are_kept() {
+  let meshes = Mesh::from_gltf(include_bytes!("assets/tangents.glb")).unwrap();
+  // computing them from the UVs would give +X
+  for vertex in &meshes[0].vertices {
+    assert_eq!(vertex.tangent, [0.0, 1.0, 0.0, -1.0]);
+  }
+}
+
+#[test]
+fn obj_tangents_follow_u() {
+  let obj = "
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+f 1/1 2/2 3/3 4/4
+";
+  let mesh = Mesh::from_obj(obj.as_bytes()).unwrap();
+  for vertex in &mesh.vertices {
+    let [x, y, z, _] = vertex.tangent;
+    assert!((x - 1.0).abs() < 1e-6 && y.abs() < 1e-6 && z.abs() < 1e-6, "{:?}", vertex.tangent);
+    // v is flipped while loading, so the bitangent points down
+    assert_eq!(vertex.tangent[3], -1.0);
+  }
+}
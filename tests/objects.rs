@@ -0,0 +1,78 @@
+mod common;
+
+use common::{assert_close, pixel, quad, render, QUAD_INDICES, HEIGHT, WIDTH};
+use sotrh::{LodGroup, LodStats, Vertex};
+
+const RED: [u8; 4] = [255, 0, 0, 255];
+const GREEN: [u8; 4] = [0, 255, 0, 255];
+
+fn translation(x: f32, y: f32) -> [[f32; 4]; 4] {
+  cgmath::Matrix4::from_translation(cgmath::Vector3::new(x, y, 0.0)).into()
+}
+
+#[test]
+fn objects_draw_with_their_own_transform() {
+  let Some(mut state) = common::headless() else { return };
+  state.set_vertices(&[]).unwrap();
+  let id = state.create_object(&quad(0.1, 0.0, [1.0, 0.0, 0.0]), &QUAD_INDICES).unwrap();
+  assert_eq!(state.num_objects(), 1);
+  let pixels = render(&mut state);
+  assert_close(pixel(&pixels, WIDTH / 2, HEIGHT / 2), RED, 0);
+
+  // moved to the right half of the frame
+  assert!(state.set_object_transform(id, translation(0.5, 0.0)));
+  let pixels = render(&mut state);
+  assert_ne!(pixel(&pixels, WIDTH / 2, HEIGHT / 2), RED);
+  assert_close(pixel(&pixels, WIDTH / 2 + 19, HEIGHT / 2), RED, 0);
+
+  assert!(state.remove_object(id));
+  assert!(!state.set_object_transform(id, translation(0.0, 0.0)));
+  assert_eq!(common::covered(&render(&mut state)), 0);
+}
+
+#[test]
+fn oversized_objects_are_an_error() {
+  let Some(mut state) = common::headless() else { return };
+  let max = state.device().limits().max_buffer_size as usize;
+  // zeroed with calloc and never read, the check comes first
+  let data = vec![0u32; (max / std::mem::size_of::<Vertex>() + 1) * std::mem::size_of::<Vertex>() / 4];
+  let vertices: &[Vertex] = bytemuck::cast_slice(&data);
+
+  let err = state.create_object(vertices, &[] as &[u16]).expect_err("over max_buffer_size");
+  assert_eq!(err.what, "Object Vertex Buffer");
+  let indices = vec![0u32; max / 4 + 1];
+  let err = state.create_object(&quad(0.1, 0.0, [1.0; 3]), &indices).expect_err("over max_buffer_size");
+  assert_eq!(err.what, "Object Index Buffer");
+  assert_eq!(state.num_objects(), 0);
+}
+
+#[test]
+fn lod_objects_switch_with_camera_distance() {
+  let Some(mut state) = common::headless() else { return };
+  state.set_vertices(&[]).unwrap();
+  let mesh = |color| (quad(0.1, 0.0, color).to_vec(), QUAD_INDICES.map(u32::from).to_vec());
+  let lod = LodGroup::new()
+    .with_level(mesh([1.0, 0.0, 0.0]), 3.0)
+    .with_level(mesh([0.0, 1.0, 0.0]), 6.0);
+  let id = state.create_lod_object(&lod).unwrap();
+  let at_depth = |z: f32| -> [[f32; 4]; 4] { cgmath::Matrix4::from_translation(cgmath::Vector3::new(0.0, 0.0, z)).into() };
+
+  // the camera is 2 away
+  let pixels = render(&mut state);
+  assert_close(pixel(&pixels, WIDTH / 2, HEIGHT / 2), RED, 0);
+  assert_eq!(state.lod_stats(), &LodStats { drawn_per_level: vec![1], culled: 0 });
+
+  state.set_object_transform(id, at_depth(-2.0));
+  let pixels = render(&mut state);
+  assert_close(pixel(&pixels, WIDTH / 2, HEIGHT / 2), GREEN, 0);
+  assert_eq!(state.lod_stats(), &LodStats { drawn_per_level: vec![0, 1], culled: 0 });
+
+  state.set_object_transform(id, at_depth(-10.0));
+  assert_eq!(common::covered(&render(&mut state)), 0);
+  assert_eq!(state.lod_stats().culled, 1);
+
+  // forcing a level ignores the distance
+  assert!(state.force_object_lod(id, Some(0)));
+  let pixels = render(&mut state);
+  assert_close(pixel(&pixels, WIDTH / 2, HEIGHT / 2), RED, 0);
+}
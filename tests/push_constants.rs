@@ -0,0 +1,42 @@
+mod common;
+
+use common::{assert_close, pixel, quad, render, srgb, HEIGHT, QUAD_INDICES, WIDTH};
+use sotrh::ShaderError;
+
+// shader.wgsl with the fragment color multiplied by a push constant tint
+fn tinted_shader(push_constants: &str) -> String {
+  let shader = include_str!("../src/shader.wgsl");
+  let fragment = shader.find("@fragment").unwrap();
+  format!(
+    "{}\n{}\n@fragment\nfn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {{\n  return encode_output(vec4<f32>(in.color * tint(), 1.0));\n}}\n",
+    &shader[..fragment],
+    push_constants,
+  )
+}
+
+#[test]
+fn push_constants_tint_the_draw() {
+  let Some(mut state) = common::headless() else { return };
+  let shader = tinted_shader(
+    "struct Tint { color: vec4<f32> };\nvar<push_constant> pc: Tint;\nfn tint() -> vec3<f32> { return pc.color.rgb; }",
+  );
+  state.load_shader(&shader).unwrap();
+  state.set_indexed_mesh(&quad(0.5, 0.0, [1.0, 1.0, 1.0]), &QUAD_INDICES).unwrap();
+  state.set_push_constants(bytemuck::cast_slice(&[0.5f32, 0.25, 1.0, 1.0])).unwrap();
+  let pixels = render(&mut state);
+  assert_close(pixel(&pixels, WIDTH / 2, HEIGHT / 2), [srgb(0.5), srgb(0.25), srgb(1.0), 255], 1);
+}
+
+// valid as a push constant, but an f32 array has a 4 byte stride, which a
+// uniform buffer doesn't allow; refused on every device, not just WebGL
+#[test]
+fn types_that_break_uniform_layout_are_refused() {
+  let Some(mut state) = common::headless() else { return };
+  let shader = tinted_shader(
+    "struct Tint { color: array<f32, 3> };\nvar<push_constant> pc: Tint;\nfn tint() -> vec3<f32> { return vec3<f32>(pc.color[0], pc.color[1], pc.color[2]); }",
+  );
+  match state.load_shader(&shader) {
+    Err(ShaderError::PushConstantLayout(message)) => assert!(message.contains("stride"), "{}", message),
+    other => panic!("expected PushConstantLayout, got {:?}", other),
+  }
+}
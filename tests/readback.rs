@@ -0,0 +1,36 @@
+mod common;
+
+use sotrh::ReadbackRing;
+use wgpu::util::DeviceExt;
+
+// results arrive through poll_readbacks() while frames keep rendering,
+// without anything waiting on the GPU
+#[test]
+fn readbacks_arrive_while_rendering() {
+  let Some(mut state) = common::headless() else { return };
+  let values: Vec<u32> = (0..64).collect();
+  let source = state.device().create_buffer_init(&wgpu::util::BufferInitDescriptor {
+    label: Some("Readback Source"),
+    contents: bytemuck::cast_slice(&values),
+    usage: wgpu::BufferUsages::COPY_SRC,
+  });
+  let mut ring = ReadbackRing::<u32>::new(state.device(), 2, 256).unwrap().tracked_by(state.map_tracker());
+
+  let mut encoder = state.device().create_command_encoder(&Default::default());
+  let id = ring.request_buffer(&mut encoder, &source, 0, 256).unwrap();
+  state.queue().submit(std::iter::once(encoder.finish()));
+  ring.after_submit();
+
+  let mut received = None;
+  for _ in 0..100 {
+    state.render().unwrap();
+    if let Some(readback) = state.poll_readbacks(&mut ring).next() {
+      received = Some(readback);
+      break;
+    }
+  }
+  let readback = received.expect("the readback never arrived");
+  assert_eq!(readback.id, id);
+  assert_eq!(readback.data, values);
+  assert_eq!(ring.in_flight(), 0);
+}
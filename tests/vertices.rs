@@ -0,0 +1,69 @@
+mod common;
+
+use common::{assert_close, pixel, quad_triangles, render, HEIGHT, WIDTH};
+use sotrh::Vertex;
+
+const GREEN: [u8; 4] = [0, 255, 0, 255];
+
+#[test]
+fn set_vertices_swaps_in_a_quad() {
+  let Some(mut state) = common::headless() else { return };
+  let quad = quad_triangles(0.5, 0.0, [0.0, 1.0, 0.0]);
+  state.set_vertices(&quad).unwrap();
+
+  assert_eq!(state.num_vertices(), 6);
+  assert_eq!(state.num_indices(), None);
+  assert!(state.vertex_buffer_size() >= std::mem::size_of_val(&quad[..]) as u64);
+
+  let pixels = render(&mut state);
+  assert_close(pixel(&pixels, WIDTH / 2, HEIGHT / 2), GREEN, 0);
+  // both triangles, on either side of the diagonal
+  assert_close(pixel(&pixels, WIDTH / 2 - 10, HEIGHT / 2 + 10), GREEN, 0);
+  assert_close(pixel(&pixels, WIDTH / 2 + 10, HEIGHT / 2 - 10), GREEN, 0);
+  assert_ne!(pixel(&pixels, 2, 2), GREEN);
+}
+
+#[test]
+fn smaller_data_reuses_the_buffer() {
+  let Some(mut state) = common::headless() else { return };
+  state.set_vertices(&quad_triangles(0.5, 0.0, [0.0, 1.0, 0.0])).unwrap();
+  let size = state.vertex_buffer_size();
+  state.set_vertices(&quad_triangles(0.5, 0.0, [0.0, 1.0, 0.0])[..3]).unwrap();
+  assert_eq!(state.num_vertices(), 3);
+  assert_eq!(state.vertex_buffer_size(), size);
+}
+
+#[test]
+fn zero_vertices_skip_the_draw() {
+  let Some(mut state) = common::headless() else { return };
+  state.update_vertices(&[]).unwrap();
+  assert_eq!(state.num_vertices(), 0);
+  assert_eq!(common::covered(&render(&mut state)), 0);
+}
+
+// procedural geometry that changes and grows a little every frame
+#[test]
+fn per_frame_updates_reallocate_rarely() {
+  let Some(mut state) = common::headless() else { return };
+  let mut sizes = Vec::new();
+  for frame in 0..100 {
+    let count = 10_000 + frame * 30;
+    let vertices: Vec<Vertex> = (0..count).map(|i| {
+      let t = (i + frame) as f32 * 0.001;
+      Vertex::new([t.sin() * 0.5, t.cos() * 0.5, 0.0], [1.0, 1.0, 1.0])
+    }).collect();
+    state.update_vertices(&vertices).unwrap();
+    assert_eq!(state.num_vertices(), count as u32);
+    assert!(state.vertex_buffer_size() >= (count * std::mem::size_of::<Vertex>()) as u64);
+    state.render().unwrap();
+    sizes.push(state.vertex_buffer_size());
+  }
+
+  // 10000 to 12970 vertices is within one 1.5x growth step
+  sizes.dedup();
+  assert!(sizes.len() <= 2, "reallocated {} times: {:?}", sizes.len(), sizes);
+
+  // shrinking keeps the allocation
+  state.update_vertices(&[Vertex::new([0.0; 3], [1.0; 3]); 3]).unwrap();
+  assert_eq!(state.vertex_buffer_size(), *sizes.last().unwrap());
+}